@@ -0,0 +1,194 @@
+//! Frame times with and without checking for errors after each call.
+//!
+//! The scene is rendered offscreen, so this runs without a display.
+//! With per-call polling, [`try_gl`] calls `glGetError`
+//! after each OpenGL call; with per-frame polling it does not,
+//! and errors are checked once at the end of each frame.
+//! Per-call polling is turned off at run time here,
+//! which is equivalent to the `elide-gl-error-polling` feature
+//! except for a single atomic load per call.
+//! Running with that feature measures only per-frame polling.
+//!
+//! Run with `cargo bench --bench gl_error_polling`.
+//!
+//! [`try_gl`]: blok::try_gl
+
+#[cfg(target_os = "linux")]
+fn main() -> anyhow::Result<()>
+{
+    let context = blok::client::graphics::offscreen::OffscreenContext::new()?;
+    bench::run(context.gl())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn main()
+{
+    eprintln!("Offscreen rendering is only supported on Linux.");
+}
+
+#[cfg(target_os = "linux")]
+mod bench
+{
+    use anyhow::Result;
+    use blok::{
+        client::graphics::{
+            GlBuffer,
+            GlContext,
+            POLL_GL_ERRORS_PER_CALL,
+            check_frame_gl_errors,
+            offscreen::OffscreenFramebuffer,
+            renderer::{Camera, Renderer, Scene},
+            set_gl_error_polling,
+            shadow::Perspective,
+            sky::DayNightCycle,
+            trivial_block,
+        },
+        state::{Block, CHUNK_SIZE, Chunk, Tick, World},
+    };
+    use glam::{IVec3, Mat4, Vec3, ivec2, ivec3};
+    use opengl::gl;
+    use std::{f32::consts::PI, time::{Duration, Instant}};
+
+    /// Size of the framebuffer, in pixels.
+    const WIDTH: i32 = 640;
+    const HEIGHT: i32 = 480;
+
+    /// Number of chunks along each horizontal dimension of the terrain.
+    const TERRAIN_CHUNKS: i32 = 4;
+
+    /// Number of frames drawn before and during measurement.
+    const WARM_UP_FRAMES: usize = 20;
+    const MEASURED_FRAMES: usize = 200;
+
+    pub fn run(context: &GlContext) -> Result<()>
+    {
+        let target = OffscreenFramebuffer::new(context, WIDTH, HEIGHT)?;
+        let mut renderer = Renderer::new(context, 2048, ivec2(16, 8))?;
+
+        let world = make_world();
+        let face_sets =
+            chunk_positions()
+            .map(|chunk_position| Ok(trivial_block::FaceSet{
+                faces: GlBuffer::new_upload(
+                    context,
+                    &trivial_block::mesh(&trivial_block::WorldVoxels{
+                        world: &world,
+                        chunk_position,
+                        texture: |_, face| (face as u16, 0),
+                    }),
+                    gl::STATIC_DRAW,
+                )?,
+                chunk_position,
+            }))
+            .collect::<Result<Vec<_>>>()?;
+
+        let extent = (TERRAIN_CHUNKS * CHUNK_SIZE) as f32;
+        let camera = Camera{
+            v_matrix: Mat4::look_at_rh(
+                /* eye    */ Vec3::new(-8.0, -8.0, 24.0),
+                /* center */ Vec3::new(extent / 2.0, extent / 2.0, 0.0),
+                /* up     */ Vec3::new(0.0, 0.0, 1.0),
+            ),
+            perspective: Perspective{
+                fov_y_radians: PI / 4.0,
+                aspect_ratio: WIDTH as f32 / HEIGHT as f32,
+                z_near: 1.0,
+                z_far: 150.0,
+            },
+        };
+
+        let scene = Scene{
+            lighting: DayNightCycle{ticks_per_day: 3600}.lighting(Tick(600)),
+            generic_models: &[],
+            trivial_block_face_sets: &face_sets,
+        };
+
+        let mut draw_frame = || -> Result<()> {
+            renderer.draw(
+                /* target        */ Some(&target.framebuffer),
+                /* viewport_size */ ivec2(WIDTH, HEIGHT),
+                /* camera        */ &camera,
+                /* scene         */ &scene,
+            )?;
+            check_frame_gl_errors(context)?;
+
+            // Include the time the driver takes to execute the frame.
+            // SAFETY: The context is current.
+            unsafe {
+                gl::Finish();
+            }
+            Ok(())
+        };
+
+        if POLL_GL_ERRORS_PER_CALL {
+            set_gl_error_polling(true);
+            report("per-call polling", measure(&mut draw_frame)?);
+        } else {
+            println!("per-call polling: elided at compile time");
+        }
+
+        set_gl_error_polling(false);
+        report("per-frame polling", measure(&mut draw_frame)?);
+
+        Ok(())
+    }
+
+    /// Draw frames and return the sorted frame times.
+    fn measure<F>(draw_frame: &mut F) -> Result<Vec<Duration>>
+        where F: FnMut() -> Result<()>
+    {
+        for _ in 0 .. WARM_UP_FRAMES {
+            draw_frame()?;
+        }
+        let mut frame_times = Vec::with_capacity(MEASURED_FRAMES);
+        for _ in 0 .. MEASURED_FRAMES {
+            let start = Instant::now();
+            draw_frame()?;
+            frame_times.push(start.elapsed());
+        }
+        frame_times.sort();
+        Ok(frame_times)
+    }
+
+    fn report(name: &str, frame_times: Vec<Duration>)
+    {
+        let millis = |d: Duration| d.as_secs_f64() * 1000.0;
+        let total: Duration = frame_times.iter().sum();
+        println!(
+            "{}: mean {:.3} ms, median {:.3} ms, p95 {:.3} ms",
+            name,
+            millis(total) / frame_times.len() as f64,
+            millis(frame_times[frame_times.len() / 2]),
+            millis(frame_times[frame_times.len() * 95 / 100]),
+        );
+    }
+
+    /// Rolling hills of stone, so that there are many faces to draw.
+    fn make_world() -> World
+    {
+        let mut world = World::new();
+        for chunk_position in chunk_positions() {
+            let mut chunk = Chunk::new();
+            for x in 0 .. CHUNK_SIZE {
+                for y in 0 .. CHUNK_SIZE {
+                    let position = chunk_position * CHUNK_SIZE + ivec3(x, y, 0);
+                    let height = 4.0
+                        + 3.0 * (position.x as f32 * 0.3).sin()
+                        + 3.0 * (position.y as f32 * 0.2).cos();
+                    for z in 0 ..= height as i32 {
+                        chunk.set_block(ivec3(x, y, z), Block::Stone);
+                    }
+                }
+            }
+            world.insert_chunk(chunk_position, chunk);
+        }
+        world
+    }
+
+    fn chunk_positions() -> impl Iterator<Item=IVec3>
+    {
+        (0 .. TERRAIN_CHUNKS).flat_map(|x| {
+            (0 .. TERRAIN_CHUNKS).map(move |y| ivec3(x, y, 0))
+        })
+    }
+}
@@ -0,0 +1,116 @@
+//! Bytes per snapshot of a crowd of moving mobs.
+//!
+//! Compares full snapshots, delta snapshots against a baseline
+//! that the client acknowledges a few ticks late,
+//! and the serialized entities without any compression.
+//! Some mobs stand still, some walk in circles,
+//! and every so often one dies and another spawns.
+//!
+//! Run with `cargo bench --bench snapshot_bandwidth`.
+
+use blok::{
+    net::SnapshotEncoder,
+    state::{
+        BoundingBox,
+        Entities,
+        EntityId,
+        Health,
+        ModelId,
+        Schedule,
+        Tick,
+        Transform,
+        Velocity,
+    },
+};
+use glam::{Quat, vec3};
+
+/// Number of mobs in the crowd.
+const MOBS: usize = 200;
+
+/// Number of snapshots sent during measurement.
+const TICKS: u64 = 600;
+
+/// Round-trip time, in ticks, after which snapshots are acknowledged.
+const ACKNOWLEDGE_DELAY: u64 = 3;
+
+fn main()
+{
+    let mut entities = Entities::new();
+    for i in 0 .. MOBS {
+        spawn_mob(&mut entities, i);
+    }
+
+    let mut schedule = Schedule::standard();
+    let mut full_encoder = SnapshotEncoder::new();
+    let mut delta_encoder = SnapshotEncoder::new();
+    let (mut full, mut delta, mut uncompressed) = (0, 0, 0);
+
+    for tick in 0 .. TICKS {
+        steer(&mut entities, tick);
+        schedule.run(Tick(tick), &mut entities);
+
+        // Never acknowledged, so always encoded in full.
+        full += full_encoder.encode(Tick(tick), &entities).len();
+
+        delta += delta_encoder.encode(Tick(tick), &entities).len();
+        if let Some(acknowledged) = tick.checked_sub(ACKNOWLEDGE_DELAY) {
+            delta_encoder.acknowledge(Tick(acknowledged));
+        }
+
+        uncompressed += bincode::serialize(&entities).unwrap().len();
+    }
+
+    let report = |name: &str, total: usize| {
+        let per_snapshot = total as f64 / TICKS as f64;
+        println!(
+            "{}: {:.1} bytes per snapshot, {:.2} bytes per mob",
+            name,
+            per_snapshot,
+            per_snapshot / MOBS as f64,
+        );
+    };
+    report("bincode", uncompressed);
+    report("full", full);
+    report("delta", delta);
+}
+
+fn spawn_mob(entities: &mut Entities, i: usize) -> EntityId
+{
+    let id = entities.spawn();
+    entities.insert(id, Transform{
+        position: vec3((i % 20) as f32 * 3.0, (i / 20) as f32 * 3.0, 64.0),
+        orientation: Quat::IDENTITY,
+    });
+    entities.insert(id, Velocity(vec3(0.0, 0.0, 0.0)));
+    entities.insert(id, BoundingBox{
+        min: vec3(-0.3, -0.3, 0.0),
+        max: vec3(0.3, 0.3, 1.8),
+    });
+    entities.insert(id, Health{current: 20, maximum: 20});
+    entities.insert(id, ModelId((i % 4) as u32));
+    id
+}
+
+/// Make every other mob walk in a circle, and replace a mob now and then.
+fn steer(entities: &mut Entities, tick: u64)
+{
+    let ids: Vec<EntityId> = entities.ids().collect();
+    for (i, &id) in ids.iter().enumerate() {
+        if i % 2 == 1 {
+            continue;
+        }
+        let angle = (tick as f32 + i as f32) * 0.05;
+        let direction = vec3(angle.cos(), angle.sin(), 0.0);
+        entities.insert(id, Velocity(direction * 0.1));
+        if let Some(transform) = entities.get_mut::<Transform>(id) {
+            transform.orientation = Quat::from_rotation_z(angle);
+        }
+    }
+
+    if tick % 10 == 0 {
+        if let Some(health) = entities.get_mut::<Health>(ids[0]) {
+            health.current = 0;
+        }
+        spawn_mob(entities, tick as usize);
+    }
+}
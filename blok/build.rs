@@ -1,6 +1,10 @@
-#![feature(exit_status_error)]
+use std::{env, fs::{create_dir_all, write}, path::Path};
 
-use std::{env, fs::create_dir_all, path::Path, process::Command};
+#[path = "build/compile.rs"]
+mod compile;
+
+#[path = "build/reflect.rs"]
+mod reflect;
 
 fn main()
 {
@@ -11,11 +15,12 @@ fn main()
 fn set_link_search()
 {
     // Use the library search path for the target operating system.
-    // The LIBRARIES_* environment variables are set by shell.nix.
+    // The LIBRARIES_* environment variables are set by shell.nix;
+    // outside nix-shell, the linker searches the system libraries.
     let target_family = env::var("CARGO_CFG_TARGET_OS").unwrap();
     let libraries_var = format!("LIBRARIES_{}", target_family);
     println!("cargo:rerun-if-env-changed={}", libraries_var);
-    let libraries = env::var(libraries_var).unwrap();
+    let libraries = env::var(libraries_var).unwrap_or_default();
     let libraries = libraries.split(';').filter(|s| !s.is_empty());
     for library in libraries {
         println!("cargo:rustc-link-search={}", library);
@@ -26,11 +31,15 @@ fn compile_shaders()
 {
     let shaders = &[
         ("frag", "client/graphics/generic/shader.frag"),
+        ("vert", "client/graphics/generic/instanced.vert"),
         ("vert", "client/graphics/generic/shader.vert"),
         ("vert", "client/graphics/trivial_block/shader.vert"),
     ];
 
+    // Shader hot-reloading compiles with the same options.
     let optimize = env::var("OPT_LEVEL").unwrap() != "0";
+    println!("cargo:rustc-env=BLOK_OPTIMIZE_SHADERS={}", optimize);
+
     let out_dir = env::var_os("OUT_DIR").unwrap();
     let out_dir = Path::new(&out_dir);
 
@@ -47,13 +56,18 @@ fn compile_shaders()
         create_dir_all(&spirv_dir).unwrap();
 
         // Compile GLSL into SPIR-V.
-        Command::new("glslc")
-            .arg(if optimize { "-O" } else { "-O0" })
-            .arg("--target-env=opengl4.5")
-            .arg("-o")
-            .arg(&spirv_path)
-            .arg(src_source_path)
-            .status().unwrap()
-            .exit_ok().unwrap();
+        let spirv = compile::compile_shader(kind, &src_source_path, optimize)
+            .unwrap_or_else(|err| {
+                panic!("Cannot compile {}:\n{}", src_source_path.display(), err)
+            });
+        write(&spirv_path, &spirv).unwrap();
+
+        // Generate Rust constants for the shader interface.
+        let interface = reflect::reflect(&spirv).unwrap_or_else(|err| {
+            panic!("Cannot reflect {}: {}", spirv_path.display(), err)
+        });
+        let mut interface_path = spirv_path.clone();
+        interface_path.set_extension("rs");
+        write(&interface_path, interface.to_rust()).unwrap();
     }
 }
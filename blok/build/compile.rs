@@ -0,0 +1,95 @@
+//! Compilation of GLSL into SPIR-V.
+//!
+//! This is used both by the build script and by shader hot-reloading,
+//! so that reloaded shaders are compiled exactly like embedded ones.
+
+use std::path::Path;
+
+#[cfg(not(feature = "in-process-shader-compiler"))]
+use std::process::Command;
+
+#[cfg(feature = "in-process-shader-compiler")]
+use std::fs::read_to_string;
+
+/// Compile GLSL into SPIR-V by running glslc.
+///
+/// On failure, returns the errors, prefixed with file and line.
+#[cfg(not(feature = "in-process-shader-compiler"))]
+pub fn compile_shader(_kind: &str, source_path: &Path, optimize: bool)
+    -> Result<Vec<u8>, String>
+{
+    // glslc writes the SPIR-V to standard output
+    // and errors with file and line to standard error.
+    // Reflection needs the names of variables,
+    // which the optimizer strips unless debug information is requested.
+    let output =
+        Command::new("glslc")
+        .arg(if optimize { "-O" } else { "-O0" })
+        .arg("-g")
+        .arg("--target-env=opengl4.5")
+        .arg("-o")
+        .arg("-")
+        .arg(source_path)
+        .output()
+        .map_err(|err| {
+            format!("Cannot run glslc: {}\n\
+                     Install shaderc, or enable the \
+                     in-process-shader-compiler feature.", err)
+        })?;
+    if !output.status.success() {
+        return Err(String::from_utf8_lossy(&output.stderr).into_owned());
+    }
+    Ok(output.stdout)
+}
+
+/// Compile GLSL into SPIR-V with the shaderc library.
+///
+/// On failure, returns the errors, prefixed with file and line.
+#[cfg(feature = "in-process-shader-compiler")]
+pub fn compile_shader(kind: &str, source_path: &Path, optimize: bool)
+    -> Result<Vec<u8>, String>
+{
+    use shaderc::{
+        CompileOptions,
+        Compiler,
+        EnvVersion,
+        OptimizationLevel,
+        ShaderKind,
+        TargetEnv,
+    };
+
+    let shader_kind = match kind {
+        "frag" => ShaderKind::Fragment,
+        "vert" => ShaderKind::Vertex,
+        _ => return Err(format!("Unknown shader kind {}", kind)),
+    };
+
+    let mut options = CompileOptions::new()
+        .ok_or("Cannot create shaderc options")?;
+    options.set_target_env(TargetEnv::OpenGL, EnvVersion::OpenGL4_5 as u32);
+    options.set_optimization_level(
+        if optimize { OptimizationLevel::Performance }
+        else { OptimizationLevel::Zero }
+    );
+
+    // Reflection needs the names of variables,
+    // which the optimizer strips unless debug information is requested.
+    options.set_generate_debug_info();
+
+    let source = read_to_string(source_path)
+        .map_err(|err| format!("Cannot read {}: {}",
+                               source_path.display(), err))?;
+    let mut compiler = Compiler::new().ok_or("Cannot create shaderc")?;
+    let artifact = compiler.compile_into_spirv(
+        /* source_text     */ &source,
+        /* shader_kind     */ shader_kind,
+        /* input_file_name */ &source_path.display().to_string(),
+        /* entry_point     */ "main",
+        /* options         */ Some(&options),
+    );
+
+    // Errors are prefixed with the file and line they occur on.
+    artifact
+        .map(|artifact| artifact.as_binary_u8().to_vec())
+        .map_err(|err| err.to_string())
+}
@@ -0,0 +1,287 @@
+//! Reflection of the interfaces of SPIR-V modules.
+//!
+//! Only the parts of SPIR-V that describe the interface are parsed:
+//! names, decorations, types, and global variables.
+//! The result is Rust code with typed constants for each location,
+//! so that a mismatch between GLSL and Rust fails to compile.
+
+use std::{collections::HashMap, fmt::Write};
+
+const MAGIC_NUMBER: u32 = 0x07230203;
+
+// Opcodes.
+const OP_NAME: u32 = 5;
+const OP_TYPE_INT: u32 = 21;
+const OP_TYPE_FLOAT: u32 = 22;
+const OP_TYPE_VECTOR: u32 = 23;
+const OP_TYPE_MATRIX: u32 = 24;
+const OP_TYPE_ARRAY: u32 = 28;
+const OP_TYPE_POINTER: u32 = 32;
+const OP_SPEC_CONSTANT: u32 = 50;
+const OP_VARIABLE: u32 = 59;
+const OP_DECORATE: u32 = 71;
+
+// Decorations.
+const DECORATION_SPEC_ID: u32 = 1;
+const DECORATION_LOCATION: u32 = 30;
+const DECORATION_BINDING: u32 = 33;
+
+// Storage classes.
+const STORAGE_CLASS_UNIFORM_CONSTANT: u32 = 0;
+const STORAGE_CLASS_INPUT: u32 = 1;
+
+/// Type of a variable, as far as reflection is concerned.
+#[derive(Clone, Copy)]
+enum Type
+{
+    Int{signed: bool},
+    Float,
+    Vector{component: u32, count: u32},
+    Matrix{column: u32, count: u32},
+    Array{element: u32},
+    Pointer{pointee: u32},
+}
+
+/// The interface of a shader.
+#[derive(Default)]
+pub struct Interface
+{
+    /// Names and types of uniforms by location.
+    pub uniforms: Vec<(String, String, u32)>,
+
+    /// Names and types of inputs by location.
+    pub inputs: Vec<(String, String, u32)>,
+
+    /// Names of uniform blocks and samplers by binding.
+    pub bindings: Vec<(String, u32)>,
+
+    /// Names of specialization constants by constant ID.
+    pub specialization_constants: Vec<(String, u32)>,
+}
+
+/// Parse the interface of a SPIR-V module.
+pub fn reflect(spirv: &[u8]) -> Result<Interface, String>
+{
+    if spirv.len() % 4 != 0 || spirv.len() < 20 {
+        return Err("SPIR-V module is truncated".into());
+    }
+    let words: Vec<u32> =
+        spirv.chunks(4)
+        .map(|w| u32::from_le_bytes([w[0], w[1], w[2], w[3]]))
+        .collect();
+    if words[0] != MAGIC_NUMBER {
+        return Err("SPIR-V module has wrong magic number".into());
+    }
+
+    let mut names = HashMap::new();
+    let mut decorations = HashMap::new();
+    let mut types = HashMap::new();
+    let mut variables = Vec::new();
+    let mut spec_constants = Vec::new();
+
+    // Skip the header, then visit each instruction.
+    let mut rest = &words[5 ..];
+    while let Some(&first) = rest.first() {
+        let (length, opcode) = ((first >> 16) as usize, first & 0xFFFF);
+        if length == 0 || length > rest.len() {
+            return Err("SPIR-V instruction is truncated".into());
+        }
+        let operands = &rest[1 .. length];
+        rest = &rest[length ..];
+
+        match (opcode, operands) {
+            (OP_NAME, [id, name @ ..]) => {
+                names.insert(*id, string(name));
+            },
+            (OP_DECORATE, [id, decoration, value, ..]) => {
+                decorations.insert((*id, *decoration), *value);
+            },
+            (OP_TYPE_INT, [id, _width, signed]) => {
+                types.insert(*id, Type::Int{signed: *signed != 0});
+            },
+            (OP_TYPE_FLOAT, [id, _width]) => {
+                types.insert(*id, Type::Float);
+            },
+            (OP_TYPE_VECTOR, [id, component, count]) => {
+                let vector = Type::Vector{component: *component, count: *count};
+                types.insert(*id, vector);
+            },
+            (OP_TYPE_MATRIX, [id, column, count]) => {
+                types.insert(*id, Type::Matrix{column: *column, count: *count});
+            },
+            (OP_TYPE_ARRAY, [id, element, _length]) => {
+                types.insert(*id, Type::Array{element: *element});
+            },
+            (OP_TYPE_POINTER, [id, _storage_class, pointee]) => {
+                types.insert(*id, Type::Pointer{pointee: *pointee});
+            },
+            (OP_SPEC_CONSTANT, [_type, id, ..]) => {
+                spec_constants.push(*id);
+            },
+            (OP_VARIABLE, [type_, id, storage_class, ..]) => {
+                variables.push((*type_, *id, *storage_class));
+            },
+            _ => (),
+        }
+    }
+
+    let name = |id| names.get(&id).cloned().unwrap_or_default();
+    let mut interface = Interface::default();
+
+    for (type_, id, storage_class) in variables {
+        let pointee = match types.get(&type_) {
+            Some(Type::Pointer{pointee}) => *pointee,
+            _ => return Err(format!("Variable {} is not a pointer", id)),
+        };
+
+        // Uniform blocks are named after their block, not their variable.
+        if let Some(&binding) = decorations.get(&(id, DECORATION_BINDING)) {
+            let variable_name = name(id);
+            let name = if variable_name.is_empty() { name(pointee) }
+                       else { variable_name };
+            interface.bindings.push((name, binding));
+            continue;
+        }
+
+        let location = match decorations.get(&(id, DECORATION_LOCATION)) {
+            Some(&location) => location,
+            None => continue,
+        };
+        let interface_variables = match storage_class {
+            STORAGE_CLASS_UNIFORM_CONSTANT => &mut interface.uniforms,
+            STORAGE_CLASS_INPUT => &mut interface.inputs,
+            _ => continue,
+        };
+        if name(id).is_empty() {
+            return Err(format!("Variable {} has no name", id));
+        }
+        let rust_type = rust_type(&types, pointee)
+            .ok_or_else(|| format!("Variable {} has unsupported type",
+                                   name(id)))?;
+        interface_variables.push((name(id), rust_type, location));
+    }
+
+    for id in spec_constants {
+        if let Some(&spec_id) = decorations.get(&(id, DECORATION_SPEC_ID)) {
+            interface.specialization_constants.push((name(id), spec_id));
+        }
+    }
+
+    interface.uniforms.sort_by_key(|&(_, _, location)| location);
+    interface.inputs.sort_by_key(|&(_, _, location)| location);
+    interface.bindings.sort();
+    interface.specialization_constants.sort_by_key(|&(_, id)| id);
+
+    Ok(interface)
+}
+
+/// The Rust type that corresponds to a SPIR-V type.
+fn rust_type(types: &HashMap<u32, Type>, id: u32) -> Option<String>
+{
+    let scalar = |id| match types.get(&id)? {
+        Type::Int{signed: true} => Some("i32"),
+        Type::Int{signed: false} => Some("u32"),
+        Type::Float => Some("f32"),
+        _ => None,
+    };
+    let vector = |id| match types.get(&id)? {
+        Type::Vector{component, count: count @ 2 ..= 4} => {
+            let prefix = match scalar(*component)? {
+                "f32" => "",
+                "u32" => "U",
+                "i32" => "I",
+                _ => return None,
+            };
+            Some(format!("::glam::{}Vec{}", prefix, count))
+        },
+        _ => None,
+    };
+    match types.get(&id)? {
+        Type::Int{..} | Type::Float => scalar(id).map(String::from),
+        Type::Vector{..} => vector(id),
+        Type::Matrix{column, count: 4} if vector(*column)? == "::glam::Vec4" =>
+            Some("::glam::Mat4".into()),
+        Type::Array{element} =>
+            Some(format!("[{}]", rust_type(types, *element)?)),
+        _ => None,
+    }
+}
+
+/// Decode a nul-terminated string literal.
+fn string(words: &[u32]) -> String
+{
+    let bytes: Vec<u8> =
+        words.iter()
+        .flat_map(|w| w.to_le_bytes())
+        .take_while(|&b| b != 0)
+        .collect();
+    String::from_utf8_lossy(&bytes).into_owned()
+}
+
+impl Interface
+{
+    /// Generate Rust code with constants for the interface.
+    pub fn to_rust(&self) -> String
+    {
+        let mut code = String::new();
+        let c = &mut code;
+        let graphics = "crate::client::graphics";
+
+        writeln!(c, "/// Uniforms, by location.").unwrap();
+        writeln!(c, "#[allow(dead_code)]").unwrap();
+        writeln!(c, "pub mod uniforms {{").unwrap();
+        for (name, type_, location) in &self.uniforms {
+            writeln!(c, "    pub const {}: {}::GlUniformLocation<{}> = \
+                         {}::GlUniformLocation::new({});",
+                     to_screaming_snake_case(name), graphics, type_,
+                     graphics, location).unwrap();
+        }
+        writeln!(c, "}}").unwrap();
+
+        writeln!(c, "/// Inputs, by location.").unwrap();
+        writeln!(c, "#[allow(dead_code)]").unwrap();
+        writeln!(c, "pub mod inputs {{").unwrap();
+        for (name, type_, location) in &self.inputs {
+            writeln!(c, "    pub const {}: {}::GlInputLocation<{}> = \
+                         {}::GlInputLocation::new({});",
+                     to_screaming_snake_case(name), graphics, type_,
+                     graphics, location).unwrap();
+        }
+        writeln!(c, "}}").unwrap();
+
+        writeln!(c, "/// Uniform blocks and samplers, by binding.").unwrap();
+        writeln!(c, "#[allow(dead_code)]").unwrap();
+        writeln!(c, "pub mod bindings {{").unwrap();
+        for (name, binding) in &self.bindings {
+            writeln!(c, "    pub const {}: u32 = {};",
+                     to_screaming_snake_case(name), binding).unwrap();
+        }
+        writeln!(c, "}}").unwrap();
+
+        writeln!(c, "/// Specialization constants, by constant ID.").unwrap();
+        writeln!(c, "#[allow(dead_code)]").unwrap();
+        writeln!(c, "pub mod specialization_constants {{").unwrap();
+        for (name, spec_id) in &self.specialization_constants {
+            writeln!(c, "    pub const {}: u32 = {};",
+                     to_screaming_snake_case(name), spec_id).unwrap();
+        }
+        writeln!(c, "}}").unwrap();
+
+        code
+    }
+}
+
+/// Convert `camelCase`, `PascalCase`, or `snake_case` to `SCREAMING_CASE`.
+fn to_screaming_snake_case(name: &str) -> String
+{
+    let mut result = String::new();
+    let mut previous_lowercase = false;
+    for c in name.chars() {
+        if c.is_uppercase() && previous_lowercase {
+            result.push('_');
+        }
+        previous_lowercase = c.is_lowercase() || c.is_ascii_digit();
+        result.extend(c.to_uppercase());
+    }
+    result
+}
@@ -1,25 +1,147 @@
-use anyhow::{Result, anyhow};
+use anyhow::{Context, Result, anyhow};
 use blok::{
-    client::graphics::{
-        GlBuffer,
-        generic,
-        parameters,
-        trivial_block,
+    client::{
+        graphics::{
+            GlBuffer,
+            GlContext,
+            check_frame_gl_errors,
+            entities::entity_instances,
+            generic,
+            install_debug_callback,
+            parameters,
+            renderer::{Camera, Renderer, Scene},
+            screenshot::save_screenshot,
+            shadow::Perspective,
+            sky::DayNightCycle,
+            set_gl_error_polling,
+            trivial_block,
+        },
+        interpolation::InterpolationConfig,
+        session::Session,
+    },
+    net::transport::{
+        LinkConditions,
+        MemorySocket,
+        SimulatedSocket,
+        Socket,
+        bind_udp,
     },
-    try_gl,
+    server,
+    state::{AbstractInput, ModelId, Tick, Transform, World},
 };
-use glam::{Mat4, Vec3, ivec2, ivec3, vec2, vec3};
+use glam::{IVec3, Mat4, Vec3, ivec2, vec2, vec3, vec4};
 use opengl::gl;
-use std::{f32::consts::PI, ffi::c_void};
+use sdl2::{event::Event, keyboard::{Keycode, Scancode}};
+use std::{
+    collections::BTreeSet,
+    env,
+    f32::consts::PI,
+    ffi::c_void,
+    net::SocketAddr,
+    path::Path,
+    sync::{Arc, atomic::{AtomicBool, Ordering::SeqCst}},
+    thread,
+    time::Instant,
+};
+
+/// Directory in which screenshots are saved.
+const SCREENSHOT_DIRECTORY: &str = "screenshots";
+
+/// Where the embedded server saves the world in single-player.
+const SAVE_PATH: &str = "saves/world.bin";
+
+/// Number of ticks between logging statistics about snapshot arrival.
+const JITTER_STATS_INTERVAL: u64 = 200;
 
 fn main() -> Result<()>
 {
-    unsafe {
-        unsafe_main()
+    env_logger::init();
+
+    // With --screenshot, draw a frame once the player has joined,
+    // save it, and exit.
+    let single_screenshot = env::args().skip(1).any(|arg| arg == "--screenshot");
+
+    // With --gl-debug, log messages from the OpenGL driver.
+    let gl_debug = env::args().skip(1).any(|arg| arg == "--gl-debug");
+
+    // With --server ADDRESS, join a server rather than playing alone.
+    let server_address = env::args().skip(1)
+        .skip_while(|arg| arg != "--server")
+        .nth(1)
+        .map(|arg| arg.parse::<SocketAddr>())
+        .transpose()
+        .context("Parse server address")?;
+
+    // With --simulate-link CONDITIONS, such as latency=100,loss=0.05,
+    // delay and drop datagrams, for testing prediction.
+    // Against a remote server, only datagrams to the server are affected.
+    let link_conditions = env::args().skip(1)
+        .skip_while(|arg| arg != "--simulate-link")
+        .nth(1)
+        .map(|arg| arg.parse::<LinkConditions>())
+        .transpose()
+        .context("Parse link conditions")?;
+    let simulate = |socket, seed| -> Box<dyn Socket + Send> {
+        match link_conditions {
+            Some(conditions) =>
+                Box::new(SimulatedSocket::new(socket, conditions, seed)),
+            None => socket,
+        }
+    };
+
+    // Without a server, run one on a thread, connected through memory.
+    let now = Instant::now();
+    let stop_server = Arc::new(AtomicBool::new(false));
+    let (mut session, server_thread) = match server_address {
+        Some(address) => {
+            let socket = simulate(Box::new(bind_udp("0.0.0.0:0")?), 1);
+            let config = InterpolationConfig::default();
+            (Session::connect(socket, address, now, config), None)
+        },
+        None => {
+            let (client_socket, server_socket) = MemorySocket::pair();
+            let address = server_socket.local_addr();
+            let server_socket = simulate(Box::new(server_socket), 2);
+            let stop = stop_server.clone();
+            let server_thread = thread::spawn(move || {
+                let save_path = Path::new(SAVE_PATH);
+                server::run(server_socket, 1, save_path, &stop)
+            });
+            let socket = simulate(Box::new(client_socket), 1);
+            let config = InterpolationConfig::default();
+            let session = Session::connect(socket, address, now, config);
+            (session, Some(server_thread))
+        },
+    };
+
+    let played = play(&mut session, single_screenshot, gl_debug);
+
+    // Let the embedded server save the world before exiting,
+    // also when playing failed.
+    log::info!("Snapshot arrival: {:?}", session.jitter_stats());
+    let played = played.and(session.disconnect());
+    stop_server.store(true, SeqCst);
+    let served = match server_thread {
+        Some(server_thread) => server_thread.join()
+            .unwrap_or_else(|_| Err(anyhow!("Server panicked"))),
+        None => Ok(()),
+    };
+
+    // A failed server makes the session fail too, such as by timing out,
+    // so the error of the server is the one that explains the failure.
+    if let (Err(_), Err(error)) = (&served, &played) {
+        log::error!("Session failed: {:#}", error);
     }
+    served.and(played)
 }
 
-unsafe fn unsafe_main() -> Result<()>
+/// Open a window and play the game until it is closed.
+fn play<S>(
+    session: &mut Session<S>,
+    single_screenshot: bool,
+    gl_debug: bool,
+) -> Result<()>
+    where S: Socket
 {
     // Obtain SDL features.
     let sdl_context = sdl2::init().map_err(|e| anyhow!(e))?;
@@ -35,6 +157,9 @@ unsafe fn unsafe_main() -> Result<()>
     gl_attr.set_blue_size(parameters::pixel_format::COLOR_BITS);
     gl_attr.set_alpha_size(parameters::pixel_format::ALPHA_BITS);
     gl_attr.set_depth_size(parameters::pixel_format::DEPTH_BITS);
+    if gl_debug {
+        gl_attr.set_context_flags().debug().set();
+    }
 
     // Create SDL window.
     let sdl_window =
@@ -51,89 +176,184 @@ unsafe fn unsafe_main() -> Result<()>
         sdl_video.gl_get_proc_address(proc_name) as *const c_void
     });
 
+    // SAFETY: Creating the context made it current,
+    //         and it is dropped after everything that uses the token.
+    let context = &unsafe { GlContext::new_unchecked() };
+
+    if gl_debug {
+        install_debug_callback(context)?;
+
+        // The debug callback also reports errors, so in release builds
+        // we can avoid the cost of checking for errors after each call.
+        if !cfg!(debug_assertions) {
+            set_gl_error_polling(false);
+        }
+    }
+
     // Create rendering pipelines.
-    let generic_fragment_shader = generic::FragmentShader::new()?;
-    let generic_pipeline = generic::Pipeline::new(&generic_fragment_shader)?;
-    let trivial_block_pipeline = trivial_block::Pipeline::new(&generic_fragment_shader)?;
+    let mut renderer = Renderer::new(
+        /* context               */ context,
+        /* shadow_map_resolution */ 2048,
+        /* atlas_size            */ ivec2(16, 8),
+    )?;
+
+    // Recompile shaders when their sources change.
+    #[cfg(all(feature = "shader-hot-reload", debug_assertions))]
+    let mut shader_reloader =
+        blok::client::graphics::hot_reload::ShaderReloader::new()?;
 
     // Create rendering state.
 
-    let model = generic::Model{
-        vertices: GlBuffer::new_upload(&[
+    let model = generic::Model::new(
+        /* context  */ context,
+        /* vertices */ &[
             generic::Vertex{
                 position: vec3(-1.0, -1.0, 0.0),
+                normal: vec3(0.0, 0.0, 1.0),
                 texcoord: vec2(0.0, 0.0),
-                bone: 0,
+                bones: [0; 4],
+                weights: vec4(1.0, 0.0, 0.0, 0.0),
             },
             generic::Vertex{
                 position: vec3(1.0, -1.0, 0.0),
+                normal: vec3(0.0, 0.0, 1.0),
                 texcoord: vec2(1.0, 0.0),
-                bone: 0,
+                bones: [0; 4],
+                weights: vec4(1.0, 0.0, 0.0, 0.0),
             },
             generic::Vertex{
                 position: vec3(0.0, 1.0, 0.0),
+                normal: vec3(0.0, 0.0, 1.0),
                 texcoord: vec2(0.0, 1.0),
-                bone: 0,
+                bones: [0; 4],
+                weights: vec4(1.0, 0.0, 0.0, 0.0),
             },
-        ], gl::STATIC_DRAW)?,
-        indices: GlBuffer::new_upload(&[
-            0, 1, 2,
-        ], gl::STATIC_DRAW)?,
-    };
+        ],
+        /* indices  */ &[0, 1, 2],
+        /* bones    */ 1,
+    )?;
 
-    let instance = generic::Instance{
-        m_matrix: Mat4::IDENTITY,
-        bone_matrices: [Mat4::IDENTITY; generic::BONES],
-    };
+    model.label("triangle")?;
 
-    let generic_models: &[(_, &[_])] = &[
-        (model, &[instance]),
-    ];
-
-    let trivial_block_face_sets = &[
-        trivial_block::FaceSet{
-            faces: GlBuffer::new_upload(&[
-                // TODO: Call TrivialBlockFace::new.
-                trivial_block::Face{
-                    xy: 0,
-                    zf: 3,
-                    u: 0,
-                    v: 0,
-                },
-                trivial_block::Face{
-                    xy: 0,
-                    zf: 4,
-                    u: 15,
-                    v: 7,
-                },
-                trivial_block::Face{
-                    xy: 0,
-                    zf: 0,
-                    u: 7,
-                    v: 3,
-                },
-            ], gl::STATIC_DRAW)?,
-            chunk_position: ivec3(0, 0, 0),
-        },
-    ];
+    // Models by model identifier.
+    let models = [model];
+
+    // Models that the server referred to but the client does not have,
+    // which are logged once and then not drawn.
+    let mut unknown_models: BTreeSet<ModelId> = BTreeSet::new();
+
+    // Faces of the chunks received so far.
+    let mut trivial_block_face_sets = Vec::new();
+
+    let day_night_cycle = DayNightCycle{ticks_per_day: 3600};
+    let mut next_input_tick = Tick(0);
 
     'outer: loop {
 
         // Handle SDL events.
+        let mut take_screenshot = false;
         for sdl_event in sdl_event_pump.poll_iter() {
             match sdl_event {
-                sdl2::event::Event::Quit{..} => break 'outer,
+                Event::Quit{..} => break 'outer,
+                Event::KeyDown{keycode: Some(Keycode::F12), repeat: false, ..} =>
+                    take_screenshot = true,
                 _ => (),
             }
         }
 
-        draw(
-            &generic_pipeline,
-            &trivial_block_pipeline,
-            generic_models,
-            trivial_block_face_sets,
+        #[cfg(all(feature = "shader-hot-reload", debug_assertions))]
+        renderer.reload_shaders(&mut shader_reloader);
+
+        // Send input for each tick that passed since the previous frame.
+        let now = Instant::now();
+        let tick = Tick(session.local_time(now) as u64);
+        let input = abstract_input(&sdl_event_pump);
+        while next_input_tick <= tick {
+            session.send_input(next_input_tick, input)?;
+            if next_input_tick.0 % JITTER_STATS_INTERVAL == 0 {
+                log::debug!("Snapshot arrival: {:?}", session.jitter_stats());
+            }
+            next_input_tick = Tick(next_input_tick.0 + 1);
+        }
+
+        // Mesh the chunks that arrived, and their neighbors,
+        // whose faces towards the new chunks are now hidden.
+        let arrived = session.update(now)?;
+        let mut remesh: Vec<IVec3> =
+            arrived.iter()
+            .flat_map(|&position| {
+                NEIGHBORS.iter()
+                .map(move |&offset| position + IVec3::from(offset))
+            })
+            .chain(arrived.iter().copied())
+            .filter(|&position| session.world().chunk(position).is_some())
+            .collect();
+        remesh.sort_by_key(|position| position.to_array());
+        remesh.dedup();
+        trivial_block_face_sets.retain(|face_set: &trivial_block::FaceSet| {
+            !remesh.contains(&face_set.chunk_position)
+        });
+        for chunk_position in remesh {
+            let world = session.world();
+            let face_set = mesh_chunk(context, world, chunk_position)?;
+            trivial_block_face_sets.push(face_set);
+        }
+
+        // Until the first snapshot arrives, there are no entities.
+        let entities = session.entities(now).unwrap_or_default();
+        let player_transform = session.player()
+            .and_then(|player| entities.get::<Transform>(player));
+
+        // With --screenshot, wait for the player to appear.
+        take_screenshot |= single_screenshot && player_transform.is_some();
+
+        let player_position = player_transform
+            .map(|transform| transform.position)
+            .unwrap_or(Vec3::ZERO);
+
+        let instances = entity_instances(&entities);
+        let generic_models: Vec<(_, &[_])> =
+            instances.iter()
+            .filter_map(|(&model, instances)| {
+                let loaded = models.get(model.0 as usize);
+                if loaded.is_none() && unknown_models.insert(model) {
+                    log::warn!("Unknown model {:?}", model);
+                }
+                Some((loaded?, instances.as_slice()))
+            })
+            .collect();
+
+        renderer.draw(
+            /* target        */ None,
+            /* viewport_size */ ivec2(640, 480),
+            /* camera        */ &camera(player_position),
+            /* scene         */ &Scene{
+                lighting: day_night_cycle.lighting(tick),
+                generic_models: &generic_models,
+                trivial_block_face_sets: &trivial_block_face_sets,
+            },
         )?;
 
+        // Catch errors that were not checked after each call.
+        check_frame_gl_errors(context)?;
+
+        // The back buffer is undefined after swapping,
+        // so screenshots must be taken before presenting.
+        if take_screenshot {
+            let (width, height) = sdl_window.drawable_size();
+            let path = save_screenshot(
+                /* context     */ context,
+                /* framebuffer */ None,
+                /* width       */ width,
+                /* height      */ height,
+                /* directory   */ Path::new(SCREENSHOT_DIRECTORY),
+            )?;
+            log::info!("Saved screenshot to {}", path.display());
+            if single_screenshot {
+                break 'outer;
+            }
+        }
+
         // Present buffer we drew to.
         sdl_window.gl_swap_window();
 
@@ -142,41 +362,59 @@ unsafe fn unsafe_main() -> Result<()>
     Ok(())
 }
 
-unsafe fn draw(
-    generic_pipeline: &generic::Pipeline,
-    trivial_block_pipeline: &trivial_block::Pipeline,
-    generic_models: &[(generic::Model, &[generic::Instance])],
-    trivial_block_face_sets: &[trivial_block::FaceSet],
-) -> Result<()>
+/// Offsets of the chunks that share a face with a chunk.
+const NEIGHBORS: [[i32; 3]; 6] = [
+    [-1, 0, 0], [1, 0, 0],
+    [0, -1, 0], [0, 1, 0],
+    [0, 0, -1], [0, 0, 1],
+];
+
+/// Map the keyboard state to input.
+fn abstract_input(event_pump: &sdl2::EventPump) -> AbstractInput
 {
-    try_gl! { gl::ClearColor(0.1, 0.9, 0.2, 1.0); }
-    try_gl! { gl::Clear(gl::COLOR_BUFFER_BIT); }
-
-    let v_matrix = Mat4::look_at_rh(
-        /* eye    */ Vec3::new(2.0, -2.0, 2.0),
-        /* center */ Vec3::new(0.0, 0.0, 0.0),
-        /* up     */ Vec3::new(0.0, 0.0, 1.0),
-    );
-
-    let p_matrix = Mat4::perspective_rh(
-        /* fov_y_radians */ PI / 4.0,
-        /* aspect_ratio  */ 640.0 / 480.0,
-        /* z_near        */ 1.0,
-        /* z_far         */ 1000.0,
-    );
-
-    let vp_matrix = p_matrix * v_matrix;
-
-    generic_pipeline.render(
-        /* vp_matrix */ &vp_matrix,
-        /* models    */ generic_models.iter().map(|(m, i)| (m, *i)),
-    )?;
+    let keyboard = event_pump.keyboard_state();
+    AbstractInput{
+        move_forward: keyboard.is_scancode_pressed(Scancode::W),
+        move_backward: keyboard.is_scancode_pressed(Scancode::S),
+        strafe_left: keyboard.is_scancode_pressed(Scancode::A),
+        strafe_right: keyboard.is_scancode_pressed(Scancode::D),
+    }
+}
 
-    trivial_block_pipeline.render(
-        /* atlas_size */ &ivec2(16, 8),
-        /* vp_matrix  */ &vp_matrix,
-        /* models     */ trivial_block_face_sets,
+/// Upload the faces of a chunk.
+fn mesh_chunk<'c>(
+    context: &'c GlContext,
+    world: &World,
+    chunk_position: IVec3,
+) -> Result<trivial_block::FaceSet<'c>>
+{
+    let faces = GlBuffer::new_upload(
+        context,
+        &trivial_block::mesh(&trivial_block::WorldVoxels{
+            world,
+            chunk_position,
+            texture: |_, face| (face as u16, 0),
+        }),
+        gl::STATIC_DRAW,
     )?;
+    faces.label("chunk faces")?;
+    Ok(trivial_block::FaceSet{faces, chunk_position})
+}
 
-    Ok(())
+/// The camera from which the world is viewed, following the player.
+fn camera(target: Vec3) -> Camera
+{
+    Camera{
+        v_matrix: Mat4::look_at_rh(
+            /* eye    */ target + Vec3::new(2.0, -2.0, 2.0),
+            /* center */ target,
+            /* up     */ Vec3::new(0.0, 0.0, 1.0),
+        ),
+        perspective: Perspective{
+            fov_y_radians: PI / 4.0,
+            aspect_ratio: 640.0 / 480.0,
+            z_near: 1.0,
+            z_far: 1000.0,
+        },
+    }
 }
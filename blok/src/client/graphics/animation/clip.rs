@@ -0,0 +1,199 @@
+use super::Pose;
+use crate::state::slerp;
+use glam::{Quat, Vec3};
+
+/// Keyframed animation of the joints of a skeleton.
+#[derive(Clone, Debug)]
+pub struct Clip
+{
+    duration: f32,
+    channels: Vec<Channel>,
+}
+
+impl Clip
+{
+    /// Create a clip from its channels.
+    ///
+    /// The duration is the number of ticks after which the clip ends.
+    /// Joints without channels keep their bind pose.
+    pub fn new(duration: f32, channels: Vec<Channel>) -> Self
+    {
+        Self{duration, channels}
+    }
+
+    /// The number of ticks after which the clip ends.
+    pub fn duration(&self) -> f32
+    {
+        self.duration
+    }
+
+    /// The channels of the clip.
+    pub fn channels(&self) -> &[Channel]
+    {
+        &self.channels
+    }
+
+    /// Overwrite the animated properties of a pose
+    /// with their values at the given time.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a channel animates a joint that is not in the pose.
+    pub fn sample(&self, time: f32, pose: &mut Pose)
+    {
+        for channel in &self.channels {
+            let transform = &mut pose.transforms[channel.joint];
+            let interpolation = channel.interpolation;
+            match &channel.keyframes {
+                Keyframes::Translation(keyframes) =>
+                    transform.translation =
+                        sample(keyframes, interpolation, time, Vec3::lerp),
+                Keyframes::Rotation(keyframes) =>
+                    transform.rotation =
+                        sample(keyframes, interpolation, time, slerp),
+                Keyframes::Scale(keyframes) =>
+                    transform.scale =
+                        sample(keyframes, interpolation, time, Vec3::lerp),
+            }
+        }
+    }
+}
+
+/// Keyframes of a single property of a single joint.
+#[derive(Clone, Debug)]
+pub struct Channel
+{
+    joint: usize,
+    interpolation: Interpolation,
+    keyframes: Keyframes,
+}
+
+impl Channel
+{
+    /// Create a channel that animates the joint with the given index.
+    ///
+    /// # Panics
+    ///
+    /// Panics if there are no keyframes,
+    /// or if the times of the keyframes do not strictly increase.
+    pub fn new(joint: usize, interpolation: Interpolation, keyframes: Keyframes)
+        -> Self
+    {
+        let times = keyframes.times();
+        assert!(!times.is_empty());
+        assert!(times.windows(2).all(|w| w[0] < w[1]));
+        Self{joint, interpolation, keyframes}
+    }
+
+    /// The index of the animated joint.
+    pub fn joint(&self) -> usize
+    {
+        self.joint
+    }
+}
+
+/// How values between keyframes are computed.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Interpolation
+{
+    /// Keep the value of the previous keyframe.
+    Step,
+
+    /// Interpolate linearly, and rotations along the shortest arc.
+    Linear,
+}
+
+/// Keyframes of a property, as pairs of time in ticks and value.
+#[derive(Clone, Debug)]
+pub enum Keyframes
+{
+    /// Translations of the joint.
+    Translation(Vec<(f32, Vec3)>),
+
+    /// Rotations of the joint, which must be normalized.
+    Rotation(Vec<(f32, Quat)>),
+
+    /// Scales of the joint.
+    Scale(Vec<(f32, Vec3)>),
+}
+
+impl Keyframes
+{
+    fn times(&self) -> Vec<f32>
+    {
+        match self {
+            Self::Translation(k) => k.iter().map(|&(t, _)| t).collect(),
+            Self::Rotation(k)    => k.iter().map(|&(t, _)| t).collect(),
+            Self::Scale(k)       => k.iter().map(|&(t, _)| t).collect(),
+        }
+    }
+}
+
+/// The value of a property at the given time.
+///
+/// Before the first keyframe and after the last one,
+/// the value of that keyframe is held.
+fn sample<T, F>(
+    keyframes: &[(f32, T)],
+    interpolation: Interpolation,
+    time: f32,
+    lerp: F,
+) -> T
+    where T: Copy
+        , F: Fn(T, T, f32) -> T
+{
+    // Index of the first keyframe after the time.
+    let next = keyframes.partition_point(|&(t, _)| t <= time);
+    if next == 0 {
+        return keyframes[0].1;
+    }
+    if next == keyframes.len() {
+        return keyframes[next - 1].1;
+    }
+
+    let (t0, v0) = keyframes[next - 1];
+    let (t1, v1) = keyframes[next];
+    match interpolation {
+        Interpolation::Step => v0,
+        Interpolation::Linear => lerp(v0, v1, (time - t0) / (t1 - t0)),
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+    use glam::vec3;
+
+    #[test]
+    fn sample_between_keyframes()
+    {
+        let keyframes = [
+            (0.0, vec3(0.0, 0.0, 0.0)),
+            (2.0, vec3(2.0, 0.0, 0.0)),
+            (4.0, vec3(2.0, 4.0, 0.0)),
+        ];
+        let linear = |time| {
+            sample(&keyframes, Interpolation::Linear, time, Vec3::lerp)
+        };
+        let step = |time| {
+            sample(&keyframes, Interpolation::Step, time, Vec3::lerp)
+        };
+        assert_eq!(linear(-1.0), vec3(0.0, 0.0, 0.0));
+        assert_eq!(linear(1.0), vec3(1.0, 0.0, 0.0));
+        assert_eq!(linear(3.0), vec3(2.0, 2.0, 0.0));
+        assert_eq!(linear(5.0), vec3(2.0, 4.0, 0.0));
+        assert_eq!(step(3.0), vec3(2.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn rotation_takes_shortest_arc()
+    {
+        // The same rotation as the identity, with the opposite sign.
+        let keyframes = [(0.0, Quat::IDENTITY), (1.0, -Quat::IDENTITY)];
+        let halfway =
+            sample(&keyframes, Interpolation::Linear, 0.5, slerp);
+        assert!(halfway.abs_diff_eq(Quat::IDENTITY, 1e-6)
+             || halfway.abs_diff_eq(-Quat::IDENTITY, 1e-6));
+    }
+}
@@ -0,0 +1,223 @@
+//! Skeletal animation of models.
+//!
+//! A [`Skeleton`] describes the joints of a model in their bind pose.
+//! A [`Clip`] moves the joints over time with keyframed channels.
+//! Clips are sampled into a [`Pose`], poses of different clips can be
+//! blended, and the skeleton turns the pose into the bone matrices
+//! of a [`generic::Instance`](crate::client::graphics::generic::Instance).
+//!
+//! Clip time is measured in ticks, so that animations advance with the game.
+//! Rendering happens between ticks, so evaluation takes a tick
+//! and an interpolation alpha, which is the fraction of the next tick
+//! that has passed at the time of rendering.
+
+pub use self::clip::*;
+pub use self::skeleton::*;
+
+use crate::state::{Tick, slerp};
+use glam::{Mat4, Quat, Vec3};
+
+mod clip;
+mod skeleton;
+
+/// Transform of a joint relative to its parent.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Transform
+{
+    /// Translation, applied last.
+    pub translation: Vec3,
+
+    /// Rotation, applied after scaling.
+    ///
+    /// This must be normalized.
+    pub rotation: Quat,
+
+    /// Scale, applied first.
+    pub scale: Vec3,
+}
+
+impl Transform
+{
+    /// The transform that does nothing.
+    pub const IDENTITY: Self = Self{
+        translation: Vec3::ZERO,
+        rotation: Quat::IDENTITY,
+        scale: Vec3::ONE,
+    };
+
+    /// The matrix that applies the transform.
+    pub fn to_matrix(&self) -> Mat4
+    {
+        Mat4::from_scale_rotation_translation(
+            self.scale,
+            self.rotation,
+            self.translation,
+        )
+    }
+
+    /// Interpolate between two transforms.
+    ///
+    /// Rotations are interpolated along the shortest arc.
+    pub fn lerp(&self, other: &Self, t: f32) -> Self
+    {
+        Self{
+            translation: self.translation.lerp(other.translation, t),
+            rotation: slerp(self.rotation, other.rotation, t),
+            scale: self.scale.lerp(other.scale, t),
+        }
+    }
+}
+
+/// Transforms of all joints of a skeleton, relative to their parents.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Pose
+{
+    /// The transform of each joint, in the order of the skeleton.
+    pub transforms: Vec<Transform>,
+}
+
+impl Pose
+{
+    /// Interpolate between two poses of the same skeleton.
+    ///
+    /// A weight of zero yields this pose and a weight of one the other.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the poses have different numbers of joints.
+    pub fn blend(&self, other: &Self, weight: f32) -> Self
+    {
+        assert_eq!(self.transforms.len(), other.transforms.len());
+        let transforms =
+            self.transforms.iter()
+            .zip(&other.transforms)
+            .map(|(a, b)| a.lerp(b, weight))
+            .collect();
+        Self{transforms}
+    }
+}
+
+/// A clip that is being played, starting at a particular tick.
+#[derive(Clone, Copy, Debug)]
+pub struct Playback<'a>
+{
+    /// The clip being played.
+    pub clip: &'a Clip,
+
+    /// The tick at which the clip started playing.
+    pub start: Tick,
+
+    /// Whether the clip restarts when it ends.
+    ///
+    /// Clips that do not loop hold their final pose.
+    pub looping: bool,
+}
+
+impl<'a> Playback<'a>
+{
+    /// The time within the clip at the given tick and alpha.
+    pub fn time(&self, tick: Tick, alpha: f32) -> f32
+    {
+        let elapsed = tick.0.saturating_sub(self.start.0) as f32 + alpha;
+        let duration = self.clip.duration();
+        if self.looping && duration > 0.0 {
+            elapsed % duration
+        } else {
+            elapsed.min(duration)
+        }
+    }
+
+    /// The pose of the skeleton at the given tick and alpha.
+    pub fn pose(&self, skeleton: &Skeleton, tick: Tick, alpha: f32) -> Pose
+    {
+        let mut pose = skeleton.bind_pose();
+        self.clip.sample(self.time(tick, alpha), &mut pose);
+        pose
+    }
+}
+
+/// Transition from one clip to another.
+///
+/// The poses of both clips are blended,
+/// with the weight moving linearly from the first to the second.
+#[derive(Clone, Copy, Debug)]
+pub struct Crossfade<'a>
+{
+    /// The clip that is fading out.
+    pub from: Playback<'a>,
+
+    /// The clip that is fading in.
+    ///
+    /// The transition starts when this clip starts.
+    pub to: Playback<'a>,
+
+    /// The number of ticks the transition takes.
+    pub duration: f32,
+}
+
+impl<'a> Crossfade<'a>
+{
+    /// The weight of the second clip at the given tick and alpha.
+    pub fn weight(&self, tick: Tick, alpha: f32) -> f32
+    {
+        let elapsed = tick.0.saturating_sub(self.to.start.0) as f32 + alpha;
+        if self.duration > 0.0 {
+            (elapsed / self.duration).min(1.0)
+        } else {
+            1.0
+        }
+    }
+
+    /// The pose of the skeleton at the given tick and alpha.
+    pub fn pose(&self, skeleton: &Skeleton, tick: Tick, alpha: f32) -> Pose
+    {
+        let weight = self.weight(tick, alpha);
+        let from = self.from.pose(skeleton, tick, alpha);
+        let to = self.to.pose(skeleton, tick, alpha);
+        from.blend(&to, weight)
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+    use glam::vec3;
+
+    #[test]
+    fn looping_playback_wraps()
+    {
+        let clip = Clip::new(10.0, Vec::new());
+        let playback = Playback{clip: &clip, start: Tick(100), looping: true};
+        assert_eq!(playback.time(Tick(103), 0.5), 3.5);
+        assert_eq!(playback.time(Tick(112), 0.0), 2.0);
+        assert_eq!(playback.time(Tick(99), 0.0), 0.0);
+
+        let once = Playback{looping: false, ..playback};
+        assert_eq!(once.time(Tick(112), 0.0), 10.0);
+    }
+
+    #[test]
+    fn crossfade_blends_poses()
+    {
+        let skeleton = Skeleton::new(vec![
+            Joint{name: "root".into(), parent: None,
+                  bind_pose: Transform::IDENTITY},
+        ]);
+        let still = Clip::new(1.0, Vec::new());
+        let moved = Clip::new(1.0, vec![
+            Channel::new(0, Interpolation::Step, Keyframes::Translation(
+                vec![(0.0, vec3(4.0, 0.0, 0.0))],
+            )),
+        ]);
+        let crossfade = Crossfade{
+            from: Playback{clip: &still, start: Tick(0), looping: true},
+            to: Playback{clip: &moved, start: Tick(10), looping: true},
+            duration: 4.0,
+        };
+        let pose = crossfade.pose(&skeleton, Tick(11), 0.0);
+        assert_eq!(pose.transforms[0].translation, vec3(1.0, 0.0, 0.0));
+        let pose = crossfade.pose(&skeleton, Tick(20), 0.0);
+        assert_eq!(pose.transforms[0].translation, vec3(4.0, 0.0, 0.0));
+    }
+}
@@ -0,0 +1,206 @@
+use super::{Pose, Transform};
+use crate::client::graphics::generic::BONES;
+use glam::Mat4;
+
+/// Joint of a skeleton.
+#[derive(Clone, Debug)]
+pub struct Joint
+{
+    /// Name of the joint, for diagnostics and for matching clips.
+    pub name: String,
+
+    /// Index of the parent joint, or [`None`] for a root joint.
+    pub parent: Option<usize>,
+
+    /// Transform of the joint relative to its parent in the bind pose.
+    ///
+    /// The bind pose is the pose in which the mesh was modelled.
+    pub bind_pose: Transform,
+}
+
+/// Hierarchy of joints that deform a mesh.
+///
+/// Each joint corresponds to the bone with the same index
+/// in the vertices of the mesh and in the bone matrices.
+#[derive(Clone, Debug)]
+pub struct Skeleton
+{
+    // INVARIANT: Parents precede their children.
+    joints: Vec<Joint>,
+
+    // INVARIANT: There is one matrix for each joint.
+    inverse_bind_matrices: Vec<Mat4>,
+}
+
+impl Skeleton
+{
+    /// Create a skeleton from its joints in the bind pose.
+    ///
+    /// # Panics
+    ///
+    /// Panics if there are more than [`BONES`] joints,
+    /// or if a joint does not come after its parent.
+    pub fn new(joints: Vec<Joint>) -> Self
+    {
+        let bind_pose = Pose{
+            transforms: joints.iter().map(|j| j.bind_pose).collect(),
+        };
+        let mut this = Self{joints, inverse_bind_matrices: Vec::new()};
+        this.validate();
+        this.inverse_bind_matrices =
+            this.model_matrices(&bind_pose).iter()
+            .map(Mat4::inverse)
+            .collect();
+        this
+    }
+
+    /// Create a skeleton whose inverse bind matrices are given.
+    ///
+    /// Model formats store these matrices alongside the joints,
+    /// and they need not agree exactly with the bind poses of the joints.
+    ///
+    /// # Panics
+    ///
+    /// Panics if there are more than [`BONES`] joints,
+    /// if a joint does not come after its parent,
+    /// or if the number of matrices differs from the number of joints.
+    pub fn with_inverse_bind_matrices(
+        joints: Vec<Joint>,
+        inverse_bind_matrices: Vec<Mat4>,
+    ) -> Self
+    {
+        assert_eq!(joints.len(), inverse_bind_matrices.len());
+        let this = Self{joints, inverse_bind_matrices};
+        this.validate();
+        this
+    }
+
+    fn validate(&self)
+    {
+        assert!(self.joints.len() <= BONES);
+        for (index, joint) in self.joints.iter().enumerate() {
+            if let Some(parent) = joint.parent {
+                assert!(parent < index, "Joint {} precedes its parent",
+                        joint.name);
+            }
+        }
+    }
+
+    /// The joints of the skeleton.
+    pub fn joints(&self) -> &[Joint]
+    {
+        &self.joints
+    }
+
+    /// The index of the joint with the given name.
+    pub fn joint_index(&self, name: &str) -> Option<usize>
+    {
+        self.joints.iter().position(|j| j.name == name)
+    }
+
+    /// The pose in which all joints are in their bind pose.
+    pub fn bind_pose(&self) -> Pose
+    {
+        let transforms = self.joints.iter().map(|j| j.bind_pose).collect();
+        Pose{transforms}
+    }
+
+    /// Compute the bone matrices for a pose of the skeleton.
+    ///
+    /// Each bone matrix takes a vertex from the bind pose to the pose.
+    /// Bones beyond the joints of the skeleton are set to the identity.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the pose has a different number of joints.
+    pub fn bone_matrices(&self, pose: &Pose) -> [Mat4; BONES]
+    {
+        let mut bone_matrices = [Mat4::IDENTITY; BONES];
+        let model_matrices = self.model_matrices(pose);
+        let bones = model_matrices.iter().zip(&self.inverse_bind_matrices);
+        for (bone_matrix, (model, inverse_bind)) in
+            bone_matrices.iter_mut().zip(bones)
+        {
+            *bone_matrix = *model * *inverse_bind;
+        }
+        bone_matrices
+    }
+
+    /// Transform of each joint relative to the model.
+    fn model_matrices(&self, pose: &Pose) -> Vec<Mat4>
+    {
+        assert_eq!(pose.transforms.len(), self.joints.len());
+        let mut model_matrices: Vec<Mat4> =
+            Vec::with_capacity(self.joints.len());
+        for (joint, transform) in self.joints.iter().zip(&pose.transforms) {
+            let local = transform.to_matrix();
+            let model = match joint.parent {
+                // Parents precede children, so the parent is computed.
+                Some(parent) => model_matrices[parent] * local,
+                None => local,
+            };
+            model_matrices.push(model);
+        }
+        model_matrices
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+    use glam::{Quat, Vec3, vec3};
+    use std::f32::consts::FRAC_PI_2;
+
+    fn arm() -> Skeleton
+    {
+        let joint = |name: &str, parent, translation| Joint{
+            name: name.into(),
+            parent,
+            bind_pose: Transform{translation, ..Transform::IDENTITY},
+        };
+        Skeleton::new(vec![
+            joint("shoulder", None, vec3(0.0, 0.0, 1.0)),
+            joint("elbow", Some(0), vec3(2.0, 0.0, 0.0)),
+        ])
+    }
+
+    #[test]
+    fn bind_pose_has_identity_bones()
+    {
+        let skeleton = arm();
+        let bone_matrices = skeleton.bone_matrices(&skeleton.bind_pose());
+        for bone_matrix in bone_matrices {
+            assert!(bone_matrix.abs_diff_eq(Mat4::IDENTITY, 1e-6));
+        }
+    }
+
+    #[test]
+    fn child_follows_parent()
+    {
+        let skeleton = arm();
+        let mut pose = skeleton.bind_pose();
+        pose.transforms[0].rotation = Quat::from_rotation_z(FRAC_PI_2);
+        let bone_matrices = skeleton.bone_matrices(&pose);
+
+        // The hand, one unit past the elbow, swings round the shoulder.
+        let hand = bone_matrices[1].transform_point3(vec3(3.0, 0.0, 1.0));
+        assert!(hand.abs_diff_eq(vec3(0.0, 3.0, 1.0), 1e-6));
+
+        // The shoulder itself does not move.
+        let shoulder = bone_matrices[0].transform_point3(Vec3::Z);
+        assert!(shoulder.abs_diff_eq(Vec3::Z, 1e-6));
+    }
+
+    #[test]
+    #[should_panic]
+    fn parent_must_precede_child()
+    {
+        Skeleton::new(vec![
+            Joint{name: "child".into(), parent: Some(1),
+                  bind_pose: Transform::IDENTITY},
+            Joint{name: "parent".into(), parent: None,
+                  bind_pose: Transform::IDENTITY},
+        ]);
+    }
+}
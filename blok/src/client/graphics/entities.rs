@@ -0,0 +1,63 @@
+//! Rendering of entities with the generic pipeline.
+
+use crate::{
+    client::graphics::generic::{BONES, Instance},
+    state::{Entities, ModelId, Transform},
+};
+use glam::Mat4;
+use std::collections::BTreeMap;
+
+/// Instances of the entities that have a model and a transform,
+/// grouped by model.
+///
+/// This is called each frame, and the client draws each group
+/// with the model it loaded for the model reference.
+pub fn entity_instances(entities: &Entities)
+    -> BTreeMap<ModelId, Vec<Instance>>
+{
+    let mut instances: BTreeMap<ModelId, Vec<Instance>> = BTreeMap::new();
+    for (id, &model) in entities.iter::<ModelId>() {
+        let transform = match entities.get::<Transform>(id) {
+            Some(transform) => transform,
+            None => continue,
+        };
+        instances.entry(model).or_default().push(Instance{
+            m_matrix: transform.to_matrix(),
+            bone_matrices: [Mat4::IDENTITY; BONES],
+        });
+    }
+    instances
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+    use glam::{Quat, vec3};
+
+    #[test]
+    fn instances_are_grouped_by_model()
+    {
+        let mut entities = Entities::new();
+        for (model, x) in [(1, 0.0), (0, 1.0), (1, 2.0)] {
+            let id = entities.spawn();
+            entities.insert(id, ModelId(model));
+            entities.insert(id, Transform{
+                position: vec3(x, 0.0, 0.0),
+                orientation: Quat::IDENTITY,
+            });
+        }
+        // Entities without a transform are not rendered.
+        let id = entities.spawn();
+        entities.insert(id, ModelId(0));
+
+        let instances = entity_instances(&entities);
+        let xs = |model| -> Vec<f32> {
+            instances[&ModelId(model)].iter()
+                .map(|i| i.m_matrix.w_axis.x)
+                .collect()
+        };
+        assert_eq!(xs(0), [1.0]);
+        assert_eq!(xs(1), [0.0, 2.0]);
+    }
+}
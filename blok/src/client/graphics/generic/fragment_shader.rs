@@ -1,7 +1,20 @@
-use crate::client::graphics::GlShader;
+use crate::client::graphics::{
+    GlBuffer,
+    GlContext,
+    GlShader,
+    shadow::{CASCADES, Cascades, ShadowMap},
+};
 use anyhow::Result;
+use glam::{Mat4, Vec3, Vec4};
 use opengl::gl;
 
+#[cfg(all(feature = "shader-hot-reload", debug_assertions))]
+use crate::client::graphics::hot_reload::ShaderReloader;
+
+/// Path of the GLSL source of the fragment shader.
+pub const FRAGMENT_SHADER_PATH: &str =
+    "src/client/graphics/generic/shader.frag";
+
 static FRAGMENT_SHADER_BINARY: &'static [u8] =
     include_bytes!(
         concat!(
@@ -10,21 +23,51 @@ static FRAGMENT_SHADER_BINARY: &'static [u8] =
         )
     );
 
+/// Interface of the fragment shader, reflected by the build script.
+mod fragment_interface
+{
+    include!(
+        concat!(
+            env!("OUT_DIR"),
+            "/client/graphics/generic/shader.frag.rs",
+        )
+    );
+}
+
 /// Fragment shader used by most pipelines.
-pub struct FragmentShader
+///
+/// The fragment shader reads its parameters from an [`Environment`],
+/// which must be bound before drawing with a pipeline that uses it.
+pub struct FragmentShader<'c>
 {
-    inner: GlShader,
+    inner: GlShader<'c>,
 }
 
-impl FragmentShader
+impl<'c> FragmentShader<'c>
 {
     /// Compile the shader.
-    #[doc = crate::doc_safety_opengl!()]
-    pub unsafe fn new() -> Result<Self>
+    pub fn new(context: &'c GlContext) -> Result<Self>
+    {
+        Self::from_binary(context, FRAGMENT_SHADER_BINARY)
+    }
+
+    /// Compile the shader from its most recent binary.
+    #[cfg(all(feature = "shader-hot-reload", debug_assertions))]
+    pub fn from_reloader(context: &'c GlContext, reloader: &ShaderReloader)
+        -> Result<Self>
+    {
+        let binary =
+            reloader.binary(FRAGMENT_SHADER_PATH, FRAGMENT_SHADER_BINARY);
+        Self::from_binary(context, binary)
+    }
+
+    fn from_binary(context: &'c GlContext, binary: &[u8]) -> Result<Self>
     {
         let inner = GlShader::new(
+            /* context          */ context,
             /* shader_type      */ gl::FRAGMENT_SHADER,
-            /* shader_binary    */ FRAGMENT_SHADER_BINARY,
+            /* source_path      */ FRAGMENT_SHADER_PATH,
+            /* shader_binary    */ binary,
             /* constant_indices */ &[],
             /* constant_values  */ &[],
         )?;
@@ -32,8 +75,107 @@ impl FragmentShader
     }
 
     /// The underlying shader.
-    pub fn as_shader(&self) -> &GlShader
+    pub fn as_shader(&self) -> &GlShader<'c>
     {
         &self.inner
     }
 }
+
+/// Parameters for directional lighting by the sun.
+///
+/// Surfaces are lit by ambient light, plus the sun light scaled by
+/// the cosine of the angle between the surface normal and the sun.
+/// Sun light does not reach surfaces that are in shadow.
+/// Both are scaled by the sky light level of the surface,
+/// so that surfaces underground are not lit by the sun.
+#[derive(Clone, Copy, Debug)]
+pub struct Lighting
+{
+    /// Direction in which the sun light travels.
+    ///
+    /// This must be normalized.
+    pub sun_direction: Vec3,
+
+    /// Colour of the sun light.
+    pub sun_color: Vec3,
+
+    /// Colour of the light that reaches surfaces from all directions.
+    pub ambient_color: Vec3,
+}
+
+/// Uniform block of the fragment shader.
+///
+/// The layout of this struct must match the std140 layout
+/// of the `Environment` uniform block in the fragment shader.
+/// In std140, vec3 members are aligned like vec4 members.
+#[derive(Clone, Copy)]
+#[repr(C)]
+struct EnvironmentBlock
+{
+    sun_direction: Vec4,
+    sun_color: Vec4,
+    ambient_color: Vec4,
+    v_matrix: Mat4,
+    cascade_vp_matrices: [Mat4; CASCADES],
+    cascade_far: Vec4,
+}
+
+/// Parameters of the fragment shader that are shared by all pipelines.
+///
+/// The parameters are stored in a uniform buffer,
+/// so they are set only once per frame rather than once per pipeline.
+pub struct Environment<'c>
+{
+    buffer: GlBuffer<'c, EnvironmentBlock>,
+}
+
+impl<'c> Environment<'c>
+{
+    /// Create an environment with no parameters set.
+    pub fn new(context: &'c GlContext) -> Result<Self>
+    {
+        let buffer = GlBuffer::new(context)?;
+        buffer.label("environment uniform block")?;
+        Ok(Self{buffer})
+    }
+
+    /// Set the parameters for the next frame.
+    ///
+    /// # Parameters
+    ///
+    /// <dl>
+    /// <dt><code>lighting</code></dt>
+    /// <dd>The lighting by the sun.</dd>
+    /// <dt><code>v_matrix</code></dt>
+    /// <dd>The view matrix of the camera,
+    ///     used for selecting the shadow cascade.</dd>
+    /// <dt><code>cascades</code></dt>
+    /// <dd>The cascades that the shadow map was rendered with.</dd>
+    /// </dl>
+    pub fn update(
+        &mut self,
+        lighting: &Lighting,
+        v_matrix: &Mat4,
+        cascades: &Cascades,
+    ) -> Result<()>
+    {
+        let block = EnvironmentBlock{
+            sun_direction: lighting.sun_direction.extend(0.0),
+            sun_color: lighting.sun_color.extend(0.0),
+            ambient_color: lighting.ambient_color.extend(0.0),
+            v_matrix: *v_matrix,
+            cascade_vp_matrices: cascades.vp_matrices,
+            cascade_far: Vec4::from(cascades.far),
+        };
+        self.buffer.upload(&[block], gl::DYNAMIC_DRAW)
+    }
+
+    /// Bind the parameters and the shadow map for drawing.
+    pub fn bind(&self, shadow_map: &ShadowMap) -> Result<()>
+    {
+        use fragment_interface::bindings::{ENVIRONMENT, SHADOW_MAP};
+        self.buffer.bind_base(gl::UNIFORM_BUFFER, ENVIRONMENT)?;
+        shadow_map.texture().bind_unit(SHADOW_MAP)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,751 @@
+use super::{BONES, BONES_PER_VERTEX, Model, Vertex};
+use crate::client::graphics::{
+    GlContext,
+    animation::{
+        Channel,
+        Clip,
+        Interpolation,
+        Joint,
+        Keyframes,
+        Skeleton,
+        Transform,
+    },
+};
+use anyhow::{Context, Result, anyhow, bail, ensure};
+use glam::{Mat4, Quat, Vec2, Vec3, Vec4, vec2, vec3};
+use gltf::{
+    Document,
+    Gltf,
+    Mesh,
+    Node,
+    Primitive,
+    Skin,
+    animation::{self, util::ReadOutputs},
+    buffer::Source,
+    mesh::Mode,
+};
+use std::{collections::HashMap, fs, path::Path};
+
+/// Model read from a glTF file, ready to be uploaded.
+///
+/// Only the parts of glTF that the generic pipeline can render are read:
+/// triangle meshes with normals and texture coordinates,
+/// at most one skin, and animations of its joints.
+/// Materials, textures, cameras and lights are ignored.
+/// Other features, such as morph targets, are rejected with an error.
+///
+/// glTF is Y-up, whereas the game is Z-up,
+/// so the model is rotated such that its Y axis becomes the Z axis.
+/// This undoes the conversion done by the Blender exporter.
+pub struct ModelData
+{
+    /// Vertices of all meshes of the model.
+    pub vertices: Vec<Vertex>,
+
+    /// Vertex indices of all meshes of the model, three per triangle.
+    pub indices: Vec<u32>,
+
+    /// The skeleton that deforms the model, if the model is skinned.
+    ///
+    /// The bones of the vertices refer to the joints of the skeleton.
+    /// Without a skeleton, all vertices use bone zero,
+    /// which should be set to the identity matrix.
+    pub skeleton: Option<Skeleton>,
+
+    /// Animations of the skeleton, in the order of the file.
+    pub animations: Vec<Animation>,
+}
+
+/// Animation read from a glTF file.
+pub struct Animation
+{
+    /// Name of the animation, as named by the artist.
+    ///
+    /// Unnamed animations are named after their index in the file.
+    pub name: String,
+
+    /// The keyframes of the animation, with times in ticks.
+    pub clip: Clip,
+}
+
+impl ModelData
+{
+    /// Read a model from a `.gltf` or `.glb` file.
+    ///
+    /// Buffers in separate files are read relative to the file.
+    /// glTF measures time in seconds, which are converted to ticks.
+    pub fn import_gltf(path: &Path, ticks_per_second: f32) -> Result<Self>
+    {
+        let data = fs::read(path)
+            .with_context(|| format!("Read {}", path.display()))?;
+        Self::import_gltf_slice(&data, path.parent(), ticks_per_second)
+            .with_context(|| format!("Import {}", path.display()))
+    }
+
+    /// Read a model from the contents of a `.gltf` or `.glb` file.
+    ///
+    /// Buffers in separate files are read relative to the given directory.
+    /// If no directory is given, such buffers are an error.
+    pub fn import_gltf_slice(
+        data: &[u8],
+        directory: Option<&Path>,
+        ticks_per_second: f32,
+    ) -> Result<Self>
+    {
+        let Gltf{document, blob} = Gltf::from_slice(data)
+            .context("Parse glTF")?;
+
+        if let Some(extension) = document.extensions_required().next() {
+            bail!("Required extension {} is not supported", extension);
+        }
+
+        let buffers = read_buffers(&document, blob, directory)?;
+        let importer = Importer{
+            document: &document,
+            buffers: &buffers,
+            parents: parents(&document),
+        };
+
+        let skin = match document.skins().len() {
+            0 => None,
+            1 => document.skins().next(),
+            n => bail!("Model has {} skins; only one is supported", n),
+        };
+        let skeleton = skin.map(|skin| importer.skeleton(&skin)).transpose()?;
+
+        let mut this = Self{
+            vertices: Vec::new(),
+            indices: Vec::new(),
+            skeleton: None,
+            animations: Vec::new(),
+        };
+        importer.meshes(skeleton.as_ref(), &mut this)?;
+        if let Some(skeleton) = skeleton {
+            for animation in document.animations() {
+                let name = match animation.name() {
+                    Some(name) => name.to_owned(),
+                    None => format!("animation {}", animation.index()),
+                };
+                let clip = importer.clip(&animation, &skeleton,
+                                         ticks_per_second)
+                    .with_context(|| format!("Import animation {}", name))?;
+                this.animations.push(Animation{name, clip});
+            }
+            this.skeleton = Some(skeleton.skeleton);
+        } else if document.animations().len() != 0 {
+            bail!("Animations are only supported for skinned models");
+        }
+
+        Ok(this)
+    }
+
+    /// Upload the vertices and indices of the model.
+    pub fn upload<'c>(&self, context: &'c GlContext) -> Result<Model<'c>>
+    {
+        Model::new(context, &self.vertices, &self.indices, self.bones())
+    }
+
+    /// The number of bones that the vertices refer to.
+    ///
+    /// This is the number of joints of the skeleton,
+    /// or one for a model without a skeleton.
+    pub fn bones(&self) -> usize
+    {
+        self.skeleton.as_ref()
+            .map_or(1, |skeleton| skeleton.joints().len())
+            .max(1)
+    }
+}
+
+/// Read the contents of all buffers of a document.
+fn read_buffers(
+    document: &Document,
+    mut blob: Option<Vec<u8>>,
+    directory: Option<&Path>,
+) -> Result<Vec<Vec<u8>>>
+{
+    let mut buffers = Vec::new();
+    for buffer in document.buffers() {
+        let mut data = match buffer.source() {
+            Source::Bin =>
+                blob.take()
+                    .ok_or_else(|| anyhow!("Binary chunk is missing"))?,
+            Source::Uri(uri) if uri.starts_with("data:") => {
+                let (_, encoded) = uri.split_once(";base64,")
+                    .ok_or_else(|| anyhow!("Data URI is not base64"))?;
+                base64::decode(encoded)
+                    .context("Decode base64 data URI")?
+            },
+            Source::Uri(uri) => {
+                let directory = directory.ok_or_else(|| {
+                    anyhow!("External buffer {} has no directory", uri)
+                })?;
+                let path = directory.join(uri);
+                fs::read(&path)
+                    .with_context(|| format!("Read {}", path.display()))?
+            },
+        };
+        ensure!(
+            data.len() >= buffer.length(),
+            "Buffer {} has {} bytes instead of {}",
+            buffer.index(), data.len(), buffer.length(),
+        );
+        // The binary chunk may be padded.
+        data.truncate(buffer.length());
+        buffers.push(data);
+    }
+    Ok(buffers)
+}
+
+/// The parent of each node, by node index.
+fn parents(document: &Document) -> Vec<Option<usize>>
+{
+    let mut parents = vec![None; document.nodes().len()];
+    for node in document.nodes() {
+        for child in node.children() {
+            parents[child.index()] = Some(node.index());
+        }
+    }
+    parents
+}
+
+/// Skeleton together with the mapping from glTF joints to its joints.
+struct ImportedSkeleton
+{
+    skeleton: Skeleton,
+
+    /// Index of the skeleton joint for each joint of the skin.
+    bone_of_joint: Vec<u8>,
+
+    /// Index of the skeleton joint for each node that is a joint.
+    bone_of_node: HashMap<usize, usize>,
+}
+
+/// State shared by the steps of importing a document.
+struct Importer<'a>
+{
+    document: &'a Document,
+    buffers: &'a [Vec<u8>],
+    parents: Vec<Option<usize>>,
+}
+
+impl<'a> Importer<'a>
+{
+    /// Build a skeleton from a skin.
+    ///
+    /// glTF lists joints in any order, but the skeleton needs
+    /// parents to precede their children, so joints are sorted by depth.
+    fn skeleton(&self, skin: &Skin) -> Result<ImportedSkeleton>
+    {
+        let nodes: Vec<Node> = skin.joints().collect();
+        ensure!(
+            nodes.len() <= BONES,
+            "Skin has {} joints; at most {} are supported",
+            nodes.len(), BONES,
+        );
+
+        let is_joint = |index| nodes.iter().any(|n| n.index() == index);
+        let depth = |mut index| {
+            let mut depth = 0;
+            while let Some(parent) = self.parents[index] {
+                depth += 1;
+                index = parent;
+            }
+            depth
+        };
+
+        let mut order: Vec<usize> = (0 .. nodes.len()).collect();
+        order.sort_by_key(|&joint| depth(nodes[joint].index()));
+        let bone_of_node: HashMap<usize, usize> =
+            order.iter().enumerate()
+            .map(|(bone, &joint)| (nodes[joint].index(), bone))
+            .collect();
+
+        let mut joints = Vec::with_capacity(nodes.len());
+        for &joint in &order {
+            let node = &nodes[joint];
+            let name = node_name(node);
+            let parent = self.parents[node.index()];
+            let parent = match parent {
+                Some(parent) if is_joint(parent) =>
+                    Some(bone_of_node[&parent]),
+                _ => {
+                    // The transforms of the ancestors of a root joint
+                    // would have to be applied to every bone matrix.
+                    self.check_identity_ancestors(node)?;
+                    None
+                },
+            };
+            let bind_pose = node_transform(node);
+            joints.push(Joint{name, parent, bind_pose});
+        }
+
+        let reader = skin.reader(|buffer| Some(&self.buffers[buffer.index()]));
+        let inverse_bind_matrices = match reader.read_inverse_bind_matrices() {
+            Some(matrices) => {
+                let matrices: Vec<[[f32; 4]; 4]> = matrices.collect();
+                ensure!(matrices.len() == nodes.len(),
+                        "Skin has {} inverse bind matrices for {} joints",
+                        matrices.len(), nodes.len());
+                order.iter()
+                    .map(|&joint| convert_matrix(&matrices[joint]))
+                    .collect()
+            },
+            None => vec![Mat4::IDENTITY; nodes.len()],
+        };
+
+        let mut bone_of_joint = vec![0; nodes.len()];
+        for (bone, &joint) in order.iter().enumerate() {
+            bone_of_joint[joint] = bone as u8;
+        }
+
+        Ok(ImportedSkeleton{
+            skeleton: Skeleton::with_inverse_bind_matrices(
+                joints,
+                inverse_bind_matrices,
+            ),
+            bone_of_joint,
+            bone_of_node,
+        })
+    }
+
+    fn check_identity_ancestors(&self, joint: &Node) -> Result<()>
+    {
+        let mut index = joint.index();
+        while let Some(parent) = self.parents[index] {
+            let node = self.document.nodes().nth(parent).unwrap();
+            ensure!(
+                node_transform(&node) == Transform::IDENTITY,
+                "Node {} above root joint {} has a transform, \
+                 which is not supported; apply its transform in Blender",
+                node_name(&node), node_name(joint),
+            );
+            index = parent;
+        }
+        Ok(())
+    }
+
+    /// Read the meshes of all nodes of the scene.
+    fn meshes(&self, skeleton: Option<&ImportedSkeleton>, out: &mut ModelData)
+        -> Result<()>
+    {
+        let scene = self.document.default_scene()
+            .or_else(|| self.document.scenes().next())
+            .ok_or_else(|| anyhow!("File has no scenes"))?;
+        let mut stack: Vec<(Node, Mat4)> =
+            scene.nodes().map(|node| (node, Mat4::IDENTITY)).collect();
+        while let Some((node, parent_matrix)) = stack.pop() {
+            let matrix = parent_matrix * node_transform(&node).to_matrix();
+            stack.extend(node.children().map(|child| (child, matrix)));
+
+            let mesh = match node.mesh() {
+                Some(mesh) => mesh,
+                None => continue,
+            };
+            let skinned = match (node.skin(), skeleton) {
+                (Some(_), Some(skeleton)) => Some(skeleton),
+                (None, None) => None,
+                (Some(_), None) => unreachable!("A skin implies a skeleton"),
+                (None, Some(_)) =>
+                    bail!("Mesh {} is not skinned, but the model is; \
+                           mixing skinned and static meshes is not supported",
+                          mesh_name(&mesh)),
+            };
+            for primitive in mesh.primitives() {
+                self.primitive(&primitive, matrix, skinned, out)
+                    .with_context(|| {
+                        format!("Import mesh {} primitive {}",
+                                mesh_name(&mesh), primitive.index())
+                    })?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Read the triangles of a primitive.
+    ///
+    /// Skinned meshes ignore the transforms of their nodes;
+    /// static meshes are transformed into model space.
+    fn primitive(
+        &self,
+        primitive: &Primitive,
+        matrix: Mat4,
+        skeleton: Option<&ImportedSkeleton>,
+        out: &mut ModelData,
+    ) -> Result<()>
+    {
+        ensure!(primitive.mode() == Mode::Triangles,
+                "Primitive mode {:?} is not supported; only triangles are",
+                primitive.mode());
+        ensure!(primitive.morph_targets().len() == 0,
+                "Morph targets are not supported");
+
+        let reader =
+            primitive.reader(|buffer| Some(&self.buffers[buffer.index()]));
+
+        let positions: Vec<Vec3> =
+            reader.read_positions()
+            .ok_or_else(|| anyhow!("Primitive has no positions"))?
+            .map(convert_vector)
+            .collect();
+        let count = positions.len();
+
+        let normals: Vec<Vec3> =
+            reader.read_normals()
+            .ok_or_else(|| anyhow!("Primitive has no normals; \
+                                    export the model with normals"))?
+            .map(convert_vector)
+            .collect();
+
+        // glTF puts the origin of texture coordinates at the top left,
+        // OpenGL at the bottom left.
+        let texcoords: Vec<Vec2> = match reader.read_tex_coords(0) {
+            Some(texcoords) =>
+                texcoords.into_f32().map(|[u, v]| vec2(u, 1.0 - v)).collect(),
+            None => vec![Vec2::ZERO; count],
+        };
+
+        ensure!(reader.read_joints(1).is_none(),
+                "Vertices are influenced by more than {} bones; \
+                 limit the number of bone influences to {} when exporting",
+                BONES_PER_VERTEX, BONES_PER_VERTEX);
+        let influences: Vec<([u8; 4], Vec4)> = match skeleton {
+            Some(skeleton) => read_influences(
+                reader.read_joints(0)
+                    .ok_or_else(|| anyhow!("Skinned primitive has no joints"))?
+                    .into_u16(),
+                reader.read_weights(0)
+                    .ok_or_else(|| anyhow!("Skinned primitive has no weights"))?
+                    .into_f32(),
+                &skeleton.bone_of_joint,
+            )?,
+            None => vec![([0; 4], Vec4::X); count],
+        };
+
+        ensure!(
+            normals.len() == count
+                && texcoords.len() == count
+                && influences.len() == count,
+            "Vertex attributes have different numbers of elements",
+        );
+
+        // Normals are transformed by the inverse transpose,
+        // which preserves their angle with the surface.
+        let (matrix, normal_matrix) = match skeleton {
+            Some(_) => (Mat4::IDENTITY, Mat4::IDENTITY),
+            None => (matrix, matrix.inverse().transpose()),
+        };
+
+        let base = out.vertices.len() as u32;
+        for i in 0 .. count {
+            let (bones, weights) = influences[i];
+            out.vertices.push(Vertex{
+                position: matrix.transform_point3(positions[i]),
+                normal: normal_matrix.transform_vector3(normals[i])
+                    .normalize(),
+                texcoord: texcoords[i],
+                bones,
+                weights,
+            });
+        }
+
+        match reader.read_indices() {
+            Some(indices) => {
+                for index in indices.into_u32() {
+                    ensure!((index as usize) < count,
+                            "Index {} is out of bounds", index);
+                    out.indices.push(base + index);
+                }
+            },
+            None => out.indices.extend(base .. base + count as u32),
+        }
+        ensure!(out.indices.len() % 3 == 0,
+                "Number of indices is not a multiple of three");
+
+        Ok(())
+    }
+
+    /// Read an animation of the joints of a skeleton.
+    fn clip(
+        &self,
+        animation: &animation::Animation,
+        skeleton: &ImportedSkeleton,
+        ticks_per_second: f32,
+    ) -> Result<Clip>
+    {
+        let mut duration: f32 = 0.0;
+        let mut channels = Vec::new();
+        for channel in animation.channels() {
+            let node = channel.target().node();
+            let joint = *skeleton.bone_of_node.get(&node.index())
+                .ok_or_else(|| {
+                    anyhow!("Node {} is animated but is not a joint; \
+                             only skeletal animation is supported",
+                            node_name(&node))
+                })?;
+
+            let interpolation = match channel.sampler().interpolation() {
+                animation::Interpolation::Step => Interpolation::Step,
+                animation::Interpolation::Linear => Interpolation::Linear,
+                animation::Interpolation::CubicSpline =>
+                    bail!("Cubic spline interpolation is not supported; \
+                           bake the animation with linear interpolation"),
+            };
+
+            let reader =
+                channel.reader(|buffer| Some(&self.buffers[buffer.index()]));
+            let times: Vec<f32> =
+                reader.read_inputs()
+                .ok_or_else(|| anyhow!("Channel has no keyframe times"))?
+                .map(|seconds| seconds * ticks_per_second)
+                .collect();
+            ensure!(!times.is_empty(), "Channel has no keyframes");
+            ensure!(times.windows(2).all(|w| w[0] < w[1]),
+                    "Keyframe times of channel do not increase");
+            duration = duration.max(times[times.len() - 1]);
+
+            let outputs = reader.read_outputs()
+                .ok_or_else(|| anyhow!("Channel has no keyframe values"))?;
+            let keyframes = match outputs {
+                ReadOutputs::Translations(values) => Keyframes::Translation(
+                    zip_keyframes(&times, values.map(convert_vector))?,
+                ),
+                ReadOutputs::Rotations(values) => Keyframes::Rotation(
+                    zip_keyframes(&times, values.into_f32().map(convert_quat))?,
+                ),
+                ReadOutputs::Scales(values) => Keyframes::Scale(
+                    zip_keyframes(&times, values.map(convert_scale))?,
+                ),
+                ReadOutputs::MorphTargetWeights(_) =>
+                    bail!("Morph target animation is not supported"),
+            };
+
+            channels.push(Channel::new(joint, interpolation, keyframes));
+        }
+        Ok(Clip::new(duration, channels))
+    }
+}
+
+/// Pair keyframe times with their values.
+fn zip_keyframes<T, I>(times: &[f32], values: I) -> Result<Vec<(f32, T)>>
+    where I: Iterator<Item=T>
+{
+    let keyframes: Vec<(f32, T)> = times.iter().copied().zip(values).collect();
+    ensure!(keyframes.len() == times.len(),
+            "Channel has fewer values than keyframe times");
+    Ok(keyframes)
+}
+
+/// Convert glTF joints and weights into bone indices and weights.
+///
+/// Weights are normalized, as not all exporters do so exactly.
+fn read_influences<J, W>(joints: J, weights: W, bone_of_joint: &[u8])
+    -> Result<Vec<([u8; 4], Vec4)>>
+    where J: Iterator<Item=[u16; 4]>
+        , W: Iterator<Item=[f32; 4]>
+{
+    let mut influences = Vec::new();
+    for (joints, weights) in joints.zip(weights) {
+        let mut bones = [0; BONES_PER_VERTEX];
+        for (bone, &joint) in bones.iter_mut().zip(&joints) {
+            *bone = *bone_of_joint.get(joint as usize)
+                .ok_or_else(|| anyhow!("Joint {} is out of bounds", joint))?;
+        }
+        let weights = Vec4::from(weights);
+        let sum = weights.dot(Vec4::ONE);
+        ensure!(sum > 0.0, "Vertex is not influenced by any bone");
+        influences.push((bones, weights / sum));
+    }
+    Ok(influences)
+}
+
+fn node_name(node: &Node) -> String
+{
+    match node.name() {
+        Some(name) => name.to_owned(),
+        None => format!("#{}", node.index()),
+    }
+}
+
+fn mesh_name(mesh: &Mesh) -> String
+{
+    match mesh.name() {
+        Some(name) => name.to_owned(),
+        None => format!("#{}", mesh.index()),
+    }
+}
+
+/// The transform of a node relative to its parent.
+fn node_transform(node: &Node) -> Transform
+{
+    let (translation, rotation, scale) = node.transform().decomposed();
+    Transform{
+        translation: convert_vector(translation),
+        rotation: convert_quat(rotation),
+        scale: convert_scale(scale),
+    }
+}
+
+// The conversion from Y-up to Z-up is a rotation about the X axis
+// that takes Y to Z and Z to -Y. The functions below conjugate
+// glTF vectors, rotations, scales and matrices with that rotation.
+
+fn convert_vector([x, y, z]: [f32; 3]) -> Vec3
+{
+    vec3(x, -z, y)
+}
+
+fn convert_scale([x, y, z]: [f32; 3]) -> Vec3
+{
+    vec3(x, z, y)
+}
+
+fn convert_quat([x, y, z, w]: [f32; 4]) -> Quat
+{
+    Quat::from_xyzw(x, -z, y, w).normalize()
+}
+
+fn convert_matrix(matrix: &[[f32; 4]; 4]) -> Mat4
+{
+    let rotation = Mat4::from_rotation_x(std::f32::consts::FRAC_PI_2);
+    rotation * Mat4::from_cols_array_2d(matrix) * rotation.inverse()
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    /// glTF file with a single skinned triangle, in a data URI.
+    ///
+    /// The vertices lie at the origin and at one unit along X and along Y.
+    /// Joint 1 is listed before its parent, joint 0,
+    /// and is translated one unit along Z by an animation of one second.
+    fn skinned_triangle() -> String
+    {
+        let mut buffer = Vec::new();
+        let mut push = |values: &[f32]| {
+            let offset = buffer.len();
+            for value in values {
+                buffer.extend_from_slice(&value.to_le_bytes());
+            }
+            offset
+        };
+        let positions = push(&[0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 0.0]);
+        let normals = push(&[0.0, 0.0, 1.0, 0.0, 0.0, 1.0, 0.0, 0.0, 1.0]);
+        let weights = push(&[1.0, 0.0, 0.0, 0.0, 0.5, 0.5, 0.0, 0.0,
+                             2.0, 0.0, 0.0, 0.0]);
+        let times = push(&[0.0, 1.0]);
+        let translations = push(&[0.0, 0.0, 0.0, 0.0, 0.0, 1.0]);
+        let joints = buffer.len();
+        buffer.extend_from_slice(&[1, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0]);
+
+        format!(r#"{{
+            "asset": {{"version": "2.0"}},
+            "scene": 0,
+            "scenes": [{{"nodes": [0, 2]}}],
+            "nodes": [
+                {{"name": "root", "children": [1]}},
+                {{"name": "tip", "translation": [0, 1, 0]}},
+                {{"name": "mesh", "mesh": 0, "skin": 0}}
+            ],
+            "skins": [{{"joints": [1, 0]}}],
+            "meshes": [{{"primitives": [{{"attributes": {{
+                "POSITION": 0, "NORMAL": 1, "WEIGHTS_0": 2, "JOINTS_0": 3
+            }}}}]}}],
+            "animations": [{{
+                "name": "lift",
+                "channels": [{{
+                    "sampler": 0,
+                    "target": {{"node": 1, "path": "translation"}}
+                }}],
+                "samplers": [{{"input": 4, "output": 5}}]
+            }}],
+            "accessors": [
+                {{"bufferView": 0, "byteOffset": {positions},
+                  "componentType": 5126, "count": 3, "type": "VEC3",
+                  "min": [0, 0, 0], "max": [1, 1, 0]}},
+                {{"bufferView": 0, "byteOffset": {normals},
+                  "componentType": 5126, "count": 3, "type": "VEC3"}},
+                {{"bufferView": 0, "byteOffset": {weights},
+                  "componentType": 5126, "count": 3, "type": "VEC4"}},
+                {{"bufferView": 0, "byteOffset": {joints},
+                  "componentType": 5121, "count": 3, "type": "VEC4"}},
+                {{"bufferView": 0, "byteOffset": {times},
+                  "componentType": 5126, "count": 2, "type": "SCALAR",
+                  "min": [0], "max": [1]}},
+                {{"bufferView": 0, "byteOffset": {translations},
+                  "componentType": 5126, "count": 2, "type": "VEC3"}}
+            ],
+            "bufferViews": [{{"buffer": 0, "byteLength": {length}}}],
+            "buffers": [{{
+                "byteLength": {length},
+                "uri": "data:application/octet-stream;base64,{data}"
+            }}]
+        }}"#,
+            positions = positions,
+            normals = normals,
+            weights = weights,
+            joints = joints,
+            times = times,
+            translations = translations,
+            length = buffer.len(),
+            data = base64::encode(&buffer),
+        )
+    }
+
+    #[test]
+    fn import_skinned_triangle()
+    {
+        let gltf = skinned_triangle();
+        let model = ModelData::import_gltf_slice(gltf.as_bytes(), None, 20.0)
+            .unwrap();
+
+        // Y-up is converted to Z-up.
+        let positions: Vec<Vec3> =
+            model.vertices.iter().map(|v| v.position).collect();
+        assert_eq!(positions, [Vec3::ZERO, Vec3::X, Vec3::Z]);
+        assert_eq!(model.vertices[0].normal, -Vec3::Y);
+        assert_eq!(model.indices, [0, 1, 2]);
+
+        // Joints are reordered so that the parent comes first,
+        // and weights are normalized.
+        let skeleton = model.skeleton.as_ref().unwrap();
+        let names: Vec<&str> =
+            skeleton.joints().iter().map(|j| j.name.as_str()).collect();
+        assert_eq!(names, ["root", "tip"]);
+        assert_eq!(model.bones(), 2);
+        assert_eq!(skeleton.joints()[1].parent, Some(0));
+        assert_eq!(model.vertices[0].bones, [0, 1, 1, 1]);
+        assert_eq!(model.vertices[1].weights, Vec4::new(0.5, 0.5, 0.0, 0.0));
+        assert_eq!(model.vertices[2].weights, Vec4::X);
+
+        // Seconds are converted to ticks.
+        assert_eq!(model.animations.len(), 1);
+        let animation = &model.animations[0];
+        assert_eq!(animation.name, "lift");
+        assert_eq!(animation.clip.duration(), 20.0);
+        assert_eq!(animation.clip.channels()[0].joint(), 1);
+
+        let mut pose = skeleton.bind_pose();
+        animation.clip.sample(10.0, &mut pose);
+        assert_eq!(pose.transforms[1].translation, vec3(0.0, -0.5, 0.0));
+    }
+
+    #[test]
+    fn reject_unsupported_features()
+    {
+        let gltf = skinned_triangle()
+            .replace(r#""attributes""#, r#""mode": 1, "attributes""#);
+        let err = ModelData::import_gltf_slice(gltf.as_bytes(), None, 20.0)
+            .err().unwrap();
+        assert!(format!("{:?}", err).contains("only triangles"));
+
+        let gltf = skinned_triangle()
+            .replace(r#""input": 4"#,
+                     r#""interpolation": "CUBICSPLINE", "input": 4"#);
+        let err = ModelData::import_gltf_slice(gltf.as_bytes(), None, 20.0)
+            .err().unwrap();
+        assert!(format!("{:?}", err).contains("Cubic spline"));
+    }
+}
@@ -0,0 +1,114 @@
+use super::{BONES, Instance, instanced_vertex_interface};
+use crate::client::graphics::{GlBuffer, GlContext};
+use anyhow::Result;
+use glam::Mat4;
+use opengl::gl;
+use std::borrow::Borrow;
+
+/// Element of the instances storage buffer.
+///
+/// The layout of this struct must match the std430 layout
+/// of the `Instance` struct in the instanced vertex shader.
+/// In std430, structs are aligned to their largest member,
+/// which is a mat4, hence the padding.
+#[derive(Clone, Copy)]
+#[repr(C)]
+struct InstanceData
+{
+    m_matrix: Mat4,
+    bone_offset: u32,
+    _padding: [u32; 3],
+}
+
+/// Instances of models, stored in buffers for instanced rendering.
+///
+/// All instances of all models are uploaded at once,
+/// after which each model can be drawn with a single draw call
+/// by [`Pipeline::render_instanced`](super::Pipeline::render_instanced),
+/// as many times as needed, for instance once per shadow cascade.
+pub struct InstanceBuffer<'c>
+{
+    instances: GlBuffer<'c, InstanceData>,
+    bone_palettes: GlBuffer<'c, Mat4>,
+
+    // INVARIANT: Each range lies within the instances buffer.
+    ranges: Vec<(u32, u32)>,
+
+    bones: usize,
+}
+
+impl<'c> InstanceBuffer<'c>
+{
+    /// Create a buffer with no instances.
+    pub fn new(context: &'c GlContext) -> Result<Self>
+    {
+        let instances = GlBuffer::new(context)?;
+        instances.label("generic instances")?;
+        let bone_palettes = GlBuffer::new(context)?;
+        bone_palettes.label("generic bone palettes")?;
+        Ok(Self{instances, bone_palettes, ranges: Vec::new(), bones: 0})
+    }
+
+    /// Replace the instances with those of the given models.
+    ///
+    /// For each model, pass the sequence of its instances.
+    /// Only the first `bones` bone matrices of each instance are uploaded,
+    /// so this must be at least the number of bones of the pipelines
+    /// that render the instances.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the number of bones is greater than [`BONES`].
+    pub fn upload<I, J, N>(&mut self, bones: usize, models: I) -> Result<()>
+        where I: IntoIterator<Item=J>
+            , J: IntoIterator<Item=N>
+            , N: Borrow<Instance>
+    {
+        assert!(bones <= BONES);
+
+        let mut instances = Vec::new();
+        let mut bone_palettes = Vec::new();
+        self.ranges.clear();
+        for model in models {
+            let first = instances.len() as u32;
+            for instance in model {
+                let instance = instance.borrow();
+                instances.push(InstanceData{
+                    m_matrix: instance.m_matrix,
+                    bone_offset: bone_palettes.len() as u32,
+                    _padding: [0; 3],
+                });
+                bone_palettes.extend(&instance.bone_matrices[.. bones]);
+            }
+            let count = instances.len() as u32 - first;
+            self.ranges.push((first, count));
+        }
+        self.bones = bones;
+
+        self.instances.upload(&instances, gl::STREAM_DRAW)?;
+        self.bone_palettes.upload(&bone_palettes, gl::STREAM_DRAW)?;
+        Ok(())
+    }
+
+    /// The number of bone matrices uploaded for each instance.
+    pub fn bones(&self) -> usize
+    {
+        self.bones
+    }
+
+    /// The index of the first instance and the number of instances
+    /// of each model, in the order they were uploaded.
+    pub(super) fn ranges(&self) -> &[(u32, u32)]
+    {
+        &self.ranges
+    }
+
+    /// Bind the storage buffers for drawing.
+    pub(super) fn bind(&self) -> Result<()>
+    {
+        use instanced_vertex_interface::bindings::{BONE_PALETTES, INSTANCES};
+        self.instances.bind_base(gl::SHADER_STORAGE_BUFFER, INSTANCES)?;
+        self.bone_palettes.bind_base(gl::SHADER_STORAGE_BUFFER, BONE_PALETTES)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,76 @@
+#version 450 core
+
+// Variant of shader.vert that reads the parameters of each instance
+// from storage buffers, so that all instances of a model
+// can be drawn with a single draw call.
+
+// Must match generic::InstanceData, with std430 layout.
+struct Instance
+{
+    mat4 m_matrix;
+    uint bone_offset;
+};
+
+layout(binding = 0, std430) readonly buffer Instances
+{
+    Instance instances[];
+};
+
+// The bone matrices of all instances, one palette after the other.
+layout(binding = 1, std430) readonly buffer BonePalettes
+{
+    mat4 bone_palettes[];
+};
+
+layout(location = 0) uniform mat4 vp_matrix;
+
+// Index of the first instance of the model in the instances buffer.
+layout(location = 1) uniform uint first_instance;
+
+layout(location = 0) in vec3 vertex_position;
+layout(location = 1) in vec2 vertex_texcoord;
+layout(location = 2) in uvec4 vertex_bones;
+layout(location = 3) in vec3 vertex_normal;
+layout(location = 4) in vec4 vertex_weights;
+
+layout(location = 0) out vec2 fragment_uv;
+layout(location = 1) out float fragment_ao;
+layout(location = 2) out vec2 fragment_light;
+layout(location = 3) out vec3 fragment_normal;
+layout(location = 4) out vec3 fragment_position;
+
+void main()
+{
+    Instance instance = instances[first_instance + gl_InstanceID];
+    uvec4 bones = instance.bone_offset + vertex_bones;
+
+    // Blend the bone matrices by the weights of the bones.
+    // Unused bones have zero weight, so they do not contribute.
+    mat4 bone_matrix =
+        vertex_weights.x * bone_palettes[bones.x] +
+        vertex_weights.y * bone_palettes[bones.y] +
+        vertex_weights.z * bone_palettes[bones.z] +
+        vertex_weights.w * bone_palettes[bones.w];
+
+    // The fragment shader needs the world position for shadow mapping.
+    vec4 world_position =
+        instance.m_matrix *
+        bone_matrix *
+        vec4(vertex_position, 1.0);
+    fragment_position = world_position.xyz;
+
+    gl_Position = vp_matrix * world_position;
+
+    // Transform the normal into world space.
+    // This assumes the matrices do not scale non-uniformly,
+    // as otherwise we would need the inverse transpose.
+    fragment_normal = mat3(instance.m_matrix * bone_matrix) * vertex_normal;
+
+    // Pass through texture coordinate.
+    fragment_uv = vertex_texcoord;
+
+    // Models are not subject to ambient occlusion,
+    // and they are lit as if they were under the open sky.
+    fragment_ao = 1.0;
+    fragment_light = vec2(1.0, 0.0);
+}
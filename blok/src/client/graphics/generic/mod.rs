@@ -1,17 +1,46 @@
 //! Pipeline for rendering triangle meshes.
 
 pub use self::fragment_shader::*;
+pub use self::gltf_import::*;
+pub use self::instance_buffer::*;
 
 use crate::{
-    client::graphics::{GlBuffer, GlProgram, GlShader, GlUniform},
+    client::graphics::{
+        GlBuffer,
+        GlContext,
+        GlProgram,
+        GlShader,
+        GlVertexArray,
+    },
+    gl_vertex,
     try_gl,
 };
-use anyhow::Result;
-use glam::{Mat4, Vec2, Vec3};
-use opengl::gl::{self, types::*};
-use std::{borrow::Borrow, mem::size_of, ptr::null};
+use anyhow::{Result, bail, ensure};
+use glam::{Mat4, Vec2, Vec3, Vec4};
+use opengl::gl;
+use std::{borrow::Borrow, ptr::null};
+
+#[cfg(all(feature = "shader-hot-reload", debug_assertions))]
+use crate::client::graphics::hot_reload::ShaderReloader;
 
 mod fragment_shader;
+mod gltf_import;
+mod instance_buffer;
+
+/// Interface of the vertex shader, reflected by the build script.
+mod vertex_interface
+{
+    include!(
+        concat!(
+            env!("OUT_DIR"),
+            "/client/graphics/generic/shader.vert.rs",
+        )
+    );
+}
+
+/// Path of the GLSL source of the vertex shader.
+pub const VERTEX_SHADER_PATH: &str =
+    "src/client/graphics/generic/shader.vert";
 
 static VERTEX_SHADER_BINARY: &'static [u8] =
     include_bytes!(
@@ -21,8 +50,37 @@ static VERTEX_SHADER_BINARY: &'static [u8] =
         )
     );
 
+/// Interface of the instanced vertex shader, reflected by the build script.
+mod instanced_vertex_interface
+{
+    include!(
+        concat!(
+            env!("OUT_DIR"),
+            "/client/graphics/generic/instanced.vert.rs",
+        )
+    );
+}
+
+/// Path of the GLSL source of the instanced vertex shader.
+pub const INSTANCED_VERTEX_SHADER_PATH: &str =
+    "src/client/graphics/generic/instanced.vert";
+
+static INSTANCED_VERTEX_SHADER_BINARY: &'static [u8] =
+    include_bytes!(
+        concat!(
+            env!("OUT_DIR"),
+            "/client/graphics/generic/instanced.vert.spv",
+        )
+    );
+
 /// Maximum number of bones supported.
-pub const BONES: usize = 6;
+///
+/// Pipelines may be built for fewer bones,
+/// in which case only that many bone matrices are uploaded.
+pub const BONES: usize = 32;
+
+/// Maximum number of bones that influence a single vertex.
+pub const BONES_PER_VERTEX: usize = 4;
 
 /// Vertex in a model’s vertex buffer.
 #[derive(Clone, Copy)]
@@ -32,24 +90,113 @@ pub struct Vertex
     /// Position of the vertex in model space.
     pub position: Vec3,
 
+    /// Normal of the surface at the vertex in model space.
+    ///
+    /// This must be normalized.
+    pub normal: Vec3,
+
     /// Texture coordinates of the vertex.
     pub texcoord: Vec2,
 
-    /// Index of the bones to apply.
+    /// Indices of the bones that influence the vertex.
     ///
-    /// At the moment exactly one bone is applied to each vertex.
+    /// Each index must be less than the number of bones of the model,
+    /// even if its weight is zero, which [`Model::new`] checks.
     /// To apply no bone, set the bone to the identity matrix.
-    pub bone: u32,
+    pub bones: [u8; BONES_PER_VERTEX],
+
+    /// Weights of the bones that influence the vertex.
+    ///
+    /// The weights must sum to one.
+    /// Bones that do not influence the vertex must have zero weight.
+    pub weights: Vec4,
+}
+
+gl_vertex! {
+    Vertex {
+        position: vertex_interface::inputs::VERTEX_POSITION,
+        normal: vertex_interface::inputs::VERTEX_NORMAL,
+        texcoord: vertex_interface::inputs::VERTEX_TEXCOORD,
+        bones: vertex_interface::inputs::VERTEX_BONES,
+        weights: vertex_interface::inputs::VERTEX_WEIGHTS,
+    }
 }
 
 /// Vertex and index buffer for a model.
-pub struct Model
+///
+/// The buffers are only created by [`Model::new`],
+/// which checks that drawing the model stays within its buffers.
+pub struct Model<'c>
 {
-    /// The vertices of the model.
-    pub vertices: GlBuffer<Vertex>,
+    vertices: GlBuffer<'c, Vertex>,
+
+    // INVARIANT: Each index refers to a vertex in the vertex buffer.
+    indices: GlBuffer<'c, u32>,
 
-    /// The vertex indices of the model.
-    pub indices: GlBuffer<u32>,
+    // INVARIANT: This is at least one and at most BONES,
+    //            and each bone of each vertex is less than this.
+    bones: usize,
+}
+
+impl<'c> Model<'c>
+{
+    /// Upload the vertices and vertex indices of a model
+    /// whose vertices refer to the given number of bones.
+    ///
+    /// The number of bones is the number of bone matrices
+    /// used for each instance.
+    /// This fails if an index does not refer to a vertex,
+    /// if the number of bones is zero or greater than [`BONES`],
+    /// or if a vertex refers to a bone that is not less than it.
+    pub fn new(
+        context: &'c GlContext,
+        vertices: &[Vertex],
+        indices: &[u32],
+        bones: usize,
+    ) -> Result<Self>
+    {
+        ensure!(
+            (1 ..= BONES).contains(&bones),
+            "Model has {} bones; between 1 and {} are supported",
+            bones, BONES,
+        );
+        let vertex_count = vertices.len();
+        if let Some(index) =
+            indices.iter().find(|&&i| i as usize >= vertex_count)
+        {
+            bail!("Index {} refers to none of {} vertices",
+                  index, vertex_count);
+        }
+        if let Some(bone) =
+            vertices.iter()
+            .flat_map(|vertex| &vertex.bones)
+            .find(|&&bone| bone as usize >= bones)
+        {
+            bail!("Vertex refers to bone {} of {} bones", bone, bones);
+        }
+
+        Ok(Self{
+            vertices: GlBuffer::new_upload(context, vertices,
+                                           gl::STATIC_DRAW)?,
+            indices: GlBuffer::new_upload(context, indices,
+                                          gl::STATIC_DRAW)?,
+            bones,
+        })
+    }
+
+    /// The number of bones that the vertices refer to.
+    pub fn bones(&self) -> usize
+    {
+        self.bones
+    }
+
+    /// Give the buffers of the model names that appear in debug messages.
+    pub fn label(&self, label: &str) -> Result<()>
+    {
+        self.vertices.label(&format!("{} vertices", label))?;
+        self.indices.label(&format!("{} indices", label))?;
+        Ok(())
+    }
 }
 
 /// Parameters for a single rendering of a model.
@@ -59,75 +206,164 @@ pub struct Instance
     pub m_matrix: Mat4,
 
     /// Bone matrices for the instance.
+    ///
+    /// Only the bones up to the number of bones of the model are used.
     pub bone_matrices: [Mat4; BONES],
 }
 
 /// Pipeline for rendering triangle meshes.
-pub struct Pipeline
+pub struct Pipeline<'c>
 {
-    program: GlProgram,
-    vertex_array: GLuint,
+    context: &'c GlContext,
+    program: GlProgram<'c>,
+    instanced_program: GlProgram<'c>,
+    vertex_array: GlVertexArray<'c>,
+    bones: usize,
 }
 
-impl Drop for Pipeline
+impl<'c> Pipeline<'c>
 {
-    fn drop(&mut self)
+    /// Compile the pipeline for models with the given number of bones.
+    ///
+    /// The vertex shader is specialized for the number of bones,
+    /// so pipelines for models with fewer bones upload fewer matrices.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the number of bones is zero or greater than [`BONES`].
+    pub fn new(
+        context: &'c GlContext,
+        fragment_shader: &FragmentShader,
+        bones: usize,
+    ) -> Result<Self>
     {
-        // SAFETY: Provided by caller of `new`.
-        unsafe {
-            gl::DeleteVertexArrays(1, &self.vertex_array);
-        }
+        Self::new_impl(context, Some(fragment_shader), bones)
     }
-}
 
-impl Pipeline
-{
-    /// Compile the pipeline.
-    #[doc = crate::doc_safety_opengl!()]
-    pub unsafe fn new(fragment_shader: &FragmentShader) -> Result<Self>
+    /// Compile a variant of the pipeline that only writes depth.
+    ///
+    /// This is used for rendering shadow maps.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the number of bones is zero or greater than [`BONES`].
+    pub fn new_depth_only(context: &'c GlContext, bones: usize)
+        -> Result<Self>
     {
-        let program = Self::make_program(fragment_shader)?;
-        let mut this = Self{program, vertex_array: 0};
-        this.make_vertex_array()?;
-        Ok(this)
+        Self::new_impl(context, None, bones)
     }
 
-    unsafe fn make_program(fragment_shader: &FragmentShader)
-        -> Result<GlProgram>
+    fn new_impl(
+        context: &'c GlContext,
+        fragment_shader: Option<&FragmentShader>,
+        bones: usize,
+    ) -> Result<Self>
     {
-        let vertex_shader = GlShader::new(
-            /* shader_type      */ gl::VERTEX_SHADER,
-            /* shader_binary    */ VERTEX_SHADER_BINARY,
-            /* constant_indices */ &[0],
-            /* constant_values  */ &[BONES as _],
+        assert!((1 ..= BONES).contains(&bones));
+        let program = Self::make_program(
+            /* context              */ context,
+            /* vertex_shader_binary */ VERTEX_SHADER_BINARY,
+            /* fragment_shader      */ fragment_shader,
+            /* bones                */ bones,
+        )?;
+        let instanced_program = Self::make_instanced_program(
+            /* context              */ context,
+            /* vertex_shader_binary */ INSTANCED_VERTEX_SHADER_BINARY,
+            /* fragment_shader      */ fragment_shader,
         )?;
-        GlProgram::new(&[&vertex_shader, fragment_shader.as_shader()])
+        let vertex_array = Self::make_vertex_array(context)?;
+        Ok(Self{context, program, instanced_program, vertex_array, bones})
     }
 
-    unsafe fn make_vertex_array(&mut self) -> Result<()>
+    /// Rebuild the program from the most recent shader binaries.
+    ///
+    /// The fragment shader must be given if and only if
+    /// the pipeline was not created with `new_depth_only`.
+    /// If this fails, the pipeline keeps using its previous program.
+    #[cfg(all(feature = "shader-hot-reload", debug_assertions))]
+    pub fn reload(
+        &mut self,
+        reloader: &ShaderReloader,
+        fragment_shader: Option<&FragmentShader>,
+    ) -> Result<()>
     {
-        // Create vertex array.
-        try_gl! { gl::CreateVertexArrays(1, &mut self.vertex_array); }
-
-        // Convenient alias.
-        let vao = self.vertex_array;
-
-        // Enable vertex attributes.
-        try_gl! { gl::EnableVertexArrayAttrib(vao, 0); }
-        try_gl! { gl::EnableVertexArrayAttrib(vao, 1); }
-        try_gl! { gl::EnableVertexArrayAttrib(vao, 2); }
+        let program = Self::make_program(
+            /* context              */ self.context,
+            /* vertex_shader_binary */
+                reloader.binary(VERTEX_SHADER_PATH, VERTEX_SHADER_BINARY),
+            /* fragment_shader      */ fragment_shader,
+            /* bones                */ self.bones,
+        )?;
+        let instanced_program = Self::make_instanced_program(
+            /* context              */ self.context,
+            /* vertex_shader_binary */
+                reloader.binary(INSTANCED_VERTEX_SHADER_PATH,
+                                INSTANCED_VERTEX_SHADER_BINARY),
+            /* fragment_shader      */ fragment_shader,
+        )?;
+        self.program = program;
+        self.instanced_program = instanced_program;
+        Ok(())
+    }
 
-        // Associate the attributes with the sole binding.
-        try_gl! { gl::VertexArrayAttribBinding(vao, 0, 0); }
-        try_gl! { gl::VertexArrayAttribBinding(vao, 1, 0); }
-        try_gl! { gl::VertexArrayAttribBinding(vao, 2, 0); }
+    fn make_program(
+        context: &'c GlContext,
+        vertex_shader_binary: &[u8],
+        fragment_shader: Option<&FragmentShader>,
+        bones: usize,
+    ) -> Result<GlProgram<'c>>
+    {
+        let vertex_shader = GlShader::new(
+            /* context          */ context,
+            /* shader_type      */ gl::VERTEX_SHADER,
+            /* source_path      */ VERTEX_SHADER_PATH,
+            /* shader_binary    */ vertex_shader_binary,
+            /* constant_indices */
+                &[vertex_interface::specialization_constants::BONES],
+            /* constant_values  */ &[bones as _],
+        )?;
+        let (program, label) = match fragment_shader {
+            Some(fs) => (GlProgram::new(context,
+                                        &[&vertex_shader, fs.as_shader()])?,
+                         "generic pipeline"),
+            None     => (GlProgram::new(context, &[&vertex_shader])?,
+                         "generic depth-only pipeline"),
+        };
+        program.label(label)?;
+        Ok(program)
+    }
 
-        // Configure the formats of the attributes.
-        try_gl! { gl::VertexArrayAttribFormat(vao, 0, 3, gl::FLOAT, gl::FALSE, 0); }
-        try_gl! { gl::VertexArrayAttribFormat(vao, 1, 2, gl::FLOAT, gl::FALSE, 12); }
-        try_gl! { gl::VertexArrayAttribIFormat(vao, 2, 1, gl::UNSIGNED_INT, 20); }
+    fn make_instanced_program(
+        context: &'c GlContext,
+        vertex_shader_binary: &[u8],
+        fragment_shader: Option<&FragmentShader>,
+    ) -> Result<GlProgram<'c>>
+    {
+        let vertex_shader = GlShader::new(
+            /* context          */ context,
+            /* shader_type      */ gl::VERTEX_SHADER,
+            /* source_path      */ INSTANCED_VERTEX_SHADER_PATH,
+            /* shader_binary    */ vertex_shader_binary,
+            /* constant_indices */ &[],
+            /* constant_values  */ &[],
+        )?;
+        let (program, label) = match fragment_shader {
+            Some(fs) => (GlProgram::new(context,
+                                        &[&vertex_shader, fs.as_shader()])?,
+                         "generic instanced pipeline"),
+            None     => (GlProgram::new(context, &[&vertex_shader])?,
+                         "generic instanced depth-only pipeline"),
+        };
+        program.label(label)?;
+        Ok(program)
+    }
 
-        Ok(())
+    fn make_vertex_array(context: &'c GlContext) -> Result<GlVertexArray<'c>>
+    {
+        let mut vertex_array = GlVertexArray::new(context)?;
+        vertex_array.label("generic vertex array")?;
+        vertex_array.vertex_layout::<Vertex>(0)?;
+        Ok(vertex_array)
     }
 
     /// Render a collection of instances of models.
@@ -137,17 +373,21 @@ impl Pipeline
     /// For each model you also pass a sequence of instances.
     /// The pipeline will set up rendering of each model only once,
     /// then render all instances of that model in sequence.
-    #[doc = crate::doc_safety_opengl!()]
-    pub unsafe fn render<I, J, M, N>(&self, vp_matrix: &Mat4, models: I)
+    ///
+    /// # Panics
+    ///
+    /// Panics if a model has more bones than the pipeline.
+    pub fn render<'m, I, J, M, N>(&self, vp_matrix: &Mat4, models: I)
         -> Result<()>
         where I: IntoIterator<Item=(M, J)>
             , J: IntoIterator<Item=N>
-            , M: Borrow<Model>
+            , M: Borrow<Model<'m>>
             , N: Borrow<Instance>
     {
-        self.pre_render()?;
+        self.pre_render(&self.program)?;
         for (model, instances) in models {
             let model = model.borrow();
+            assert!(model.bones <= self.bones);
             self.pre_render_model(model)?;
             for instance in instances {
                 let instance = instance.borrow();
@@ -157,47 +397,99 @@ impl Pipeline
         Ok(())
     }
 
-    /// Implementation detail of `render`.
-    unsafe fn pre_render(&self) -> Result<()>
+    /// Render the instances in an instance buffer,
+    /// with a single draw call per model.
+    ///
+    /// The models must be given in the order in which
+    /// their instances were uploaded to the instance buffer.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the number of models differs from the number of models
+    /// whose instances were uploaded, or if fewer bone matrices
+    /// were uploaded per instance than the pipeline or a model has bones.
+    pub fn render_instanced<'m, I, M>(
+        &self,
+        vp_matrix: &Mat4,
+        instance_buffer: &InstanceBuffer,
+        models: I,
+    ) -> Result<()>
+        where I: IntoIterator<Item=M>
+            , M: Borrow<Model<'m>>
     {
-        // Select program and vertex array.
-        try_gl! { gl::UseProgram(self.program.as_raw()); }
-        try_gl! { gl::BindVertexArray(self.vertex_array); }
+        assert!(instance_buffer.bones() >= self.bones);
+
+        self.pre_render(&self.instanced_program)?;
+        instance_buffer.bind()?;
+
+        use instanced_vertex_interface::uniforms::*;
+        VP_MATRIX.set(self.context, vp_matrix)?;
 
-        // Configure face culling.
-        try_gl! { gl::Enable(gl::CULL_FACE); }
-        try_gl! { gl::CullFace(gl::BACK); }
-        try_gl! { gl::FrontFace(gl::CCW); }
+        let mut ranges = instance_buffer.ranges().iter();
+        for model in models {
+            let model = model.borrow();
+            assert!(model.bones <= instance_buffer.bones());
+            let &(first, count) = ranges.next()
+                .expect("More models than uploaded to the instance buffer");
+            if count == 0 {
+                continue;
+            }
+            self.pre_render_model(model)?;
+            FIRST_INSTANCE.set(self.context, &first)?;
+
+            // Draw all instances of the model.
+            // SAFETY: The context is current,
+            //         the count matches the bound index buffer,
+            //         the indices refer to vertices in the vertex buffer,
+            //         the instances lie within the instances buffer,
+            //         and their bones lie within the bone palettes,
+            //         as the model has no more bones than were uploaded.
+            unsafe {
+                try_gl! {
+                    gl::DrawElementsInstanced(
+                        /* mode          */ gl::TRIANGLES,
+                        /* count         */ model.indices.len() as _,
+                        /* type          */ gl::UNSIGNED_INT,
+                        /* indices       */ null(),
+                        /* instancecount */ count as _,
+                    );
+                }
+            }
+        }
+        assert!(ranges.next().is_none(),
+                "Fewer models than uploaded to the instance buffer");
 
         Ok(())
     }
 
-    /// Implementation detail of `render`.
-    unsafe fn pre_render_model(&self, model: &Model) -> Result<()>
-    {
-        // Bind vertex buffer.
-        try_gl! {
-            gl::BindVertexBuffer(
-                /* bindingindex */ 0,
-                /* buffer       */ model.vertices.as_raw(),
-                /* offset       */ 0,
-                /* stride       */ size_of::<Vertex>() as _,
-            );
-        }
+    /// Implementation detail of `render` and `render_instanced`.
+    fn pre_render(&self, program: &GlProgram) -> Result<()>
+    {
+        // Select program and vertex array.
+        self.vertex_array.bind()?;
+        // SAFETY: The context is current.
+        unsafe {
+            try_gl! { gl::UseProgram(program.as_raw()); }
 
-        // Bind index buffer.
-        try_gl! {
-            gl::BindBuffer(
-                /* target */ gl::ELEMENT_ARRAY_BUFFER,
-                /* buffer */ model.indices.as_raw(),
-            );
+            // Configure face culling.
+            try_gl! { gl::Enable(gl::CULL_FACE); }
+            try_gl! { gl::CullFace(gl::BACK); }
+            try_gl! { gl::FrontFace(gl::CCW); }
         }
 
         Ok(())
     }
 
+    /// Implementation detail of `render` and `render_instanced`.
+    fn pre_render_model(&self, model: &Model) -> Result<()>
+    {
+        self.vertex_array.vertex_buffer(0, &model.vertices)?;
+        self.vertex_array.element_buffer(&model.indices)?;
+        Ok(())
+    }
+
     /// Implementation detail of `render`.
-    unsafe fn render_instance(
+    fn render_instance(
         &self,
         vp_matrix: &Mat4,
         model: &Model,
@@ -208,19 +500,105 @@ impl Pipeline
         let mvp_matrix = *vp_matrix * instance.m_matrix;
 
         // Set uniforms specific to this instance.
-        mvp_matrix.gl_uniform(0)?;
-        instance.bone_matrices.gl_uniform(1)?;
+        use vertex_interface::uniforms::*;
+        MVP_MATRIX.set(self.context, &mvp_matrix)?;
+        M_MATRIX.set(self.context, &instance.m_matrix)?;
+        let bone_matrices = &instance.bone_matrices[.. model.bones];
+        BONE_MATRICES.set(self.context, bone_matrices)?;
 
         // Draw model for this instance.
-        try_gl! {
-            gl::DrawElements(
-                /* mode    */ gl::TRIANGLES,
-                /* count   */ model.indices.len() as _,
-                /* type    */ gl::UNSIGNED_INT,
-                /* indices */ null(),
-            );
+        // SAFETY: The context is current,
+        //         the count matches the bound index buffer,
+        //         the indices refer to vertices in the vertex buffer,
+        //         and the bones lie within the bone matrices,
+        //         as the model has no more bones than the pipeline.
+        unsafe {
+            try_gl! {
+                gl::DrawElements(
+                    /* mode    */ gl::TRIANGLES,
+                    /* count   */ model.indices.len() as _,
+                    /* type    */ gl::UNSIGNED_INT,
+                    /* indices */ null(),
+                );
+            }
         }
 
         Ok(())
     }
 }
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests
+{
+    use super::*;
+    use crate::client::graphics::{
+        GlErrors,
+        GlLinkError,
+        POLL_GL_ERRORS_PER_CALL,
+        offscreen::OffscreenContext,
+    };
+
+    /// Create the instanced vertex shader, specialized with constants.
+    fn instanced_vertex_shader<'c>(
+        context: &'c GlContext,
+        constant_indices: &[u32],
+    ) -> Result<GlShader<'c>>
+    {
+        GlShader::new(
+            /* context          */ context,
+            /* shader_type      */ gl::VERTEX_SHADER,
+            /* source_path      */ INSTANCED_VERTEX_SHADER_PATH,
+            /* shader_binary    */ INSTANCED_VERTEX_SHADER_BINARY,
+            /* constant_indices */ constant_indices,
+            /* constant_values  */ &vec![0; constant_indices.len()],
+        )
+    }
+
+    #[test]
+    fn models_stay_within_their_buffers()
+    {
+        let context = OffscreenContext::new().unwrap();
+        let context = context.gl();
+
+        let vertex = |bone| Vertex{
+            position: Vec3::ZERO,
+            normal: Vec3::Z,
+            texcoord: Vec2::ZERO,
+            bones: [0, bone, 0, 0],
+            weights: Vec4::X,
+        };
+        let vertices = [vertex(0), vertex(1), vertex(0)];
+        assert!(Model::new(context, &vertices, &[0, 1, 2], 2).is_ok());
+
+        // Indices must refer to vertices, even unused bones must exist,
+        // and the number of bones must be supported.
+        assert!(Model::new(context, &vertices, &[0, 1, 3], 2).is_err());
+        assert!(Model::new(context, &vertices, &[0, 1, 2], 1).is_err());
+        assert!(Model::new(context, &[], &[], 0).is_err());
+        assert!(Model::new(context, &[], &[], BONES + 1).is_err());
+    }
+
+    #[test]
+    fn shader_errors_name_their_sources()
+    {
+        let context = OffscreenContext::new().unwrap();
+        let context = context.gl();
+
+        // Mesa rejects unknown specialization constants
+        // with an OpenGL error rather than a failed compile status.
+        if POLL_GL_ERRORS_PER_CALL {
+            let err = instanced_vertex_shader(context, &[999]).err().unwrap();
+            assert!(err.downcast_ref::<GlErrors>().is_some());
+            let message = format!("{:#}", err);
+            assert!(message.contains(INSTANCED_VERTEX_SHADER_PATH));
+        }
+
+        // A program cannot have two vertex shaders.
+        let a = instanced_vertex_shader(context, &[]).unwrap();
+        let b = instanced_vertex_shader(context, &[]).unwrap();
+        let err = GlProgram::new(context, &[&a, &b]).err().unwrap();
+        let link_error = err.downcast_ref::<GlLinkError>().unwrap();
+        assert!(!link_error.info_log().is_empty());
+        assert!(format!("{:#}", err).contains(INSTANCED_VERTEX_SHADER_PATH));
+    }
+}
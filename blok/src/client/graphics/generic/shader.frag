@@ -1,10 +1,85 @@
 #version 450 core
 
 layout(location = 0) in vec2 uv;
+layout(location = 1) in float ao;
+layout(location = 2) in vec2 light;
+layout(location = 3) in vec3 normal;
+layout(location = 4) in vec3 position;
+
+/// Must agree with [`shadow::CASCADES`].
+const int CASCADES = 4;
+
+/// See [`Environment`].
+layout(binding = 0, std140) uniform Environment
+{
+    vec3 sun_direction;
+    vec3 sun_color;
+    vec3 ambient_color;
+    mat4 v_matrix;
+    mat4 cascade_vp_matrices[CASCADES];
+    vec4 cascade_far;
+};
+
+/// One layer per cascade, see [`ShadowMap`].
+layout(binding = 0) uniform sampler2DArrayShadow shadow_map;
 
 layout(location = 0) out vec4 color;
 
+/// Brightness for a normalized light level.
+///
+/// Each level is a constant factor brighter than the level below it,
+/// which looks more natural than a linear progression.
+float light_brightness(float level)
+{
+    return pow(0.8, 15.0 * (1.0 - level));
+}
+
+/// Fraction of sun light that reaches the fragment.
+float sun_visibility(vec3 n)
+{
+    // Select the first cascade that extends beyond the fragment.
+    float depth = -(v_matrix * vec4(position, 1.0)).z;
+    int cascade = 0;
+    while (cascade < CASCADES && depth > cascade_far[cascade])
+        ++cascade;
+    if (cascade == CASCADES)
+        return 1.0;
+
+    // Find the fragment in the shadow map.
+    // Moving the position along the normal avoids self-shadowing
+    // on surfaces that are nearly parallel to the sun light.
+    vec3 offset_position = position + 0.05 * (cascade + 1) * n;
+    vec4 clip = cascade_vp_matrices[cascade] * vec4(offset_position, 1.0);
+    vec3 shadow_position = clip.xyz / clip.w * 0.5 + 0.5;
+
+    // Percentage-closer filtering over a 3×3 texel neighbourhood.
+    // Each sample already averages four comparisons due to linear filtering.
+    // The shadow map has no mipmaps, so we pass zero gradients;
+    // implicit gradients are undefined in non-uniform control flow.
+    vec2 texel = 1.0 / vec2(textureSize(shadow_map, 0).xy);
+    float visibility = 0.0;
+    for (int x = -1; x <= 1; ++x)
+    for (int y = -1; y <= 1; ++y) {
+        vec2 uv = shadow_position.xy + vec2(x, y) * texel;
+        vec4 coordinate = vec4(uv, cascade, shadow_position.z);
+        visibility += textureGrad(shadow_map, coordinate, vec2(0.0), vec2(0.0));
+    }
+    return visibility / 9.0;
+}
+
 void main()
 {
-    color = vec4(uv, 1.0, 1.0);
+    vec3 n = normalize(normal);
+
+    // Light from the sun and sky reaches only as far as sky light does.
+    float diffuse = max(dot(n, -sun_direction), 0.0);
+    if (diffuse > 0.0)
+        diffuse *= sun_visibility(n);
+    vec3 sky = ambient_color + sun_color * diffuse;
+    sky *= light_brightness(light.x);
+
+    // Block light is not affected by the time of day.
+    vec3 block = vec3(light_brightness(light.y));
+
+    color = vec4(vec3(uv, 1.0) * ao * max(sky, block), 1.0);
 }
@@ -5,21 +5,50 @@
 layout(constant_id = 0) const uint BONES = 1;
 
 layout(location = 0) uniform mat4 mvp_matrix;
-layout(location = 1) uniform mat4 bone_matrices[BONES];
+layout(location = 1) uniform mat4 m_matrix;
+layout(location = 2) uniform mat4 bone_matrices[BONES];
 
 layout(location = 0) in vec3 vertex_position;
 layout(location = 1) in vec2 vertex_texcoord;
-layout(location = 2) in uint vertex_bone;
+layout(location = 2) in uvec4 vertex_bones;
+layout(location = 3) in vec3 vertex_normal;
+layout(location = 4) in vec4 vertex_weights;
 
 layout(location = 0) out vec2 fragment_uv;
+layout(location = 1) out float fragment_ao;
+layout(location = 2) out vec2 fragment_light;
+layout(location = 3) out vec3 fragment_normal;
+layout(location = 4) out vec3 fragment_position;
 
 void main()
 {
+    // Blend the bone matrices by the weights of the bones.
+    // Unused bones have zero weight, so they do not contribute.
+    mat4 bone_matrix =
+        vertex_weights.x * bone_matrices[vertex_bones.x] +
+        vertex_weights.y * bone_matrices[vertex_bones.y] +
+        vertex_weights.z * bone_matrices[vertex_bones.z] +
+        vertex_weights.w * bone_matrices[vertex_bones.w];
+
     gl_Position =
         mvp_matrix *
-        bone_matrices[vertex_bone] *
+        bone_matrix *
         vec4(vertex_position, 1.0);
 
+    // The fragment shader needs the world position for shadow mapping.
+    vec4 world_position = m_matrix * bone_matrix * vec4(vertex_position, 1.0);
+    fragment_position = world_position.xyz;
+
+    // Transform the normal into world space.
+    // This assumes the matrices do not scale non-uniformly,
+    // as otherwise we would need the inverse transpose.
+    fragment_normal = mat3(m_matrix * bone_matrix) * vertex_normal;
+
     // Pass through texture coordinate.
     fragment_uv = vertex_texcoord;
+
+    // Models are not subject to ambient occlusion,
+    // and they are lit as if they were under the open sky.
+    fragment_ao = 1.0;
+    fragment_light = vec2(1.0, 0.0);
 }
@@ -1,54 +1,76 @@
-use crate::try_gl;
+use crate::{client::graphics::{GlContext, object_label}, try_gl};
 use anyhow::Result;
 use opengl::gl::{self, types::*};
 use std::{marker::PhantomData, mem::size_of_val};
 
 /// Owned handle to an OpenGL buffer.
-pub struct GlBuffer<T>
+pub struct GlBuffer<'c, T>
     where T: Copy
 {
     _phantom: PhantomData<*mut [T]>,
+    context: &'c GlContext,
     raw: GLuint,
     len: usize,
 }
 
-impl<T> GlBuffer<T>
+impl<'c, T> GlBuffer<'c, T>
     where T: Copy
 {
     /// Create an empty buffer.
-    #[doc = crate::doc_safety_opengl!()]
-    pub unsafe fn new() -> Result<Self>
+    pub fn new(context: &'c GlContext) -> Result<Self>
     {
-        let mut this = Self{_phantom: PhantomData, raw: 0, len: 0};
-        try_gl! { gl::CreateBuffers(1, &mut this.raw); }
+        let mut this = Self{_phantom: PhantomData, context, raw: 0, len: 0};
+        // SAFETY: The context is current.
+        unsafe {
+            try_gl! { gl::CreateBuffers(1, &mut this.raw); }
+        }
         Ok(this)
     }
 
     /// Create a buffer and upload data for it.
-    #[doc = crate::doc_safety_opengl!()]
-    pub unsafe fn new_upload(data: &[T], usage: GLenum) -> Result<Self>
+    pub fn new_upload(context: &'c GlContext, data: &[T], usage: GLenum)
+        -> Result<Self>
     {
-        let mut this = Self::new()?;
+        let mut this = Self::new(context)?;
         this.upload(data, usage)?;
         Ok(this)
     }
 
     /// Upload data to the buffer.
-    #[doc = crate::doc_safety_opengl!()]
-    pub unsafe fn upload(&mut self, data: &[T], usage: GLenum) -> Result<()>
+    pub fn upload(&mut self, data: &[T], usage: GLenum) -> Result<()>
     {
-        try_gl! {
-            gl::NamedBufferData(
-                /* buffer */ self.raw,
-                /* size   */ size_of_val(data) as _,
-                /* data   */ data.as_ptr() as _,
-                /* usage  */ usage,
-            );
+        // SAFETY: The context is current,
+        //         and the size matches the data.
+        unsafe {
+            try_gl! {
+                gl::NamedBufferData(
+                    /* buffer */ self.raw,
+                    /* size   */ size_of_val(data) as _,
+                    /* data   */ data.as_ptr() as _,
+                    /* usage  */ usage,
+                );
+            }
         }
         self.len = data.len();
         Ok(())
     }
 
+    /// Bind the buffer to an indexed binding point, such as a uniform block.
+    pub fn bind_base(&self, target: GLenum, index: GLuint) -> Result<()>
+    {
+        // SAFETY: The context is current.
+        unsafe {
+            try_gl! { gl::BindBufferBase(target, index, self.raw); }
+        }
+        Ok(())
+    }
+
+    /// Give the buffer a name that appears in debug messages.
+    pub fn label(&self, label: &str) -> Result<()>
+    {
+        object_label(self.context, gl::BUFFER, self.raw, label)
+    }
+
     /// The OpenGL name of the buffer.
     pub fn as_raw(&self) -> GLuint
     {
@@ -62,12 +84,12 @@ impl<T> GlBuffer<T>
     }
 }
 
-impl<T> Drop for GlBuffer<T>
+impl<'c, T> Drop for GlBuffer<'c, T>
     where T: Copy
 {
     fn drop(&mut self)
     {
-        // SAFETY: Provided by caller of `new`.
+        // SAFETY: The context is current.
         unsafe {
             gl::DeleteBuffers(1, &self.raw);
         }
@@ -0,0 +1,32 @@
+use std::marker::PhantomData;
+
+/// Proof that an OpenGL context is current on this thread.
+///
+/// OpenGL functions operate on the context that is current on the calling
+/// thread, so they can only be called safely while there is one.
+/// Functions that call OpenGL take a reference to this token,
+/// and objects that own OpenGL resources borrow it for their lifetime,
+/// so that they cannot be used or dropped after the context is gone.
+///
+/// A context is current on a single thread,
+/// so the token is neither [`Send`] nor [`Sync`].
+pub struct GlContext
+{
+    _not_send: PhantomData<*const ()>,
+}
+
+impl GlContext
+{
+    /// Create a token for the context that is current on this thread.
+    ///
+    /// # Safety
+    ///
+    /// There must be a current OpenGL context on the calling thread,
+    /// and its procedures must have been loaded with `gl::load_with`.
+    /// The context must remain current and must not be destroyed
+    /// until the token is dropped.
+    pub unsafe fn new_unchecked() -> Self
+    {
+        Self{_not_send: PhantomData}
+    }
+}
@@ -0,0 +1,113 @@
+use crate::{client::graphics::GlContext, try_gl};
+use anyhow::Result;
+use opengl::gl::{self, types::*};
+use std::{ffi::c_void, ptr::null, slice};
+
+/// Route OpenGL debug messages to the [`log`] crate.
+///
+/// Messages are logged with target `opengl`,
+/// at a level that corresponds to their severity.
+/// Debug output is synchronous, so that messages are logged
+/// from within the OpenGL call that caused them,
+/// which makes backtraces taken in the logger useful.
+///
+/// Drivers are only required to produce messages in debug contexts,
+/// so the context should be created with the debug flag.
+pub fn install_debug_callback(_context: &GlContext) -> Result<()>
+{
+    // SAFETY: The context is current,
+    //         and the callback does not use the user parameter.
+    unsafe {
+        try_gl! { gl::Enable(gl::DEBUG_OUTPUT); }
+        try_gl! { gl::Enable(gl::DEBUG_OUTPUT_SYNCHRONOUS); }
+        try_gl! { gl::DebugMessageCallback(Some(debug_callback), null()); }
+    }
+    Ok(())
+}
+
+/// Give an OpenGL object a name that appears in debug messages.
+///
+/// Object wrappers have `label` methods that call this,
+/// which should be preferred over calling this directly.
+pub fn object_label(
+    _context: &GlContext,
+    identifier: GLenum,
+    name: GLuint,
+    label: &str,
+) -> Result<()>
+{
+    // SAFETY: The context is current,
+    //         and the length matches the label.
+    unsafe {
+        try_gl! {
+            gl::ObjectLabel(
+                /* identifier */ identifier,
+                /* name       */ name,
+                /* length     */ label.len() as _,
+                /* label      */ label.as_ptr() as _,
+            );
+        }
+    }
+    Ok(())
+}
+
+extern "system" fn debug_callback(
+    source: GLenum,
+    type_: GLenum,
+    id: GLuint,
+    severity: GLenum,
+    length: GLsizei,
+    message: *const GLchar,
+    _user_param: *mut c_void,
+)
+{
+    let level = match severity {
+        gl::DEBUG_SEVERITY_HIGH   => log::Level::Error,
+        gl::DEBUG_SEVERITY_MEDIUM => log::Level::Warn,
+        gl::DEBUG_SEVERITY_LOW    => log::Level::Info,
+        _                         => log::Level::Debug,
+    };
+
+    // SAFETY: OpenGL passes the message with its length.
+    let message = unsafe {
+        slice::from_raw_parts(message as *const u8, length as usize)
+    };
+    let message = String::from_utf8_lossy(message);
+
+    log::log!(
+        target: "opengl",
+        level,
+        "[{} {} {}] {}",
+        source_name(source),
+        type_name(type_),
+        id,
+        message.trim_end(),
+    );
+}
+
+fn source_name(source: GLenum) -> &'static str
+{
+    match source {
+        gl::DEBUG_SOURCE_API             => "API",
+        gl::DEBUG_SOURCE_WINDOW_SYSTEM   => "window system",
+        gl::DEBUG_SOURCE_SHADER_COMPILER => "shader compiler",
+        gl::DEBUG_SOURCE_THIRD_PARTY     => "third party",
+        gl::DEBUG_SOURCE_APPLICATION     => "application",
+        _                                => "other",
+    }
+}
+
+fn type_name(type_: GLenum) -> &'static str
+{
+    match type_ {
+        gl::DEBUG_TYPE_ERROR               => "error",
+        gl::DEBUG_TYPE_DEPRECATED_BEHAVIOR => "deprecated behavior",
+        gl::DEBUG_TYPE_UNDEFINED_BEHAVIOR  => "undefined behavior",
+        gl::DEBUG_TYPE_PORTABILITY         => "portability",
+        gl::DEBUG_TYPE_PERFORMANCE         => "performance",
+        gl::DEBUG_TYPE_MARKER              => "marker",
+        gl::DEBUG_TYPE_PUSH_GROUP          => "push group",
+        gl::DEBUG_TYPE_POP_GROUP           => "pop group",
+        _                                  => "other",
+    }
+}
@@ -1,10 +1,61 @@
+use crate::client::graphics::GlContext;
 use anyhow::{Context, Result};
 use opengl::gl::{self, types::*};
-use std::{error::Error, fmt};
+use std::{error::Error, fmt, sync::atomic::{AtomicBool, Ordering}};
 
 ////////////////////////////////////////////////////////////////////////////////
 // Error propagation macro
 
+/// Whether [`try_gl`] checks for errors after each call.
+static POLL_GL_ERRORS: AtomicBool = AtomicBool::new(true);
+
+/// Whether [`try_gl`] contains code for checking errors at all.
+///
+/// With the `elide-gl-error-polling` feature, [`try_gl`] compiles
+/// down to the bare OpenGL call. Use [`check_frame_gl_errors`]
+/// to still detect errors, albeit without knowing their origin.
+pub const POLL_GL_ERRORS_PER_CALL: bool =
+    cfg!(not(feature = "elide-gl-error-polling"));
+
+/// Enable or disable checking for errors after each call in [`try_gl`].
+///
+/// Checking for errors after each call is slow on some drivers.
+/// Errors are also reported to the debug callback
+/// (see [`install_debug_callback`][`super::install_debug_callback`]),
+/// so when that is installed, the checks can be turned off.
+/// Functions still return [`Result`], but only report errors
+/// that are detected in other ways, such as link errors.
+pub fn set_gl_error_polling(enabled: bool)
+{
+    POLL_GL_ERRORS.store(enabled, Ordering::Relaxed);
+}
+
+/// Check for errors that were not detected by [`try_gl`].
+///
+/// Call this once per frame, so that errors are still detected
+/// when checking after each call is turned off,
+/// either with [`set_gl_error_polling`] or at compile time.
+/// Polling once per frame costs only a single synchronization point.
+pub fn check_frame_gl_errors(_context: &GlContext) -> Result<()>
+{
+    // SAFETY: The context is current.
+    unsafe {
+        GlErrors::get_gl_errors().context("OpenGL errors during frame")
+    }
+}
+
+/// For use by [`try_gl`].
+#[doc(hidden)]
+#[inline]
+pub unsafe fn poll_gl_errors() -> Result<(), GlErrors>
+{
+    if POLL_GL_ERRORS.load(Ordering::Relaxed) {
+        GlErrors::get_gl_errors()
+    } else {
+        Ok(())
+    }
+}
+
 /// For use by [`try_gl`].
 #[doc(hidden)]
 pub fn context<C>(result: Result<(), GlErrors>, context: C) -> Result<()>
@@ -17,6 +68,8 @@ pub fn context<C>(result: Result<(), GlErrors>, context: C) -> Result<()>
 ///
 /// If there are any errors, they are returned from the enclosing function.
 /// The errors are annotated with the name of the OpenGL function that failed.
+/// Checking can be turned off at run time with [`set_gl_error_polling`],
+/// or at compile time with the `elide-gl-error-polling` feature.
 #[doc = crate::doc_safety_opengl!()]
 #[macro_export]
 macro_rules! try_gl
@@ -28,10 +81,12 @@ macro_rules! try_gl
     { $gl:ident :: $proc:ident ( $($argument:expr),* $(,)? ) } => {
         {
             let result = $gl::$proc($($argument),*);
-            $crate::client::graphics::context(
-                $crate::client::graphics::GlErrors::get_gl_errors(),
-                concat!("gl", stringify!($proc)),
-            )?;
+            if $crate::client::graphics::POLL_GL_ERRORS_PER_CALL {
+                $crate::client::graphics::context(
+                    $crate::client::graphics::poll_gl_errors(),
+                    concat!("gl", stringify!($proc)),
+                )?;
+            }
             result
         }
     };
@@ -89,6 +144,78 @@ impl GlErrors
     }
 }
 
+////////////////////////////////////////////////////////////////////////////////
+// Framebuffer completeness
+
+/// Status of a framebuffer that is not complete.
+#[derive(Clone, Copy)]
+pub struct GlFramebufferIncomplete
+{
+    status: GLenum,
+}
+
+impl GlFramebufferIncomplete
+{
+    /// Interpret the result of `glCheckNamedFramebufferStatus`.
+    ///
+    /// If the framebuffer is complete, this method returns [`Ok`].
+    pub fn from_status(status: GLenum) -> Result<(), Self>
+    {
+        if status == gl::FRAMEBUFFER_COMPLETE {
+            Ok(())
+        } else {
+            Err(Self{status})
+        }
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// Compilation and linking
+
+/// Shader that failed to compile.
+#[derive(Clone)]
+pub struct GlCompileError
+{
+    info_log: String,
+}
+
+impl GlCompileError
+{
+    /// Create an error from the info log of the shader.
+    pub fn new(info_log: String) -> Self
+    {
+        Self{info_log}
+    }
+
+    /// The info log of the shader, which describes the failure.
+    pub fn info_log(&self) -> &str
+    {
+        &self.info_log
+    }
+}
+
+/// Program that failed to link.
+#[derive(Clone)]
+pub struct GlLinkError
+{
+    info_log: String,
+}
+
+impl GlLinkError
+{
+    /// Create an error from the info log of the program.
+    pub fn new(info_log: String) -> Self
+    {
+        Self{info_log}
+    }
+
+    /// The info log of the program, which describes the failure.
+    pub fn info_log(&self) -> &str
+    {
+        &self.info_log
+    }
+}
+
 ////////////////////////////////////////////////////////////////////////////////
 // Formatting and error trait impls
 
@@ -141,3 +268,81 @@ impl fmt::Debug for GlErrors
 impl Error for GlErrors
 {
 }
+
+impl fmt::Display for GlFramebufferIncomplete
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+    {
+        fmt::Debug::fmt(self, f)
+    }
+}
+
+impl fmt::Debug for GlFramebufferIncomplete
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+    {
+        match self.status {
+            gl::FRAMEBUFFER_UNDEFINED
+                => write!(f, "GL_FRAMEBUFFER_UNDEFINED"),
+            gl::FRAMEBUFFER_INCOMPLETE_ATTACHMENT
+                => write!(f, "GL_FRAMEBUFFER_INCOMPLETE_ATTACHMENT"),
+            gl::FRAMEBUFFER_INCOMPLETE_MISSING_ATTACHMENT
+                => write!(f, "GL_FRAMEBUFFER_INCOMPLETE_MISSING_ATTACHMENT"),
+            gl::FRAMEBUFFER_INCOMPLETE_DRAW_BUFFER
+                => write!(f, "GL_FRAMEBUFFER_INCOMPLETE_DRAW_BUFFER"),
+            gl::FRAMEBUFFER_INCOMPLETE_READ_BUFFER
+                => write!(f, "GL_FRAMEBUFFER_INCOMPLETE_READ_BUFFER"),
+            gl::FRAMEBUFFER_UNSUPPORTED
+                => write!(f, "GL_FRAMEBUFFER_UNSUPPORTED"),
+            gl::FRAMEBUFFER_INCOMPLETE_MULTISAMPLE
+                => write!(f, "GL_FRAMEBUFFER_INCOMPLETE_MULTISAMPLE"),
+            gl::FRAMEBUFFER_INCOMPLETE_LAYER_TARGETS
+                => write!(f, "GL_FRAMEBUFFER_INCOMPLETE_LAYER_TARGETS"),
+            other => write!(f, "{}", other),
+        }
+    }
+}
+
+impl Error for GlFramebufferIncomplete
+{
+}
+
+impl fmt::Display for GlCompileError
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+    {
+        write!(f, "Shader failed to compile:\n{}", self.info_log.trim_end())
+    }
+}
+
+impl fmt::Debug for GlCompileError
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+    {
+        fmt::Display::fmt(self, f)
+    }
+}
+
+impl Error for GlCompileError
+{
+}
+
+impl fmt::Display for GlLinkError
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+    {
+        write!(f, "Program failed to link:\n{}", self.info_log.trim_end())
+    }
+}
+
+impl fmt::Debug for GlLinkError
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+    {
+        fmt::Display::fmt(self, f)
+    }
+}
+
+impl Error for GlLinkError
+{
+}
@@ -0,0 +1,245 @@
+use crate::{
+    client::graphics::{
+        GlContext,
+        GlFramebufferIncomplete,
+        GlRenderbuffer,
+        GlTexture,
+    },
+    try_gl,
+};
+use anyhow::{Context, Result};
+use opengl::gl::{self, types::*};
+
+/// Owned handle to an OpenGL framebuffer.
+///
+/// Functions that can also operate on the default framebuffer
+/// take an `Option<&GlFramebuffer>`, where [`None`] denotes
+/// the default framebuffer.
+pub struct GlFramebuffer<'c>
+{
+    context: &'c GlContext,
+    raw: GLuint,
+}
+
+impl<'c> GlFramebuffer<'c>
+{
+    /// Create a framebuffer with no attachments.
+    pub fn new(context: &'c GlContext) -> Result<Self>
+    {
+        let mut this = Self{context, raw: 0};
+        // SAFETY: The context is current.
+        unsafe {
+            try_gl! { gl::CreateFramebuffers(1, &mut this.raw); }
+        }
+        Ok(this)
+    }
+
+    /// Attach a level of a texture to the framebuffer.
+    pub fn texture(
+        &mut self,
+        attachment: GLenum,
+        texture: &GlTexture,
+        level: GLint,
+    ) -> Result<()>
+    {
+        // SAFETY: The context is current.
+        unsafe {
+            try_gl! {
+                gl::NamedFramebufferTexture(
+                    /* framebuffer */ self.raw,
+                    /* attachment  */ attachment,
+                    /* texture     */ texture.as_raw(),
+                    /* level       */ level,
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// Attach a single layer of an array texture to the framebuffer.
+    pub fn texture_layer(
+        &mut self,
+        attachment: GLenum,
+        texture: &GlTexture,
+        level: GLint,
+        layer: GLint,
+    ) -> Result<()>
+    {
+        // SAFETY: The context is current.
+        unsafe {
+            try_gl! {
+                gl::NamedFramebufferTextureLayer(
+                    /* framebuffer */ self.raw,
+                    /* attachment  */ attachment,
+                    /* texture     */ texture.as_raw(),
+                    /* level       */ level,
+                    /* layer       */ layer,
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// Attach a renderbuffer to the framebuffer.
+    pub fn renderbuffer(
+        &mut self,
+        attachment: GLenum,
+        renderbuffer: &GlRenderbuffer,
+    ) -> Result<()>
+    {
+        // SAFETY: The context is current.
+        unsafe {
+            try_gl! {
+                gl::NamedFramebufferRenderbuffer(
+                    /* framebuffer        */ self.raw,
+                    /* attachment         */ attachment,
+                    /* renderbuffertarget */ gl::RENDERBUFFER,
+                    /* renderbuffer       */ renderbuffer.as_raw(),
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// Select the color buffer to draw into.
+    ///
+    /// Pass `GL_NONE` for framebuffers with only a depth attachment.
+    pub fn draw_buffer(&mut self, buffer: GLenum) -> Result<()>
+    {
+        // SAFETY: The context is current.
+        unsafe {
+            try_gl! { gl::NamedFramebufferDrawBuffer(self.raw, buffer); }
+        }
+        Ok(())
+    }
+
+    /// Select the color buffers to draw into.
+    pub fn draw_buffers(&mut self, buffers: &[GLenum]) -> Result<()>
+    {
+        // SAFETY: The context is current,
+        //         and the count matches the buffers.
+        unsafe {
+            try_gl! {
+                gl::NamedFramebufferDrawBuffers(
+                    /* framebuffer */ self.raw,
+                    /* n           */ buffers.len() as _,
+                    /* bufs        */ buffers.as_ptr(),
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// Select the color buffer to read from.
+    pub fn read_buffer(&mut self, buffer: GLenum) -> Result<()>
+    {
+        // SAFETY: The context is current.
+        unsafe {
+            try_gl! { gl::NamedFramebufferReadBuffer(self.raw, buffer); }
+        }
+        Ok(())
+    }
+
+    /// Check that the framebuffer is complete.
+    ///
+    /// If it is not, the returned error contains
+    /// a [`GlFramebufferIncomplete`] describing the reason.
+    pub fn check_complete(&self, target: GLenum) -> Result<()>
+    {
+        // SAFETY: The context is current.
+        let status = unsafe {
+            try_gl! { gl::CheckNamedFramebufferStatus(self.raw, target) }
+        };
+        GlFramebufferIncomplete::from_status(status)
+            .context("Framebuffer is incomplete")
+    }
+
+    /// Bind the framebuffer for drawing.
+    pub fn bind(&self) -> Result<()>
+    {
+        Self::bind_draw(self.context, Some(self))
+    }
+
+    /// Bind a framebuffer for drawing.
+    pub fn bind_draw(_context: &GlContext, framebuffer: Option<&Self>)
+        -> Result<()>
+    {
+        let raw = Self::raw_or_default(framebuffer);
+        // SAFETY: The context is current.
+        unsafe {
+            try_gl! { gl::BindFramebuffer(gl::DRAW_FRAMEBUFFER, raw); }
+        }
+        Ok(())
+    }
+
+    /// Bind a framebuffer for reading.
+    pub fn bind_read(_context: &GlContext, framebuffer: Option<&Self>)
+        -> Result<()>
+    {
+        let raw = Self::raw_or_default(framebuffer);
+        // SAFETY: The context is current.
+        unsafe {
+            try_gl! { gl::BindFramebuffer(gl::READ_FRAMEBUFFER, raw); }
+        }
+        Ok(())
+    }
+
+    /// Copy a rectangle of pixels from one framebuffer to another.
+    ///
+    /// Rectangles are given as `[x0, y0, x1, y1]`,
+    /// and the rectangles may differ in size to scale the pixels.
+    /// `mask` selects which of the color, depth, and stencil buffers to copy.
+    pub fn blit(
+        _context: &GlContext,
+        read: Option<&Self>,
+        draw: Option<&Self>,
+        src_rect: [GLint; 4],
+        dst_rect: [GLint; 4],
+        mask: GLbitfield,
+        filter: GLenum,
+    ) -> Result<()>
+    {
+        // SAFETY: The context is current.
+        unsafe {
+            try_gl! {
+                gl::BlitNamedFramebuffer(
+                    /* readFramebuffer */ Self::raw_or_default(read),
+                    /* drawFramebuffer */ Self::raw_or_default(draw),
+                    /* srcX0           */ src_rect[0],
+                    /* srcY0           */ src_rect[1],
+                    /* srcX1           */ src_rect[2],
+                    /* srcY1           */ src_rect[3],
+                    /* dstX0           */ dst_rect[0],
+                    /* dstY0           */ dst_rect[1],
+                    /* dstX1           */ dst_rect[2],
+                    /* dstY1           */ dst_rect[3],
+                    /* mask            */ mask,
+                    /* filter          */ filter,
+                );
+            }
+        }
+        Ok(())
+    }
+
+    fn raw_or_default(framebuffer: Option<&Self>) -> GLuint
+    {
+        framebuffer.map(Self::as_raw).unwrap_or(0)
+    }
+
+    /// The OpenGL name of the framebuffer.
+    pub fn as_raw(&self) -> GLuint
+    {
+        self.raw
+    }
+}
+
+impl<'c> Drop for GlFramebuffer<'c>
+{
+    fn drop(&mut self)
+    {
+        // SAFETY: The context is current.
+        unsafe {
+            gl::DeleteFramebuffers(1, &self.raw);
+        }
+    }
+}
@@ -1,38 +1,98 @@
-use crate::{client::graphics::GlShader, try_gl};
-use anyhow::Result;
+use crate::{
+    client::graphics::{GlContext, GlLinkError, GlShader, object_label},
+    try_gl,
+};
+use anyhow::{Context, Result};
 use opengl::gl::{self, types::*};
 
 /// Owned handle to an OpenGL program.
-pub struct GlProgram
+pub struct GlProgram<'c>
 {
+    context: &'c GlContext,
     raw: GLuint,
 }
 
-impl GlProgram
+impl<'c> GlProgram<'c>
 {
     /// Create and link a program.
-    #[doc = crate::doc_safety_opengl!()]
-    pub unsafe fn new(shaders: &[&GlShader]) -> Result<Self>
+    ///
+    /// If linking fails, the returned error contains
+    /// a [`GlLinkError`] with the info log of the program.
+    pub fn new(context: &'c GlContext, shaders: &[&GlShader]) -> Result<Self>
     {
-        let mut this = Self{raw: 0};
+        let mut this = Self{context, raw: 0};
+        this.new_impl(shaders)
+            .with_context(|| {
+                let source_paths: Vec<_> =
+                    shaders.iter().map(|s| s.source_path()).collect();
+                format!("Link program from {}", source_paths.join(", "))
+            })?;
+        Ok(this)
+    }
+
+    fn new_impl(&mut self, shaders: &[&GlShader]) -> Result<()>
+    {
+        // SAFETY: The context is current.
+        unsafe {
+            // Create program object.
+            self.raw = try_gl! { gl::CreateProgram() };
+
+            // Attach shaders.
+            for shader in shaders {
+                try_gl! { gl::AttachShader(self.raw, shader.as_raw()); }
+            }
+
+            // Link program.
+            try_gl! { gl::LinkProgram(self.raw); }
 
-        // Create program object.
-        this.raw = try_gl! { gl::CreateProgram() };
+            // Detach shaders.
+            for shader in shaders {
+                try_gl! { gl::DetachShader(self.raw, shader.as_raw()); }
+            }
 
-        // Attach shaders.
-        for shader in shaders {
-            try_gl! { gl::AttachShader(this.raw, shader.as_raw()); }
+            // Check that linking succeeded.
+            let mut status = 0;
+            try_gl! {
+                gl::GetProgramiv(self.raw, gl::LINK_STATUS, &mut status);
+            }
+            if status == gl::FALSE as GLint {
+                let info_log = self.info_log()?;
+                return Err(GlLinkError::new(info_log).into());
+            }
         }
 
-        // Link program.
-        try_gl! { gl::LinkProgram(this.raw); }
+        Ok(())
+    }
 
-        // Detach shaders.
-        for shader in shaders {
-            try_gl! { gl::DetachShader(this.raw, shader.as_raw()); }
+    /// Retrieve the info log of the program.
+    pub fn info_log(&self) -> Result<String>
+    {
+        let mut length = 0;
+        let mut buffer;
+        // SAFETY: The context is current,
+        //         and the buffer size matches the buffer.
+        unsafe {
+            try_gl! {
+                gl::GetProgramiv(self.raw, gl::INFO_LOG_LENGTH, &mut length);
+            }
+            buffer = vec![0u8; length.max(1) as usize];
+            try_gl! {
+                gl::GetProgramInfoLog(
+                    /* program   */ self.raw,
+                    /* bufSize   */ buffer.len() as _,
+                    /* length    */ &mut length,
+                    /* infoLog   */ buffer.as_mut_ptr() as _,
+                );
+            }
         }
+        buffer.truncate(length as usize);
+        Ok(String::from_utf8_lossy(&buffer).into_owned())
+    }
 
-        Ok(this)
+    /// Give the program a name that appears in debug messages.
+    pub fn label(&self, label: &str) -> Result<()>
+    {
+        object_label(self.context, gl::PROGRAM, self.raw, label)
     }
 
     /// The OpenGL name of the program.
@@ -42,11 +102,11 @@ impl GlProgram
     }
 }
 
-impl Drop for GlProgram
+impl<'c> Drop for GlProgram<'c>
 {
     fn drop(&mut self)
     {
-        // SAFETY: Provided by caller of `new`.
+        // SAFETY: The context is current.
         unsafe {
             gl::DeleteProgram(self.raw);
         }
@@ -0,0 +1,100 @@
+use crate::{client::graphics::GlContext, try_gl};
+use anyhow::Result;
+use opengl::gl::{self, types::*};
+
+/// Owned handle to an OpenGL renderbuffer.
+pub struct GlRenderbuffer<'c>
+{
+    _context: &'c GlContext,
+    raw: GLuint,
+}
+
+impl<'c> GlRenderbuffer<'c>
+{
+    /// Create a renderbuffer with no storage.
+    pub fn new(context: &'c GlContext) -> Result<Self>
+    {
+        let mut this = Self{_context: context, raw: 0};
+        // SAFETY: The context is current.
+        unsafe {
+            try_gl! { gl::CreateRenderbuffers(1, &mut this.raw); }
+        }
+        Ok(this)
+    }
+
+    /// Create a renderbuffer and allocate storage for it.
+    pub fn new_storage(
+        context: &'c GlContext,
+        internal_format: GLenum,
+        width: GLsizei,
+        height: GLsizei,
+    ) -> Result<Self>
+    {
+        let mut this = Self::new(context)?;
+        this.storage(internal_format, width, height)?;
+        Ok(this)
+    }
+
+    /// Allocate storage for the renderbuffer.
+    pub fn storage(
+        &mut self,
+        internal_format: GLenum,
+        width: GLsizei,
+        height: GLsizei,
+    ) -> Result<()>
+    {
+        // SAFETY: The context is current.
+        unsafe {
+            try_gl! {
+                gl::NamedRenderbufferStorage(
+                    /* renderbuffer   */ self.raw,
+                    /* internalformat */ internal_format,
+                    /* width          */ width,
+                    /* height         */ height,
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// Allocate multisample storage for the renderbuffer.
+    pub fn storage_multisample(
+        &mut self,
+        samples: GLsizei,
+        internal_format: GLenum,
+        width: GLsizei,
+        height: GLsizei,
+    ) -> Result<()>
+    {
+        // SAFETY: The context is current.
+        unsafe {
+            try_gl! {
+                gl::NamedRenderbufferStorageMultisample(
+                    /* renderbuffer   */ self.raw,
+                    /* samples        */ samples,
+                    /* internalformat */ internal_format,
+                    /* width          */ width,
+                    /* height         */ height,
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// The OpenGL name of the renderbuffer.
+    pub fn as_raw(&self) -> GLuint
+    {
+        self.raw
+    }
+}
+
+impl<'c> Drop for GlRenderbuffer<'c>
+{
+    fn drop(&mut self)
+    {
+        // SAFETY: The context is current.
+        unsafe {
+            gl::DeleteRenderbuffers(1, &self.raw);
+        }
+    }
+}
@@ -1,53 +1,136 @@
-use crate::try_gl;
-use anyhow::Result;
+use crate::{
+    client::graphics::{GlCompileError, GlContext, object_label},
+    try_gl,
+};
+use anyhow::{Context, Result};
 use opengl::gl::{self, types::*};
 
 /// Owned handle to an OpenGL shader.
-pub struct GlShader
+pub struct GlShader<'c>
 {
+    context: &'c GlContext,
     raw: GLuint,
+    source_path: &'static str,
 }
 
-impl GlShader
+impl<'c> GlShader<'c>
 {
     /// Create and specialize a shader.
-    #[doc = crate::doc_safety_opengl!()]
-    pub unsafe fn new(
+    ///
+    /// The source path is the path of the GLSL file that the binary
+    /// was compiled from; it is used in error and debug messages.
+    /// If specialization fails, the returned error contains
+    /// a [`GlCompileError`] with the info log of the shader.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the number of constant indices
+    /// differs from the number of constant values.
+    pub fn new(
+        context: &'c GlContext,
         shader_type: GLenum,
+        source_path: &'static str,
         shader_binary: &[u8],
         constant_indices: &[GLuint],
         constant_values: &[GLuint],
     ) -> Result<Self>
     {
-        let mut this = Self{raw: 0};
+        let mut this = Self{context, raw: 0, source_path};
+        this.new_impl(shader_type, shader_binary,
+                      constant_indices, constant_values)
+            .with_context(|| format!("Create shader from {}", source_path))?;
+        Ok(this)
+    }
+
+    fn new_impl(
+        &mut self,
+        shader_type: GLenum,
+        shader_binary: &[u8],
+        constant_indices: &[GLuint],
+        constant_values: &[GLuint],
+    ) -> Result<()>
+    {
+        assert_eq!(constant_indices.len(), constant_values.len());
+
+        // SAFETY: The context is current,
+        //         and the lengths match the slices.
+        unsafe {
+            // Create shader object.
+            self.raw = try_gl! { gl::CreateShader(shader_type) };
+            self.label(self.source_path)?;
+
+            // Supply shader SPIR-V code.
+            try_gl! {
+                gl::ShaderBinary(
+                    /* count        */ 1,
+                    /* shaders      */ &self.raw,
+                    /* binaryFormat */ gl::SHADER_BINARY_FORMAT_SPIR_V_ARB,
+                    /* binary       */ shader_binary.as_ptr() as _,
+                    /* length       */ shader_binary.len() as _,
+                );
+            }
 
-        // Create shader object.
-        this.raw = try_gl! { gl::CreateShader(shader_type) };
+            // Supply specialization constants.
+            try_gl! {
+                gl::SpecializeShaderARB(
+                    /* shader         */ self.raw,
+                    /* pEntryPoint    */ "main\0".as_ptr() as _,
+                    /* numSpecializationConstants */
+                        constant_indices.len() as _,
+                    /* pConstantIndex */ constant_indices.as_ptr(),
+                    /* pConstantValue */ constant_values.as_ptr(),
+                );
+            }
 
-        // Supply shader SPIR-V code.
-        try_gl! {
-            gl::ShaderBinary(
-                /* count        */ 1,
-                /* shaders      */ &this.raw,
-                /* binaryFormat */ gl::SHADER_BINARY_FORMAT_SPIR_V_ARB,
-                /* binary       */ shader_binary.as_ptr() as _,
-                /* length       */ shader_binary.len() as _,
-            );
+            // Specialization is when SPIR-V shaders are compiled.
+            let mut status = 0;
+            try_gl! {
+                gl::GetShaderiv(self.raw, gl::COMPILE_STATUS, &mut status);
+            }
+            if status == gl::FALSE as GLint {
+                let info_log = self.info_log()?;
+                return Err(GlCompileError::new(info_log).into());
+            }
         }
 
-        // Supply specialization constants.
-        assert_eq!(constant_indices.len(), constant_values.len());
-        try_gl! {
-            gl::SpecializeShaderARB(
-                /* shader         */ this.raw,
-                /* pEntryPoint    */ "main\0".as_ptr() as _,
-                /* numSpecializationConstants */ constant_indices.len() as _,
-                /* pConstantIndex */ constant_indices.as_ptr(),
-                /* pConstantValue */ constant_values.as_ptr(),
-            );
+        Ok(())
+    }
+
+    /// Retrieve the info log of the shader.
+    pub fn info_log(&self) -> Result<String>
+    {
+        let mut length = 0;
+        let mut buffer;
+        // SAFETY: The context is current,
+        //         and the buffer size matches the buffer.
+        unsafe {
+            try_gl! {
+                gl::GetShaderiv(self.raw, gl::INFO_LOG_LENGTH, &mut length);
+            }
+            buffer = vec![0u8; length.max(1) as usize];
+            try_gl! {
+                gl::GetShaderInfoLog(
+                    /* shader    */ self.raw,
+                    /* bufSize   */ buffer.len() as _,
+                    /* length    */ &mut length,
+                    /* infoLog   */ buffer.as_mut_ptr() as _,
+                );
+            }
         }
+        buffer.truncate(length as usize);
+        Ok(String::from_utf8_lossy(&buffer).into_owned())
+    }
 
-        Ok(this)
+    /// Give the shader a name that appears in debug messages.
+    pub fn label(&self, label: &str) -> Result<()>
+    {
+        object_label(self.context, gl::SHADER, self.raw, label)
+    }
+
+    /// The path of the GLSL file the shader was compiled from.
+    pub fn source_path(&self) -> &'static str
+    {
+        self.source_path
     }
 
     /// The OpenGL name of the shader.
@@ -57,11 +140,11 @@ impl GlShader
     }
 }
 
-impl Drop for GlShader
+impl<'c> Drop for GlShader<'c>
 {
     fn drop(&mut self)
     {
-        // SAFETY: Provided by caller of `new`.
+        // SAFETY: The context is current.
         unsafe {
             gl::DeleteShader(self.raw);
         }
@@ -0,0 +1,132 @@
+use crate::{client::graphics::GlContext, try_gl};
+use anyhow::Result;
+use opengl::gl::{self, types::*};
+
+/// Owned handle to an OpenGL texture.
+pub struct GlTexture<'c>
+{
+    _context: &'c GlContext,
+    raw: GLuint,
+}
+
+impl<'c> GlTexture<'c>
+{
+    /// Create a texture with no storage.
+    pub fn new(context: &'c GlContext, target: GLenum) -> Result<Self>
+    {
+        let mut this = Self{_context: context, raw: 0};
+        // SAFETY: The context is current.
+        unsafe {
+            try_gl! { gl::CreateTextures(target, 1, &mut this.raw); }
+        }
+        Ok(this)
+    }
+
+    /// Allocate immutable storage for a two-dimensional texture.
+    pub fn storage_2d(
+        &mut self,
+        levels: GLsizei,
+        internal_format: GLenum,
+        width: GLsizei,
+        height: GLsizei,
+    ) -> Result<()>
+    {
+        // SAFETY: The context is current.
+        unsafe {
+            try_gl! {
+                gl::TextureStorage2D(
+                    /* texture        */ self.raw,
+                    /* levels         */ levels,
+                    /* internalformat */ internal_format,
+                    /* width          */ width,
+                    /* height         */ height,
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// Allocate immutable storage for a three-dimensional texture
+    /// or for an array of two-dimensional textures.
+    pub fn storage_3d(
+        &mut self,
+        levels: GLsizei,
+        internal_format: GLenum,
+        width: GLsizei,
+        height: GLsizei,
+        depth: GLsizei,
+    ) -> Result<()>
+    {
+        // SAFETY: The context is current.
+        unsafe {
+            try_gl! {
+                gl::TextureStorage3D(
+                    /* texture        */ self.raw,
+                    /* levels         */ levels,
+                    /* internalformat */ internal_format,
+                    /* width          */ width,
+                    /* height         */ height,
+                    /* depth          */ depth,
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// Set an integer texture parameter.
+    pub fn parameter_i(&mut self, pname: GLenum, param: GLint) -> Result<()>
+    {
+        // SAFETY: The context is current.
+        unsafe {
+            try_gl! { gl::TextureParameteri(self.raw, pname, param); }
+        }
+        Ok(())
+    }
+
+    /// Set a floating-point vector texture parameter.
+    ///
+    /// # Panics
+    ///
+    /// Panics if there are fewer than four values,
+    /// which is the most any parameter takes.
+    pub fn parameter_fv(&mut self, pname: GLenum, params: &[GLfloat])
+        -> Result<()>
+    {
+        assert!(params.len() >= 4);
+        // SAFETY: The context is current,
+        //         and there are enough values for any parameter.
+        unsafe {
+            try_gl! {
+                gl::TextureParameterfv(self.raw, pname, params.as_ptr());
+            }
+        }
+        Ok(())
+    }
+
+    /// Bind the texture to a texture unit.
+    pub fn bind_unit(&self, unit: GLuint) -> Result<()>
+    {
+        // SAFETY: The context is current.
+        unsafe {
+            try_gl! { gl::BindTextureUnit(unit, self.raw); }
+        }
+        Ok(())
+    }
+
+    /// The OpenGL name of the texture.
+    pub fn as_raw(&self) -> GLuint
+    {
+        self.raw
+    }
+}
+
+impl<'c> Drop for GlTexture<'c>
+{
+    fn drop(&mut self)
+    {
+        // SAFETY: The context is current.
+        unsafe {
+            gl::DeleteTextures(1, &self.raw);
+        }
+    }
+}
@@ -1,36 +1,106 @@
-use crate::try_gl;
+use crate::{client::graphics::GlContext, try_gl};
 use anyhow::Result;
-use glam::{Mat4, Vec2};
+use glam::{Mat4, Vec2, Vec3};
 use opengl::gl::{self, types::*};
+use std::marker::PhantomData;
+
+/// Location of a uniform of a particular type.
+///
+/// The build script generates these for each shader,
+/// so that setting a uniform with the wrong type does not compile.
+pub struct GlUniformLocation<T>
+    where T: ?Sized
+{
+    _phantom: PhantomData<fn(&T)>,
+    location: GLint,
+}
+
+impl<T> GlUniformLocation<T>
+    where T: ?Sized
+{
+    /// Refer to the uniform at the given location.
+    pub const fn new(location: GLint) -> Self
+    {
+        Self{_phantom: PhantomData, location}
+    }
+
+    /// The location of the uniform.
+    pub fn location(&self) -> GLint
+    {
+        self.location
+    }
+}
+
+impl<T> GlUniformLocation<T>
+    where T: ?Sized + GlUniform
+{
+    /// Specify the value of the uniform
+    /// for the program that is currently in use.
+    pub fn set(&self, context: &GlContext, value: &T) -> Result<()>
+    {
+        value.gl_uniform(context, self.location)
+    }
+}
 
 /// Trait for objects that can be specified as uniforms.
 pub trait GlUniform
 {
-    /// Specify the uniform value for the given uniform.
-    #[doc = crate::doc_safety_opengl!()]
-    unsafe fn gl_uniform(&self, location: GLint) -> Result<()>;
+    /// Specify the uniform value for the given uniform
+    /// of the program that is currently in use.
+    fn gl_uniform(&self, context: &GlContext, location: GLint) -> Result<()>;
+}
+
+impl GlUniform for u32
+{
+    fn gl_uniform(&self, _context: &GlContext, location: GLint) -> Result<()>
+    {
+        // SAFETY: The context is current.
+        unsafe {
+            try_gl! { gl::Uniform1ui(location, *self); }
+        }
+        Ok(())
+    }
 }
 
 impl GlUniform for Vec2
 {
-    unsafe fn gl_uniform(&self, location: GLint) -> Result<()>
+    fn gl_uniform(&self, _context: &GlContext, location: GLint) -> Result<()>
     {
-        try_gl! { gl::Uniform2f(location, self.x, self.y); }
+        // SAFETY: The context is current.
+        unsafe {
+            try_gl! { gl::Uniform2f(location, self.x, self.y); }
+        }
+        Ok(())
+    }
+}
+
+impl GlUniform for Vec3
+{
+    fn gl_uniform(&self, _context: &GlContext, location: GLint) -> Result<()>
+    {
+        // SAFETY: The context is current.
+        unsafe {
+            try_gl! { gl::Uniform3f(location, self.x, self.y, self.z); }
+        }
         Ok(())
     }
 }
 
 impl GlUniform for Mat4
 {
-    unsafe fn gl_uniform(&self, location: GLint) -> Result<()>
+    fn gl_uniform(&self, _context: &GlContext, location: GLint) -> Result<()>
     {
-        try_gl! {
-            gl::UniformMatrix4fv(
-                /* location  */ location,
-                /* count     */ 1,
-                /* transpose */ gl::FALSE,
-                /* value     */ self.as_ref().as_ptr(),
-            );
+        // SAFETY: The context is current,
+        //         and the matrix consists of sixteen floats.
+        unsafe {
+            try_gl! {
+                gl::UniformMatrix4fv(
+                    /* location  */ location,
+                    /* count     */ 1,
+                    /* transpose */ gl::FALSE,
+                    /* value     */ self.as_ref().as_ptr(),
+                );
+            }
         }
         Ok(())
     }
@@ -38,15 +108,19 @@ impl GlUniform for Mat4
 
 impl GlUniform for [Mat4]
 {
-    unsafe fn gl_uniform(&self, location: GLint) -> Result<()>
+    fn gl_uniform(&self, _context: &GlContext, location: GLint) -> Result<()>
     {
-        try_gl! {
-            gl::UniformMatrix4fv(
-                /* location  */ location,
-                /* count     */ self.len() as _,
-                /* transpose */ gl::FALSE,
-                /* value     */ self.as_ptr() as _,
-            );
+        // SAFETY: The context is current,
+        //         and the count matches the matrices.
+        unsafe {
+            try_gl! {
+                gl::UniformMatrix4fv(
+                    /* location  */ location,
+                    /* count     */ self.len() as _,
+                    /* transpose */ gl::FALSE,
+                    /* value     */ self.as_ptr() as _,
+                );
+            }
         }
         Ok(())
     }
@@ -0,0 +1,387 @@
+use crate::{
+    client::graphics::{GlBuffer, GlContext, object_label},
+    try_gl,
+};
+use anyhow::Result;
+use glam::{UVec4, Vec2, Vec3, Vec4};
+use opengl::gl::{self, types::*};
+use std::{marker::PhantomData, mem::size_of};
+
+////////////////////////////////////////////////////////////////////////////////
+// Vertex layouts
+
+/// Location of a vertex shader input of a particular type.
+///
+/// The build script generates these for each shader,
+/// so that feeding an input from a field of the wrong type
+/// does not compile.
+pub struct GlInputLocation<T>
+{
+    _phantom: PhantomData<fn(T)>,
+    location: GLuint,
+}
+
+impl<T> GlInputLocation<T>
+{
+    /// Refer to the input at the given location.
+    pub const fn new(location: GLuint) -> Self
+    {
+        Self{_phantom: PhantomData, location}
+    }
+
+    /// The location of the input.
+    pub fn location(&self) -> GLuint
+    {
+        self.location
+    }
+}
+
+/// Type that can be the type of a vertex attribute.
+///
+/// # Safety
+///
+/// The attribute format must not describe more bytes
+/// than the size of the type, or OpenGL reads past the vertex.
+pub unsafe trait GlAttribute: Copy
+{
+    /// The type of the vertex shader input that this attribute feeds.
+    ///
+    /// Integer types of any width feed `uint` or `int` inputs.
+    type Input;
+
+    /// Number of components.
+    const SIZE: GLint;
+
+    /// Type of each component.
+    const TYPE: GLenum;
+
+    /// Whether the attribute is an integer attribute in the shader.
+    ///
+    /// Integer attributes are specified with `glVertexArrayAttribIFormat`,
+    /// others with `glVertexArrayAttribFormat` without normalization.
+    const INTEGER: bool;
+}
+
+macro_rules! impl_gl_attribute
+{
+    ($($type:ty => $input:ty, $size:expr, $gl_type:ident, $int:expr;)*) => {
+        $(
+            unsafe impl GlAttribute for $type
+            {
+                type Input = $input;
+                const SIZE: GLint = $size;
+                const TYPE: GLenum = gl::$gl_type;
+                const INTEGER: bool = $int;
+            }
+        )*
+    };
+}
+
+impl_gl_attribute! {
+    f32     => f32,   1, FLOAT,          false;
+    Vec2    => Vec2,  2, FLOAT,          false;
+    Vec3    => Vec3,  3, FLOAT,          false;
+    Vec4    => Vec4,  4, FLOAT,          false;
+    u8      => u32,   1, UNSIGNED_BYTE,  true;
+    u16     => u32,   1, UNSIGNED_SHORT, true;
+    u32     => u32,   1, UNSIGNED_INT,   true;
+    i32     => i32,   1, INT,            true;
+    [u8; 4] => UVec4, 4, UNSIGNED_BYTE,  true;
+}
+
+/// Vertex attribute of a vertex type.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct GlVertexAttribute
+{
+    /// Location of the attribute in the vertex shader.
+    pub location: GLuint,
+
+    /// Offset of the attribute within the vertex, in bytes.
+    pub offset: GLuint,
+
+    /// Number of components.
+    pub size: GLint,
+
+    /// Type of each component.
+    pub type_: GLenum,
+
+    /// Whether the attribute is an integer attribute in the shader.
+    pub integer: bool,
+}
+
+impl GlVertexAttribute
+{
+    /// Describe an attribute given pointers to a vertex and the field.
+    ///
+    /// For use by [`gl_vertex`], which obtains the pointers
+    /// without reading the vertex.
+    #[doc(hidden)]
+    pub fn of_field<V, A>(
+        location: GlInputLocation<A::Input>,
+        vertex: *const V,
+        field: *const A,
+    ) -> Self
+        where A: GlAttribute
+    {
+        let offset = field as usize - vertex as usize;
+        assert!(offset + size_of::<A>() <= size_of::<V>());
+        Self{
+            location: location.location(),
+            offset: offset as GLuint,
+            size: A::SIZE,
+            type_: A::TYPE,
+            integer: A::INTEGER,
+        }
+    }
+}
+
+/// Type that can be stored in a vertex buffer.
+///
+/// Implement this with the [`gl_vertex`] macro,
+/// which derives the offsets of the attributes from the struct layout.
+///
+/// # Safety
+///
+/// Each attribute must lie within the vertex.
+pub unsafe trait GlVertex: Copy
+{
+    /// The attributes of the vertex.
+    fn attributes() -> Vec<GlVertexAttribute>;
+}
+
+/// Implement [`GlVertex`] for a `#[repr(C)]` struct.
+///
+/// Each field is given with the vertex shader input it feeds,
+/// as generated by the build script, and offsets are taken from the struct.
+/// Every field must be listed, so that adding a field
+/// without giving it a location does not compile,
+/// and each field must match the type of its input.
+///
+/// ```ignore
+/// gl_vertex! {
+///     Vertex {
+///         position: vertex_interface::inputs::VERTEX_POSITION,
+///         normal: vertex_interface::inputs::VERTEX_NORMAL,
+///     }
+/// }
+/// ```
+#[macro_export]
+macro_rules! gl_vertex
+{
+    { $vertex:ident { $($field:ident : $location:expr),* $(,)? } } => {
+        // SAFETY: Offsets are derived from the struct layout,
+        //         and `of_field` checks that attributes fit.
+        unsafe impl $crate::client::graphics::GlVertex for $vertex
+        {
+            fn attributes()
+                -> ::std::vec::Vec<$crate::client::graphics::GlVertexAttribute>
+            {
+                // Fail to compile if not all fields are listed.
+                #[allow(unused)]
+                fn exhaustive(vertex: &$vertex)
+                {
+                    let $vertex{$($field: _),*} = vertex;
+                }
+
+                let vertex = ::std::mem::MaybeUninit::<$vertex>::uninit();
+                let vertex = vertex.as_ptr();
+                ::std::vec![
+                    $(
+                        $crate::client::graphics::GlVertexAttribute::of_field(
+                            $location,
+                            vertex,
+                            // SAFETY: The field is not read.
+                            unsafe { ::std::ptr::addr_of!((*vertex).$field) },
+                        ),
+                    )*
+                ]
+            }
+        }
+    };
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// Vertex arrays
+
+/// Owned handle to an OpenGL vertex array.
+pub struct GlVertexArray<'c>
+{
+    context: &'c GlContext,
+    raw: GLuint,
+}
+
+impl<'c> GlVertexArray<'c>
+{
+    /// Create a vertex array with no attributes enabled.
+    pub fn new(context: &'c GlContext) -> Result<Self>
+    {
+        let mut this = Self{context, raw: 0};
+        // SAFETY: The context is current.
+        unsafe {
+            try_gl! { gl::CreateVertexArrays(1, &mut this.raw); }
+        }
+        Ok(this)
+    }
+
+    /// Enable and configure the attributes of a vertex type,
+    /// reading them from the given buffer binding.
+    pub fn vertex_layout<V>(&mut self, binding: GLuint) -> Result<()>
+        where V: GlVertex
+    {
+        for attribute in V::attributes() {
+            self.attribute(binding, &attribute)?;
+        }
+        Ok(())
+    }
+
+    fn attribute(&mut self, binding: GLuint, attribute: &GlVertexAttribute)
+        -> Result<()>
+    {
+        let GlVertexAttribute{location, offset, size, type_, integer} =
+            *attribute;
+        // SAFETY: The context is current.
+        unsafe {
+            try_gl! { gl::EnableVertexArrayAttrib(self.raw, location); }
+            try_gl! {
+                gl::VertexArrayAttribBinding(self.raw, location, binding);
+            }
+            if integer {
+                try_gl! {
+                    gl::VertexArrayAttribIFormat(
+                        /* vaobj          */ self.raw,
+                        /* attribindex    */ location,
+                        /* size           */ size,
+                        /* type           */ type_,
+                        /* relativeoffset */ offset,
+                    );
+                }
+            } else {
+                try_gl! {
+                    gl::VertexArrayAttribFormat(
+                        /* vaobj          */ self.raw,
+                        /* attribindex    */ location,
+                        /* size           */ size,
+                        /* type           */ type_,
+                        /* normalized     */ gl::FALSE,
+                        /* relativeoffset */ offset,
+                    );
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Set how many instances pass before the binding advances.
+    ///
+    /// Zero means the binding advances every vertex.
+    pub fn binding_divisor(&mut self, binding: GLuint, divisor: GLuint)
+        -> Result<()>
+    {
+        // SAFETY: The context is current.
+        unsafe {
+            try_gl! {
+                gl::VertexArrayBindingDivisor(self.raw, binding, divisor);
+            }
+        }
+        Ok(())
+    }
+
+    /// Read vertices from a buffer at the given binding.
+    pub fn vertex_buffer<V>(&self, binding: GLuint, buffer: &GlBuffer<V>)
+        -> Result<()>
+        where V: GlVertex
+    {
+        // SAFETY: The context is current,
+        //         and the stride matches the vertex type.
+        unsafe {
+            try_gl! {
+                gl::VertexArrayVertexBuffer(
+                    /* vaobj        */ self.raw,
+                    /* bindingindex */ binding,
+                    /* buffer       */ buffer.as_raw(),
+                    /* offset       */ 0,
+                    /* stride       */ size_of::<V>() as _,
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// Read indices from a buffer.
+    pub fn element_buffer(&self, buffer: &GlBuffer<u32>) -> Result<()>
+    {
+        // SAFETY: The context is current.
+        unsafe {
+            try_gl! { gl::VertexArrayElementBuffer(self.raw, buffer.as_raw()); }
+        }
+        Ok(())
+    }
+
+    /// Bind the vertex array for drawing.
+    pub fn bind(&self) -> Result<()>
+    {
+        // SAFETY: The context is current.
+        unsafe {
+            try_gl! { gl::BindVertexArray(self.raw); }
+        }
+        Ok(())
+    }
+
+    /// Give the vertex array a name that appears in debug messages.
+    pub fn label(&self, label: &str) -> Result<()>
+    {
+        object_label(self.context, gl::VERTEX_ARRAY, self.raw, label)
+    }
+
+    /// The OpenGL name of the vertex array.
+    pub fn as_raw(&self) -> GLuint
+    {
+        self.raw
+    }
+}
+
+impl<'c> Drop for GlVertexArray<'c>
+{
+    fn drop(&mut self)
+    {
+        // SAFETY: The context is current.
+        unsafe {
+            gl::DeleteVertexArrays(1, &self.raw);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    #[derive(Clone, Copy)]
+    #[repr(C)]
+    struct Vertex
+    {
+        position: Vec3,
+        flags: u8,
+        texcoord: Vec2,
+    }
+
+    gl_vertex! {
+        Vertex {
+            position: GlInputLocation::new(0),
+            flags: GlInputLocation::new(2),
+            texcoord: GlInputLocation::new(1),
+        }
+    }
+
+    #[test]
+    fn offsets_follow_struct_layout()
+    {
+        let attribute = |location, offset, size, type_, integer|
+            GlVertexAttribute{location, offset, size, type_, integer};
+        assert_eq!(Vertex::attributes(), [
+            attribute(0, 0, 3, gl::FLOAT, false),
+            attribute(2, 12, 1, gl::UNSIGNED_BYTE, true),
+            attribute(1, 16, 2, gl::FLOAT, false),
+        ]);
+    }
+}
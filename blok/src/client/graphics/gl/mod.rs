@@ -1,11 +1,23 @@
 pub use self::gl_buffer::*;
+pub use self::gl_context::*;
+pub use self::gl_debug::*;
 pub use self::gl_error::*;
+pub use self::gl_framebuffer::*;
 pub use self::gl_program::*;
+pub use self::gl_renderbuffer::*;
 pub use self::gl_shader::*;
+pub use self::gl_texture::*;
 pub use self::gl_uniform::*;
+pub use self::gl_vertex_array::*;
 
 mod gl_buffer;
+mod gl_context;
+mod gl_debug;
 mod gl_error;
+mod gl_framebuffer;
 mod gl_program;
+mod gl_renderbuffer;
 mod gl_shader;
+mod gl_texture;
 mod gl_uniform;
+mod gl_vertex_array;
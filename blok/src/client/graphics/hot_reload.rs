@@ -0,0 +1,176 @@
+//! Recompiling shaders when their GLSL sources change.
+//!
+//! The build script embeds SPIR-V binaries in the executable,
+//! so changing a shader normally requires a rebuild and a restart.
+//! With the `shader-hot-reload` feature, in debug builds,
+//! a [`ShaderReloader`] watches the GLSL sources in the source tree,
+//! recompiles them when they change,
+//! and pipelines can be rebuilt from the new binaries.
+//! Shaders are compiled with the same compiler and options
+//! as in the build script.
+//!
+//! Shader interfaces are reflected at build time,
+//! so changes to uniforms or inputs still require a rebuild.
+
+use anyhow::{Context, Result, anyhow};
+use notify::{DebouncedEvent, RecommendedWatcher, RecursiveMode, Watcher};
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::mpsc::{Receiver, channel},
+    time::Duration,
+};
+
+#[path = "../../../build/compile.rs"]
+mod compile;
+
+/// Directory of the crate, relative to which source paths are given.
+const CRATE_DIRECTORY: &str = env!("CARGO_MANIFEST_DIR");
+
+/// Directory that contains the GLSL sources.
+const SHADER_DIRECTORY: &str = "src/client/graphics";
+
+/// How long to wait for further changes before recompiling.
+///
+/// Editors often write a file in several steps.
+const DEBOUNCE_DELAY: Duration = Duration::from_millis(100);
+
+/// Watches GLSL sources and recompiles them when they change.
+pub struct ShaderReloader
+{
+    _watcher: RecommendedWatcher,
+    events: Receiver<DebouncedEvent>,
+    binaries: HashMap<PathBuf, Vec<u8>>,
+}
+
+impl ShaderReloader
+{
+    /// Start watching the GLSL sources.
+    pub fn new() -> Result<Self>
+    {
+        let (sender, events) = channel();
+        let mut watcher = notify::watcher(sender, DEBOUNCE_DELAY)
+            .context("Create shader source watcher")?;
+        let directory = Path::new(CRATE_DIRECTORY).join(SHADER_DIRECTORY);
+        watcher.watch(&directory, RecursiveMode::Recursive)
+            .with_context(|| format!("Watch {}", directory.display()))?;
+        Ok(Self{_watcher: watcher, events, binaries: HashMap::new()})
+    }
+
+    /// Recompile the shaders whose sources changed since the last call.
+    ///
+    /// Returns the source paths of the shaders that were recompiled.
+    /// Shaders that fail to compile are logged and not returned,
+    /// so that their previous binary remains in use.
+    pub fn poll(&mut self) -> Vec<PathBuf>
+    {
+        let mut changed = Vec::new();
+        for event in self.events.try_iter() {
+            let path = match event {
+                DebouncedEvent::Create(path)
+                | DebouncedEvent::Write(path)
+                | DebouncedEvent::Rename(_, path) => path,
+                DebouncedEvent::Error(err, _) => {
+                    log::warn!("Cannot watch shader sources: {}", err);
+                    continue;
+                },
+                _ => continue,
+            };
+            let source_path = match source_path(&path) {
+                Some(source_path) => source_path,
+                None => continue,
+            };
+            if !changed.contains(&source_path) {
+                changed.push(source_path);
+            }
+        }
+
+        changed.retain(|source_path| {
+            match compile(source_path) {
+                Ok(binary) => {
+                    log::info!("Recompiled {}", source_path.display());
+                    self.binaries.insert(source_path.clone(), binary);
+                    true
+                },
+                Err(err) => {
+                    log::error!("{:?}", err);
+                    false
+                },
+            }
+        });
+        changed
+    }
+
+    /// The most recent binary of a shader.
+    ///
+    /// This is the binary that was last recompiled from the source path,
+    /// or the given binary that was embedded at build time.
+    pub fn binary<'a>(&'a self, source_path: &str, embedded: &'a [u8])
+        -> &'a [u8]
+    {
+        self.binaries.get(Path::new(source_path))
+            .map(Vec::as_slice)
+            .unwrap_or(embedded)
+    }
+}
+
+/// The source path of a changed file, if it is a GLSL source.
+fn source_path(path: &Path) -> Option<PathBuf>
+{
+    let extension = path.extension()?;
+    if extension != "vert" && extension != "frag" {
+        return None;
+    }
+    let source_path = path.strip_prefix(CRATE_DIRECTORY).ok()?;
+    Some(source_path.to_path_buf())
+}
+
+/// Compile a GLSL source into SPIR-V.
+///
+/// This uses the same compiler and options as the build script.
+fn compile(source_path: &Path) -> Result<Vec<u8>>
+{
+    let optimize = env!("BLOK_OPTIMIZE_SHADERS") == "true";
+    let kind = source_path.extension()
+        .and_then(|extension| extension.to_str())
+        .context("Shader source has no extension")?;
+    let path = Path::new(CRATE_DIRECTORY).join(source_path);
+    compile::compile_shader(kind, &path, optimize).map_err(|err| {
+        anyhow!("Compile {}:\n{}", source_path.display(), err.trim_end())
+    })
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    #[test]
+    fn source_path_is_relative_to_crate()
+    {
+        let path = Path::new(CRATE_DIRECTORY)
+            .join("src/client/graphics/generic/shader.vert");
+        assert_eq!(
+            source_path(&path),
+            Some(PathBuf::from("src/client/graphics/generic/shader.vert")),
+        );
+    }
+
+    #[test]
+    fn source_path_ignores_other_files()
+    {
+        let path = Path::new(CRATE_DIRECTORY)
+            .join("src/client/graphics/generic/mod.rs");
+        assert_eq!(source_path(&path), None);
+        let path = Path::new("/elsewhere/shader.vert");
+        assert_eq!(source_path(path), None);
+    }
+
+    #[test]
+    fn compile_produces_spirv()
+    {
+        let source_path = Path::new("src/client/graphics/generic/shader.frag");
+        let binary = compile(source_path).unwrap();
+        assert_eq!(binary[.. 4], 0x07230203u32.to_le_bytes());
+    }
+}
@@ -0,0 +1,156 @@
+//! Images read back from framebuffers.
+
+use crate::{client::graphics::{GlContext, GlFramebuffer}, try_gl};
+use anyhow::{Context, Result, bail};
+use opengl::gl::{self, types::*};
+use std::{fs::File, io::{BufReader, BufWriter}, path::Path};
+
+/// Image with 8-bit RGBA pixels.
+///
+/// Rows are stored from top to bottom, as in image files.
+/// Note that OpenGL stores rows from bottom to top.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Image
+{
+    /// Width of the image, in pixels.
+    pub width: u32,
+
+    /// Height of the image, in pixels.
+    pub height: u32,
+
+    /// Four bytes per pixel, one row after the other.
+    pub pixels: Vec<u8>,
+}
+
+impl Image
+{
+    /// Read back the color buffer of a framebuffer.
+    ///
+    /// The rectangle starting at the lower left corner
+    /// of the selected read buffer is read back.
+    /// [`None`] denotes the default framebuffer.
+    pub fn read_pixels(
+        context: &GlContext,
+        framebuffer: Option<&GlFramebuffer>,
+        width: u32,
+        height: u32,
+    ) -> Result<Self>
+    {
+        let mut pixels = vec![0; width as usize * height as usize * 4];
+
+        GlFramebuffer::bind_read(context, framebuffer)?;
+        // SAFETY: The context is current,
+        //         and the buffer size matches the buffer.
+        unsafe {
+            try_gl! { gl::PixelStorei(gl::PACK_ALIGNMENT, 1); }
+            try_gl! {
+                gl::ReadnPixels(
+                    /* x       */ 0,
+                    /* y       */ 0,
+                    /* width   */ width as GLsizei,
+                    /* height  */ height as GLsizei,
+                    /* format  */ gl::RGBA,
+                    /* type    */ gl::UNSIGNED_BYTE,
+                    /* bufSize */ pixels.len() as GLsizei,
+                    /* data    */ pixels.as_mut_ptr().cast(),
+                );
+            }
+        }
+
+        let mut this = Self{width, height, pixels};
+        this.flip_vertically();
+        Ok(this)
+    }
+
+    /// Reverse the order of the rows.
+    pub fn flip_vertically(&mut self)
+    {
+        let row_len = self.width as usize * 4;
+        let height = self.height as usize;
+        for y in 0 .. height / 2 {
+            let (top, bottom) = self.pixels.split_at_mut((height - y - 1) * row_len);
+            top[y * row_len .. (y + 1) * row_len]
+                .swap_with_slice(&mut bottom[.. row_len]);
+        }
+    }
+
+    /// Read an image from a PNG file.
+    ///
+    /// The PNG file must have 8-bit RGBA pixels,
+    /// as written by [`write_png`][`Self::write_png`].
+    pub fn read_png(path: &Path) -> Result<Self>
+    {
+        let context = || format!("Read PNG file {}", path.display());
+        let file = File::open(path).with_context(context)?;
+        let decoder = png::Decoder::new(BufReader::new(file));
+        let mut reader = decoder.read_info().with_context(context)?;
+        let mut pixels = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut pixels).with_context(context)?;
+        if info.color_type != png::ColorType::Rgba
+            || info.bit_depth != png::BitDepth::Eight {
+            bail!("{}: Pixels are not 8-bit RGBA", context());
+        }
+        pixels.truncate(info.buffer_size());
+        Ok(Self{width: info.width, height: info.height, pixels})
+    }
+
+    /// Write the image to a PNG file.
+    pub fn write_png(&self, path: &Path) -> Result<()>
+    {
+        let context = || format!("Write PNG file {}", path.display());
+        let file = File::create(path).with_context(context)?;
+        let mut encoder =
+            png::Encoder::new(BufWriter::new(file), self.width, self.height);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut writer = encoder.write_header().with_context(context)?;
+        writer.write_image_data(&self.pixels).with_context(context)?;
+        writer.finish().with_context(context)?;
+        Ok(())
+    }
+
+    /// The largest difference between corresponding channels of two images.
+    ///
+    /// Returns [`None`] if the images differ in size.
+    pub fn max_difference(&self, other: &Self) -> Option<u8>
+    {
+        if (self.width, self.height) != (other.width, other.height) {
+            return None;
+        }
+        let differences = Iterator::zip(self.pixels.iter(), other.pixels.iter())
+            .map(|(&a, &b)| (a as i16 - b as i16).unsigned_abs() as u8);
+        Some(differences.max().unwrap_or(0))
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    #[test]
+    fn flip_and_compare()
+    {
+        let mut image = Image{
+            width: 1,
+            height: 3,
+            pixels: vec![
+                0, 0, 0, 255,
+                1, 1, 1, 255,
+                2, 2, 2, 255,
+            ],
+        };
+        let original = image.clone();
+
+        image.flip_vertically();
+        assert_eq!(image.pixels[.. 4], [2, 2, 2, 255]);
+        assert_eq!(image.pixels[4 .. 8], [1, 1, 1, 255]);
+        assert_eq!(image.max_difference(&original), Some(2));
+
+        image.flip_vertically();
+        assert_eq!(image, original);
+
+        let wide = Image{width: 3, height: 1, pixels: original.pixels.clone()};
+        assert_eq!(wide.max_difference(&original), None);
+    }
+}
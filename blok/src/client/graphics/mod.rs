@@ -2,8 +2,18 @@
 
 pub use self::gl::*;
 
+pub mod animation;
+pub mod entities;
 pub mod generic;
+#[cfg(all(feature = "shader-hot-reload", debug_assertions))]
+pub mod hot_reload;
+pub mod image;
+pub mod offscreen;
 pub mod parameters;
+pub mod renderer;
+pub mod screenshot;
+pub mod shadow;
+pub mod sky;
 pub mod trivial_block;
 
 mod gl;
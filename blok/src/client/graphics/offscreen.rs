@@ -0,0 +1,269 @@
+//! Rendering without a window.
+//!
+//! This is used by golden-image tests, which run on machines
+//! that have neither a display nor a GPU.
+//! On such machines Mesa provides a software renderer (llvmpipe),
+//! which we reach through EGL with the surfaceless platform.
+
+use crate::client::graphics::{
+    GlContext,
+    GlFramebuffer,
+    GlRenderbuffer,
+    parameters,
+};
+use anyhow::Result;
+use opengl::gl::{self, types::*};
+
+#[cfg(target_os = "linux")]
+pub use self::egl_context::*;
+
+/// Framebuffer with a color and a depth renderbuffer.
+///
+/// The renderbuffers use the same pixel format as windows do,
+/// so that offscreen rendering produces the same results.
+pub struct OffscreenFramebuffer<'c>
+{
+    /// The framebuffer to render to.
+    pub framebuffer: GlFramebuffer<'c>,
+
+    /// Width of the renderbuffers, in pixels.
+    pub width: GLsizei,
+
+    /// Height of the renderbuffers, in pixels.
+    pub height: GLsizei,
+
+    _color: GlRenderbuffer<'c>,
+    _depth: GlRenderbuffer<'c>,
+}
+
+impl<'c> OffscreenFramebuffer<'c>
+{
+    /// Create a framebuffer with renderbuffers of the given size.
+    pub fn new(context: &'c GlContext, width: GLsizei, height: GLsizei)
+        -> Result<Self>
+    {
+        // Keep these in sync with the pixel format parameters.
+        const _: () = assert!(parameters::pixel_format::COLOR_BITS == 8);
+        const _: () = assert!(parameters::pixel_format::ALPHA_BITS == 8);
+        const _: () = assert!(parameters::pixel_format::DEPTH_BITS == 24);
+
+        let color = GlRenderbuffer::new_storage(
+            context, gl::RGBA8, width, height)?;
+        let depth = GlRenderbuffer::new_storage(
+            context, gl::DEPTH_COMPONENT24, width, height)?;
+
+        let mut framebuffer = GlFramebuffer::new(context)?;
+        framebuffer.renderbuffer(gl::COLOR_ATTACHMENT0, &color)?;
+        framebuffer.renderbuffer(gl::DEPTH_ATTACHMENT, &depth)?;
+        framebuffer.draw_buffer(gl::COLOR_ATTACHMENT0)?;
+        framebuffer.read_buffer(gl::COLOR_ATTACHMENT0)?;
+        framebuffer.check_complete(gl::DRAW_FRAMEBUFFER)?;
+
+        Ok(Self{framebuffer, width, height, _color: color, _depth: depth})
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod egl_context
+{
+    use crate::client::graphics::GlContext;
+    use anyhow::{Context, Result, anyhow};
+    use opengl::{egl::{self, types::*}, gl};
+    use std::{
+        ffi::CString,
+        ptr::null,
+        sync::atomic::{AtomicBool, Ordering},
+        thread,
+        time::Duration,
+    };
+
+    /// Whether an offscreen context exists in this process.
+    ///
+    /// OpenGL procedures are loaded into global function pointers,
+    /// which must not be written while another thread uses them.
+    static CONTEXT_EXISTS: AtomicBool = AtomicBool::new(false);
+
+    /// OpenGL context that is not associated with any window.
+    ///
+    /// Creating the context makes it current on the calling thread
+    /// and loads the OpenGL procedures into global function pointers.
+    /// The context is destroyed when this value is dropped.
+    ///
+    /// Only one offscreen context exists at a time;
+    /// creating another one waits until the existing one is dropped.
+    /// This makes it safe to create contexts from parallel tests.
+    pub struct OffscreenContext
+    {
+        display: EGLDisplay,
+        context: EGLContext,
+        gl_context: GlContext,
+
+        // Declared last, so that it is released after the context.
+        _lock: ContextLock,
+    }
+
+    impl OffscreenContext
+    {
+        /// Create a context with the required OpenGL version.
+        ///
+        /// This blocks while another offscreen context exists.
+        pub fn new() -> Result<Self>
+        {
+            let lock = ContextLock::acquire();
+            // SAFETY: The EGL calls are given valid arguments,
+            //         the handles are released on failure,
+            //         and no other offscreen context uses OpenGL.
+            unsafe {
+                Self::new_impl(lock)
+            }
+        }
+
+        unsafe fn new_impl(lock: ContextLock) -> Result<Self>
+        {
+            use crate::client::graphics::parameters::opengl::{MAJOR, MINOR};
+
+            let display = egl::GetPlatformDisplay(
+                /* platform        */ egl::PLATFORM_SURFACELESS_MESA,
+                /* native_display  */ egl::DEFAULT_DISPLAY as *mut _,
+                /* attrib_list     */ null(),
+            );
+            if display == egl::NO_DISPLAY {
+                return Err(egl_error())
+                    .context("Cannot get surfaceless EGL display");
+            }
+
+            let (mut major, mut minor) = (0, 0);
+            if egl::Initialize(display, &mut major, &mut minor) == egl::FALSE {
+                return Err(egl_error()).context("Cannot initialize EGL");
+            }
+
+            // From here on, dropping `this` cleans up on failure.
+            // The token is only handed out once the context is current.
+            let mut this = Self{
+                display,
+                context: egl::NO_CONTEXT,
+                gl_context: GlContext::new_unchecked(),
+                _lock: lock,
+            };
+
+            if egl::BindAPI(egl::OPENGL_API) == egl::FALSE {
+                return Err(egl_error()).context("Cannot bind OpenGL API");
+            }
+
+            // We never create surfaces, but the default is windows.
+            let config_attribs = [
+                egl::RENDERABLE_TYPE as EGLint, egl::OPENGL_BIT as EGLint,
+                egl::SURFACE_TYPE as EGLint, 0,
+                egl::NONE as EGLint,
+            ];
+            let mut config = null();
+            let mut num_config = 0;
+            let ok = egl::ChooseConfig(
+                /* dpy         */ display,
+                /* attrib_list */ config_attribs.as_ptr(),
+                /* configs     */ &mut config,
+                /* config_size */ 1,
+                /* num_config  */ &mut num_config,
+            );
+            if ok == egl::FALSE || num_config == 0 {
+                return Err(egl_error()).context("Cannot choose EGL config");
+            }
+
+            let context_attribs = [
+                egl::CONTEXT_MAJOR_VERSION as EGLint, MAJOR as EGLint,
+                egl::CONTEXT_MINOR_VERSION as EGLint, MINOR as EGLint,
+                egl::CONTEXT_OPENGL_PROFILE_MASK as EGLint,
+                egl::CONTEXT_OPENGL_CORE_PROFILE_BIT as EGLint,
+                egl::NONE as EGLint,
+            ];
+            this.context = egl::CreateContext(
+                /* dpy           */ display,
+                /* config        */ config,
+                /* share_context */ egl::NO_CONTEXT,
+                /* attrib_list   */ context_attribs.as_ptr(),
+            );
+            if this.context == egl::NO_CONTEXT {
+                return Err(egl_error()).context("Cannot create EGL context");
+            }
+
+            let ok = egl::MakeCurrent(
+                /* dpy  */ display,
+                /* draw */ egl::NO_SURFACE,
+                /* read */ egl::NO_SURFACE,
+                /* ctx  */ this.context,
+            );
+            if ok == egl::FALSE {
+                return Err(egl_error()).context("Cannot make context current");
+            }
+
+            // Load OpenGL procedures into global function pointers.
+            gl::load_with(|proc_name| {
+                let proc_name = CString::new(proc_name).unwrap();
+                egl::GetProcAddress(proc_name.as_ptr()) as *const _
+            });
+
+            Ok(this)
+        }
+
+        /// Token for calling OpenGL while the context exists.
+        pub fn gl(&self) -> &GlContext
+        {
+            &self.gl_context
+        }
+    }
+
+    impl Drop for OffscreenContext
+    {
+        fn drop(&mut self)
+        {
+            // SAFETY: The display was initialized by `new`.
+            unsafe {
+                egl::MakeCurrent(
+                    /* dpy  */ self.display,
+                    /* draw */ egl::NO_SURFACE,
+                    /* read */ egl::NO_SURFACE,
+                    /* ctx  */ egl::NO_CONTEXT,
+                );
+                if self.context != egl::NO_CONTEXT {
+                    egl::DestroyContext(self.display, self.context);
+                }
+                egl::Terminate(self.display);
+            }
+        }
+    }
+
+    /// Exclusive right to create an offscreen context,
+    /// released when dropped.
+    struct ContextLock;
+
+    impl ContextLock
+    {
+        /// Wait until no offscreen context exists.
+        fn acquire() -> Self
+        {
+            while CONTEXT_EXISTS.compare_exchange_weak(
+                /* current */ false,
+                /* new     */ true,
+                /* success */ Ordering::Acquire,
+                /* failure */ Ordering::Relaxed,
+            ).is_err() {
+                thread::sleep(Duration::from_millis(1));
+            }
+            Self
+        }
+    }
+
+    impl Drop for ContextLock
+    {
+        fn drop(&mut self)
+        {
+            CONTEXT_EXISTS.store(false, Ordering::Release);
+        }
+    }
+
+    /// Describe the most recent EGL error on this thread.
+    unsafe fn egl_error() -> anyhow::Error
+    {
+        anyhow!("EGL error {:#06X}", egl::GetError())
+    }
+}
@@ -0,0 +1,277 @@
+//! Drawing complete frames.
+
+use crate::{
+    client::graphics::{
+        GlContext,
+        GlFramebuffer,
+        generic,
+        shadow::{Cascades, Perspective, ShadowMap},
+        trivial_block,
+    },
+    try_gl,
+};
+use anyhow::Result;
+use glam::{IVec2, Mat4};
+use opengl::gl;
+use std::collections::{BTreeMap, btree_map::Entry};
+
+#[cfg(all(feature = "shader-hot-reload", debug_assertions))]
+use {crate::client::graphics::hot_reload::ShaderReloader, std::path::Path};
+
+/// Point of view from which a frame is drawn.
+#[derive(Clone, Copy, Debug)]
+pub struct Camera
+{
+    /// The view matrix.
+    pub v_matrix: Mat4,
+
+    /// The projection, whose aspect ratio should match the viewport.
+    pub perspective: Perspective,
+}
+
+/// Everything that is drawn in a frame.
+pub struct Scene<'a>
+{
+    /// Sun and ambient light.
+    pub lighting: generic::Lighting,
+
+    /// Models to draw with the generic pipeline, with their instances.
+    pub generic_models: &'a [(&'a generic::Model<'a>, &'a [generic::Instance])],
+
+    /// Chunks to draw with the trivial block pipeline.
+    pub trivial_block_face_sets: &'a [trivial_block::FaceSet<'a>],
+}
+
+/// Pipelines and resources used for drawing a frame.
+pub struct Renderer<'c>
+{
+    context: &'c GlContext,
+    fragment_shader: generic::FragmentShader<'c>,
+
+    /// Generic pipelines for each number of bones, created
+    /// when a model with that number of bones is first drawn.
+    generic_passes: BTreeMap<usize, GenericPass<'c>>,
+
+    trivial_block_pipeline: trivial_block::Pipeline<'c>,
+    trivial_block_depth_pipeline: trivial_block::Pipeline<'c>,
+    environment: generic::Environment<'c>,
+    shadow_map: ShadowMap<'c>,
+    shadow_map_resolution: u32,
+    atlas_size: IVec2,
+}
+
+/// Generic pipelines specialized for a number of bones,
+/// with the instances of the models that have that many bones.
+struct GenericPass<'c>
+{
+    pipeline: generic::Pipeline<'c>,
+    depth_pipeline: generic::Pipeline<'c>,
+    instances: generic::InstanceBuffer<'c>,
+}
+
+impl<'c> GenericPass<'c>
+{
+    fn new(
+        context: &'c GlContext,
+        fragment_shader: &generic::FragmentShader,
+        bones: usize,
+    ) -> Result<Self>
+    {
+        Ok(Self{
+            pipeline: generic::Pipeline::new(context, fragment_shader, bones)?,
+            depth_pipeline: generic::Pipeline::new_depth_only(context, bones)?,
+            instances: generic::InstanceBuffer::new(context)?,
+        })
+    }
+}
+
+impl<'c> Renderer<'c>
+{
+    /// Create the pipelines and resources.
+    ///
+    /// The atlas size is the number of textures in the texture atlas.
+    pub fn new(
+        context: &'c GlContext,
+        shadow_map_resolution: u32,
+        atlas_size: IVec2,
+    ) -> Result<Self>
+    {
+        let fragment_shader = generic::FragmentShader::new(context)?;
+        Ok(Self{
+            context,
+            generic_passes: BTreeMap::new(),
+            trivial_block_pipeline: trivial_block::Pipeline::new(context, &fragment_shader)?,
+            trivial_block_depth_pipeline: trivial_block::Pipeline::new_depth_only(context)?,
+            fragment_shader,
+            environment: generic::Environment::new(context)?,
+            shadow_map: ShadowMap::new(context, shadow_map_resolution)?,
+            shadow_map_resolution,
+            atlas_size,
+        })
+    }
+
+    /// Rebuild the pipelines whose shaders changed.
+    ///
+    /// Pipelines that fail to rebuild are logged
+    /// and keep using their previous programs.
+    #[cfg(all(feature = "shader-hot-reload", debug_assertions))]
+    pub fn reload_shaders(&mut self, reloader: &mut ShaderReloader)
+    {
+        let changed = reloader.poll();
+        let changed = |path| changed.iter().any(|c| c == Path::new(path));
+        let generic_changed =
+            changed(generic::VERTEX_SHADER_PATH)
+            || changed(generic::INSTANCED_VERTEX_SHADER_PATH);
+        let trivial_block_changed = changed(trivial_block::VERTEX_SHADER_PATH);
+        let mut fragment_changed = changed(generic::FRAGMENT_SHADER_PATH);
+
+        let log_error = |result: Result<()>| {
+            if let Err(err) = result {
+                log::error!("{:?}", err);
+            }
+        };
+
+        if fragment_changed {
+            let context = self.context;
+            match generic::FragmentShader::from_reloader(context, reloader) {
+                Ok(fragment_shader) => self.fragment_shader = fragment_shader,
+                Err(err) => {
+                    log::error!("{:?}", err);
+                    fragment_changed = false;
+                },
+            }
+        }
+
+        for pass in self.generic_passes.values_mut() {
+            if generic_changed || fragment_changed {
+                log_error(pass.pipeline.reload(
+                    /* reloader        */ reloader,
+                    /* fragment_shader */ Some(&self.fragment_shader),
+                ));
+            }
+            if generic_changed {
+                log_error(pass.depth_pipeline.reload(
+                    /* reloader        */ reloader,
+                    /* fragment_shader */ None,
+                ));
+            }
+        }
+        if trivial_block_changed || fragment_changed {
+            log_error(self.trivial_block_pipeline.reload(
+                /* reloader        */ reloader,
+                /* fragment_shader */ Some(&self.fragment_shader),
+            ));
+        }
+        if trivial_block_changed {
+            log_error(self.trivial_block_depth_pipeline.reload(
+                /* reloader        */ reloader,
+                /* fragment_shader */ None,
+            ));
+        }
+    }
+
+    /// Draw a frame into a framebuffer.
+    ///
+    /// [`None`] denotes the default framebuffer.
+    /// The viewport covers the framebuffer from the lower left corner,
+    /// with the given width and height.
+    pub fn draw(
+        &mut self,
+        target: Option<&GlFramebuffer>,
+        viewport_size: IVec2,
+        camera: &Camera,
+        scene: &Scene,
+    ) -> Result<()>
+    {
+        let Scene{lighting, generic_models, trivial_block_face_sets} = *scene;
+
+        // Models are drawn with pipelines for their number of bones,
+        // so that only the bone matrices they use are uploaded.
+        let mut generic_models_by_bones: BTreeMap<usize, Vec<_>> =
+            BTreeMap::new();
+        for &(model, instances) in generic_models {
+            generic_models_by_bones.entry(model.bones()).or_default()
+                .push((model, instances));
+        }
+
+        // Upload the instances once for all passes.
+        for (&bones, models) in &generic_models_by_bones {
+            let pass = match self.generic_passes.entry(bones) {
+                Entry::Occupied(entry) => entry.into_mut(),
+                Entry::Vacant(entry) => entry.insert(GenericPass::new(
+                    /* context         */ self.context,
+                    /* fragment_shader */ &self.fragment_shader,
+                    /* bones           */ bones,
+                )?),
+            };
+            pass.instances.upload(
+                /* bones  */ bones,
+                /* models */ models.iter().map(|(_, i)| *i),
+            )?;
+        }
+        let passes = &self.generic_passes;
+        let generic_passes = || {
+            generic_models_by_bones.iter().map(|(bones, models)| {
+                let models = models.iter().map(|(m, _)| *m);
+                (&passes[bones], models)
+            })
+        };
+
+        // Render the scene from the sun into each shadow cascade.
+        let cascades = Cascades::new(
+            /* v_matrix      */ &camera.v_matrix,
+            /* perspective   */ &camera.perspective,
+            /* sun_direction */ lighting.sun_direction,
+            /* resolution    */ self.shadow_map_resolution,
+        );
+        for (cascade, vp_matrix) in cascades.vp_matrices.iter().enumerate() {
+            let trivial_block_depth_pipeline = &self.trivial_block_depth_pipeline;
+            let atlas_size = &self.atlas_size;
+            self.shadow_map.render_cascade(cascade, || {
+                for (pass, models) in generic_passes() {
+                    pass.depth_pipeline.render_instanced(
+                        /* vp_matrix       */ vp_matrix,
+                        /* instance_buffer */ &pass.instances,
+                        /* models          */ models,
+                    )?;
+                }
+                trivial_block_depth_pipeline.render(
+                    /* atlas_size */ atlas_size,
+                    /* vp_matrix  */ vp_matrix,
+                    /* models     */ trivial_block_face_sets,
+                )
+            })?;
+        }
+
+        self.environment.update(&lighting, &camera.v_matrix, &cascades)?;
+        self.environment.bind(&self.shadow_map)?;
+
+        GlFramebuffer::bind_draw(self.context, target)?;
+        // SAFETY: The context is current.
+        unsafe {
+            let (width, height) = (viewport_size.x, viewport_size.y);
+            try_gl! { gl::Viewport(0, 0, width, height); }
+            try_gl! { gl::Enable(gl::DEPTH_TEST); }
+            try_gl! { gl::ClearColor(0.1, 0.9, 0.2, 1.0); }
+            try_gl! { gl::Clear(gl::COLOR_BUFFER_BIT | gl::DEPTH_BUFFER_BIT); }
+        }
+
+        let vp_matrix = camera.perspective.p_matrix() * camera.v_matrix;
+
+        for (pass, models) in generic_passes() {
+            pass.pipeline.render_instanced(
+                /* vp_matrix       */ &vp_matrix,
+                /* instance_buffer */ &pass.instances,
+                /* models          */ models,
+            )?;
+        }
+
+        self.trivial_block_pipeline.render(
+            /* atlas_size */ &self.atlas_size,
+            /* vp_matrix  */ &vp_matrix,
+            /* models     */ trivial_block_face_sets,
+        )?;
+
+        Ok(())
+    }
+}
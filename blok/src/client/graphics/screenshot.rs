@@ -0,0 +1,96 @@
+//! Saving the contents of a framebuffer to a file.
+
+use crate::client::graphics::{GlContext, GlFramebuffer, image::Image};
+use anyhow::{Context, Result};
+use std::{
+    fs::create_dir_all,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// Read back a framebuffer and write it to a timestamped PNG file.
+///
+/// [`None`] denotes the default framebuffer of a window;
+/// call this before swapping buffers, as the back buffer is read.
+/// Offscreen contexts have no default framebuffer,
+/// so pass the framebuffer that was rendered into,
+/// such as that of an [`OffscreenFramebuffer`].
+/// Returns the path of the file that was written.
+///
+/// [`OffscreenFramebuffer`]: super::offscreen::OffscreenFramebuffer
+pub fn save_screenshot(
+    context: &GlContext,
+    framebuffer: Option<&GlFramebuffer>,
+    width: u32,
+    height: u32,
+    directory: &Path,
+) -> Result<PathBuf>
+{
+    let image = Image::read_pixels(context, framebuffer, width, height)
+        .context("Read back framebuffer for screenshot")?;
+
+    create_dir_all(directory)
+        .with_context(|| format!("Create directory {}", directory.display()))?;
+
+    let path = directory.join(screenshot_file_name(SystemTime::now()));
+    image.write_png(&path)?;
+    Ok(path)
+}
+
+/// File name for a screenshot taken at the given time.
+///
+/// The time is formatted in UTC with millisecond precision,
+/// so that file names sort chronologically and do not collide
+/// when taking screenshots in quick succession.
+fn screenshot_file_name(time: SystemTime) -> String
+{
+    let since_epoch = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let seconds = since_epoch.as_secs();
+    let (year, month, day) = civil_from_days((seconds / 86400) as i64);
+    format!(
+        "screenshot-{:04}{:02}{:02}-{:02}{:02}{:02}-{:03}.png",
+        year, month, day,
+        seconds / 3600 % 24, seconds / 60 % 60, seconds % 60,
+        since_epoch.subsec_millis(),
+    )
+}
+
+/// Convert a number of days since 1970-01-01 into a date.
+///
+/// This is the algorithm described by Howard Hinnant in
+/// “chrono-Compatible Low-Level Date Algorithms”.
+fn civil_from_days(days: i64) -> (i64, u32, u32)
+{
+    let days = days + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days.rem_euclid(146097);
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524
+                     - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4
+                                    - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = year_of_era + era * 400 + (month <= 2) as i64;
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    use std::time::Duration;
+
+    #[test]
+    fn file_name_is_utc_timestamp()
+    {
+        let time = UNIX_EPOCH + Duration::from_millis(1_709_210_096_042);
+        assert_eq!(
+            screenshot_file_name(time),
+            "screenshot-20240229-123456-042.png",
+        );
+        assert_eq!(civil_from_days(0), (1970, 1, 1));
+        assert_eq!(civil_from_days(-1), (1969, 12, 31));
+    }
+}
@@ -0,0 +1,306 @@
+//! Cascaded shadow maps for sun light.
+//!
+//! The view frustum of the camera is split into several cascades
+//! along the view direction, each covering a larger distance.
+//! For each cascade the scene is rendered from the point of view of the sun
+//! into a layer of a depth texture, using the depth-only pipelines.
+//! The fragment shader then picks the cascade for each fragment
+//! and compares the depth of the fragment against the shadow map.
+
+use crate::{
+    client::graphics::{GlContext, GlFramebuffer, GlTexture},
+    try_gl,
+};
+use anyhow::Result;
+use glam::{Mat4, Vec3, vec2, vec3};
+use opengl::gl;
+
+/// Number of cascades.
+///
+/// This must agree with `CASCADES` in the fragment shader.
+pub const CASCADES: usize = 4;
+
+/// How far behind each cascade shadow casters are included.
+///
+/// Objects outside the view frustum can still cast shadows into it,
+/// so the volume rendered into each cascade extends towards the sun.
+const CASTER_MARGIN: f32 = 64.0;
+
+/// Weight between logarithmic and uniform cascade splits.
+///
+/// Logarithmic splits give the best resolution close to the camera,
+/// but make the first cascades impractically small.
+const SPLIT_LAMBDA: f32 = 0.75;
+
+/// Perspective projection of a camera.
+///
+/// The fields correspond to the parameters of [`Mat4::perspective_rh`].
+#[allow(missing_docs)]
+#[derive(Clone, Copy, Debug)]
+pub struct Perspective
+{
+    pub fov_y_radians: f32,
+    pub aspect_ratio: f32,
+    pub z_near: f32,
+    pub z_far: f32,
+}
+
+impl Perspective
+{
+    /// The projection matrix.
+    pub fn p_matrix(&self) -> Mat4
+    {
+        Mat4::perspective_rh(
+            self.fov_y_radians,
+            self.aspect_ratio,
+            self.z_near,
+            self.z_far,
+        )
+    }
+}
+
+/// View–projection matrices from the sun for each cascade.
+#[derive(Clone, Copy, Debug)]
+pub struct Cascades
+{
+    /// View–projection matrix to render each cascade with.
+    pub vp_matrices: [Mat4; CASCADES],
+
+    /// Distance from the camera at which each cascade ends.
+    pub far: [f32; CASCADES],
+}
+
+impl Cascades
+{
+    /// Fit the cascades to the view frustum of a camera.
+    ///
+    /// # Parameters
+    ///
+    /// <dl>
+    /// <dt><code>v_matrix</code></dt>
+    /// <dd>The view matrix of the camera.</dd>
+    /// <dt><code>perspective</code></dt>
+    /// <dd>The projection of the camera.
+    ///     Shadows end at its far plane.</dd>
+    /// <dt><code>sun_direction</code></dt>
+    /// <dd>The normalized direction in which sun light travels.</dd>
+    /// <dt><code>resolution</code></dt>
+    /// <dd>The width and height of the shadow map.</dd>
+    /// </dl>
+    pub fn new(
+        v_matrix: &Mat4,
+        perspective: &Perspective,
+        sun_direction: Vec3,
+        resolution: u32,
+    ) -> Self
+    {
+        let Perspective{z_near, z_far, ..} = *perspective;
+        let inverse_v_matrix = v_matrix.inverse();
+
+        let mut vp_matrices = [Mat4::IDENTITY; CASCADES];
+        let mut far = [0.0; CASCADES];
+        let mut near = z_near;
+        for i in 0 .. CASCADES {
+            let fraction = (i + 1) as f32 / CASCADES as f32;
+            let log = z_near * (z_far / z_near).powf(fraction);
+            let uniform = z_near + (z_far - z_near) * fraction;
+            far[i] = SPLIT_LAMBDA * log + (1.0 - SPLIT_LAMBDA) * uniform;
+
+            let corners = frustum_corners(perspective, near, far[i])
+                .map(|c| inverse_v_matrix.transform_point3(c));
+            vp_matrices[i] = fit_cascade(&corners, sun_direction, resolution);
+            near = far[i];
+        }
+
+        Self{vp_matrices, far}
+    }
+}
+
+/// The corners of a slice of the view frustum in view space.
+fn frustum_corners(perspective: &Perspective, near: f32, far: f32)
+    -> [Vec3; 8]
+{
+    let tan_y = (0.5 * perspective.fov_y_radians).tan();
+    let tan = vec2(tan_y * perspective.aspect_ratio, tan_y);
+    let mut corners = [Vec3::ZERO; 8];
+    for (i, corner) in corners.iter_mut().enumerate() {
+        let distance = if i & 4 == 0 { near } else { far };
+        let sx = if i & 1 == 0 { -1.0 } else { 1.0 };
+        let sy = if i & 2 == 0 { -1.0 } else { 1.0 };
+        *corner = vec3(sx * tan.x, sy * tan.y, -1.0) * distance;
+    }
+    corners
+}
+
+/// Compute the view–projection matrix for a cascade.
+///
+/// The cascade is fit around a bounding sphere of the frustum slice,
+/// so that its size does not change when the camera rotates.
+/// Its position is snapped to whole texels of the shadow map,
+/// so that shadow edges do not shimmer when the camera moves.
+fn fit_cascade(corners: &[Vec3; 8], sun_direction: Vec3, resolution: u32)
+    -> Mat4
+{
+    let center = corners.iter().fold(Vec3::ZERO, |a, &b| a + b) / 8.0;
+    let radius = corners.iter()
+        .map(|&c| c.distance(center))
+        .fold(0.0, f32::max)
+        .ceil();
+
+    // Snap the center in the plane perpendicular to the sun.
+    let up = if sun_direction.z.abs() > 0.99 { Vec3::Y } else { Vec3::Z };
+    let rotation = Mat4::look_at_rh(Vec3::ZERO, sun_direction, up);
+    let texel = 2.0 * radius / resolution as f32;
+    let mut light_center = rotation.transform_point3(center);
+    light_center.x = (light_center.x / texel).floor() * texel;
+    light_center.y = (light_center.y / texel).floor() * texel;
+    let center = rotation.inverse().transform_point3(light_center);
+
+    let eye = center - sun_direction * (radius + CASTER_MARGIN);
+    let v_matrix = Mat4::look_at_rh(eye, center, up);
+    let p_matrix = Mat4::orthographic_rh(
+        /* left   */ -radius,
+        /* right  */ radius,
+        /* bottom */ -radius,
+        /* top    */ radius,
+        /* near   */ 0.0,
+        /* far    */ 2.0 * radius + CASTER_MARGIN,
+    );
+    p_matrix * v_matrix
+}
+
+/// Depth texture with a layer for each cascade.
+pub struct ShadowMap<'c>
+{
+    context: &'c GlContext,
+    texture: GlTexture<'c>,
+    framebuffer: GlFramebuffer<'c>,
+    resolution: u32,
+}
+
+impl<'c> ShadowMap<'c>
+{
+    /// Create a shadow map with the given width and height.
+    pub fn new(context: &'c GlContext, resolution: u32) -> Result<Self>
+    {
+        let mut texture = GlTexture::new(context, gl::TEXTURE_2D_ARRAY)?;
+        texture.storage_3d(
+            /* levels          */ 1,
+            /* internal_format */ gl::DEPTH_COMPONENT24,
+            /* width           */ resolution as _,
+            /* height          */ resolution as _,
+            /* depth           */ CASCADES as _,
+        )?;
+
+        // Sampling compares against the stored depth,
+        // and linear filtering averages the results of four comparisons.
+        let compare_mode = gl::COMPARE_REF_TO_TEXTURE;
+        texture.parameter_i(gl::TEXTURE_COMPARE_MODE, compare_mode as _)?;
+        texture.parameter_i(gl::TEXTURE_COMPARE_FUNC, gl::LEQUAL as _)?;
+        texture.parameter_i(gl::TEXTURE_MIN_FILTER, gl::LINEAR as _)?;
+        texture.parameter_i(gl::TEXTURE_MAG_FILTER, gl::LINEAR as _)?;
+
+        // Anything outside the shadow map is lit.
+        let wrap = gl::CLAMP_TO_BORDER as _;
+        texture.parameter_i(gl::TEXTURE_WRAP_S, wrap)?;
+        texture.parameter_i(gl::TEXTURE_WRAP_T, wrap)?;
+        texture.parameter_fv(gl::TEXTURE_BORDER_COLOR, &[1.0; 4])?;
+
+        let mut framebuffer = GlFramebuffer::new(context)?;
+        framebuffer.draw_buffer(gl::NONE)?;
+        framebuffer.texture_layer(gl::DEPTH_ATTACHMENT, &texture, 0, 0)?;
+        framebuffer.check_complete(gl::DRAW_FRAMEBUFFER)?;
+
+        Ok(Self{context, texture, framebuffer, resolution})
+    }
+
+    /// Render the depth of the scene into a cascade.
+    ///
+    /// The closure must render the scene using the depth-only pipelines
+    /// and the view–projection matrix of the cascade.
+    /// Afterwards the default framebuffer is bound again,
+    /// but the caller must restore the viewport.
+    pub fn render_cascade<F>(&mut self, cascade: usize, render: F)
+        -> Result<()>
+        where F: FnOnce() -> Result<()>
+    {
+        assert!(cascade < CASCADES);
+
+        self.framebuffer.texture_layer(
+            /* attachment */ gl::DEPTH_ATTACHMENT,
+            /* texture    */ &self.texture,
+            /* level      */ 0,
+            /* layer      */ cascade as _,
+        )?;
+        self.framebuffer.bind()?;
+
+        let size = self.resolution as _;
+        // SAFETY: The context is current.
+        unsafe {
+            try_gl! { gl::Viewport(0, 0, size, size); }
+            try_gl! { gl::Enable(gl::DEPTH_TEST); }
+            try_gl! { gl::Clear(gl::DEPTH_BUFFER_BIT); }
+
+            // Offset the depth to avoid surfaces shadowing themselves.
+            try_gl! { gl::Enable(gl::POLYGON_OFFSET_FILL); }
+            try_gl! { gl::PolygonOffset(2.0, 4.0); }
+        }
+
+        let result = render();
+
+        // SAFETY: The context is current.
+        unsafe {
+            try_gl! { gl::Disable(gl::POLYGON_OFFSET_FILL); }
+        }
+        GlFramebuffer::bind_draw(self.context, None)?;
+
+        result
+    }
+
+    /// The depth texture, with one layer per cascade.
+    pub fn texture(&self) -> &GlTexture<'c>
+    {
+        &self.texture
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    use glam::Vec4Swizzles;
+
+    #[test]
+    fn cascades_cover_view()
+    {
+        let v_matrix = Mat4::look_at_rh(
+            /* eye    */ vec3(10.0, -20.0, 30.0),
+            /* center */ vec3(50.0, 40.0, 0.0),
+            /* up     */ Vec3::Z,
+        );
+        let perspective = Perspective{
+            fov_y_radians: 1.0,
+            aspect_ratio: 1.5,
+            z_near: 1.0,
+            z_far: 200.0,
+        };
+        let sun_direction = vec3(0.3, 0.2, -1.0).normalize();
+        let cascades = Cascades::new(&v_matrix, &perspective, sun_direction, 1024);
+
+        assert!(cascades.far.windows(2).all(|w| w[0] < w[1]));
+        assert!((cascades.far[CASCADES - 1] - 200.0).abs() < 1e-3);
+
+        // Points in the view frustum must fall inside their cascade.
+        let inverse_v_matrix = v_matrix.inverse();
+        let mut near = perspective.z_near;
+        for (vp_matrix, &far) in cascades.vp_matrices.iter().zip(&cascades.far) {
+            for corner in frustum_corners(&perspective, near, far) {
+                let world = inverse_v_matrix.transform_point3(corner);
+                let clip = *vp_matrix * world.extend(1.0);
+                assert!(clip.xyz().abs().max_element() <= 1.0, "{}", clip);
+            }
+            near = far;
+        }
+    }
+}
@@ -0,0 +1,94 @@
+//! Lighting that changes with the time of day.
+
+use crate::{client::graphics::generic::Lighting, state::Tick};
+use glam::{Vec3, vec3};
+use std::f32::consts::TAU;
+
+/// Sun movement and colours over the course of a day.
+///
+/// At the first tick of each day the sun rises in the east.
+/// It sets in the west halfway through the day,
+/// after which it is night until the end of the day.
+#[derive(Clone, Copy, Debug)]
+pub struct DayNightCycle
+{
+    /// The number of ticks in a day.
+    pub ticks_per_day: u64,
+}
+
+impl DayNightCycle
+{
+    /// Ambient light colour at noon.
+    const DAY_AMBIENT: Vec3 = glam::const_vec3!([0.35, 0.35, 0.40]);
+
+    /// Ambient light colour at midnight.
+    const NIGHT_AMBIENT: Vec3 = glam::const_vec3!([0.03, 0.03, 0.08]);
+
+    /// Sun light colour at noon.
+    const NOON_SUN: Vec3 = glam::const_vec3!([0.65, 0.65, 0.60]);
+
+    /// Sun light colour at sunrise and sunset.
+    const HORIZON_SUN: Vec3 = glam::const_vec3!([0.70, 0.40, 0.20]);
+
+    /// The lighting at the given tick.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `ticks_per_day` is zero.
+    pub fn lighting(&self, tick: Tick) -> Lighting
+    {
+        let time_of_day = (tick.0 % self.ticks_per_day) as f32
+                        / self.ticks_per_day as f32;
+
+        // The sun moves along a circle from east to west,
+        // tilted slightly to the south so it is never straight overhead.
+        let angle = TAU * time_of_day;
+        let sun_position = vec3(angle.cos(), -0.3, angle.sin()).normalize();
+        let elevation = sun_position.z;
+
+        // Fade between day and night around sunrise and sunset.
+        let daylight = smoothstep(-0.1, 0.2, elevation);
+        let sun_color = Vec3::lerp(
+            Self::HORIZON_SUN,
+            Self::NOON_SUN,
+            elevation.clamp(0.0, 1.0),
+        );
+
+        Lighting{
+            sun_direction: -sun_position,
+            sun_color: sun_color * smoothstep(-0.05, 0.05, elevation),
+            ambient_color: Vec3::lerp(
+                Self::NIGHT_AMBIENT,
+                Self::DAY_AMBIENT,
+                daylight,
+            ),
+        }
+    }
+}
+
+/// Hermite interpolation between 0 and 1 as `x` goes from `a` to `b`.
+fn smoothstep(a: f32, b: f32, x: f32) -> f32
+{
+    let t = ((x - a) / (b - a)).clamp(0.0, 1.0);
+    t * t * (3.0 - 2.0 * t)
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    #[test]
+    fn noon_and_midnight()
+    {
+        let cycle = DayNightCycle{ticks_per_day: 1000};
+
+        let noon = cycle.lighting(Tick(250));
+        assert!(noon.sun_direction.z < -0.9);
+        assert!(noon.sun_color.min_element() > 0.5);
+
+        let midnight = cycle.lighting(Tick(1750));
+        assert_eq!(midnight.sun_color, Vec3::ZERO);
+        assert!(midnight.ambient_color.max_element() < 0.1);
+    }
+}
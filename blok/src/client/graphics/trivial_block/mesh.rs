@@ -0,0 +1,232 @@
+use crate::{
+    client::graphics::trivial_block::Face,
+    state::{Block, CHUNK_SIZE, Light, World},
+};
+use glam::IVec3;
+
+/// For each of the six faces of the cube,
+/// the direction in which the face is pointing.
+///
+/// This must agree with `corner_positions` in the vertex shader.
+const FACE_NORMALS: [[i32; 3]; 6] = [
+    [ 1,  0,  0], // East face.
+    [ 0,  1,  0], // North face.
+    [-1,  0,  0], // West face.
+    [ 0, -1,  0], // South face.
+    [ 0,  0,  1], // Top face.
+    [ 0,  0, -1], // Bottom face.
+];
+
+/// For each of the six faces of the cube,
+/// the direction of each corner from the center of the cube.
+///
+/// These are the corner positions from the vertex shader multiplied by two.
+/// The block at the center plus such a direction is the diagonal neighbour
+/// that touches the face only at that corner.
+const CORNER_DIRECTIONS: [[[i32; 3]; 4]; 6] = [
+    [[ 1,  1,  1], [ 1, -1,  1], [ 1, -1, -1], [ 1,  1, -1]], // East.
+    [[-1,  1,  1], [ 1,  1,  1], [ 1,  1, -1], [-1,  1, -1]], // North.
+    [[-1,  1,  1], [-1,  1, -1], [-1, -1, -1], [-1, -1,  1]], // West.
+    [[-1, -1,  1], [-1, -1, -1], [ 1, -1, -1], [ 1, -1,  1]], // South.
+    [[-1,  1,  1], [-1, -1,  1], [ 1, -1,  1], [ 1,  1,  1]], // Top.
+    [[-1,  1, -1], [ 1,  1, -1], [ 1, -1, -1], [-1, -1, -1]], // Bottom.
+];
+
+/// Blocks surrounding a chunk, as needed for meshing it.
+///
+/// Positions are relative to the chunk being meshed.
+/// Besides the blocks in the chunk itself,
+/// blocks directly bordering the chunk are queried as well,
+/// so coordinates range from -1 through 16 in each dimension.
+pub trait Voxels
+{
+    /// Whether there is a trivial block at the given position.
+    fn is_opaque(&self, position: IVec3) -> bool;
+
+    /// The light levels at the given position.
+    ///
+    /// This is only called for positions where there is no trivial block.
+    fn light(&self, position: IVec3) -> Light;
+
+    /// Texture atlas coordinates for a face of the block at the position.
+    ///
+    /// This is only called for positions where there is a trivial block.
+    fn texture(&self, position: IVec3, face: u8) -> (u16, u16);
+}
+
+/// Voxels of a chunk in a world.
+pub struct WorldVoxels<'a, T>
+{
+    /// The world that contains the chunk.
+    pub world: &'a World,
+
+    /// The position of the chunk to mesh.
+    pub chunk_position: IVec3,
+
+    /// Texture atlas coordinates for each face of each kind of block.
+    pub texture: T,
+}
+
+impl<'a, T> Voxels for WorldVoxels<'a, T>
+    where T: Fn(Block, u8) -> (u16, u16)
+{
+    fn is_opaque(&self, position: IVec3) -> bool
+    {
+        self.world.block(self.world_position(position)).is_opaque()
+    }
+
+    fn light(&self, position: IVec3) -> Light
+    {
+        self.world.light(self.world_position(position))
+    }
+
+    fn texture(&self, position: IVec3, face: u8) -> (u16, u16)
+    {
+        let block = self.world.block(self.world_position(position));
+        (self.texture)(block, face)
+    }
+}
+
+impl<'a, T> WorldVoxels<'a, T>
+{
+    fn world_position(&self, position: IVec3) -> IVec3
+    {
+        self.chunk_position * CHUNK_SIZE + position
+    }
+}
+
+/// Compute the faces to draw for a chunk.
+///
+/// Faces that are adjacent to other trivial blocks are omitted.
+/// Each face is given ambient occlusion values for its corners,
+/// based on the three blocks that touch each corner from the outside,
+/// and the light levels of the voxel that the face is facing.
+pub fn mesh<V>(voxels: &V) -> Vec<Face>
+    where V: Voxels + ?Sized
+{
+    let mut faces = Vec::new();
+    for x in 0 .. CHUNK_SIZE {
+        for y in 0 .. CHUNK_SIZE {
+            for z in 0 .. CHUNK_SIZE {
+                let position = IVec3::new(x, y, z);
+                if voxels.is_opaque(position) {
+                    mesh_block(voxels, position, &mut faces);
+                }
+            }
+        }
+    }
+    faces
+}
+
+/// Implementation detail of `mesh`.
+fn mesh_block<V>(voxels: &V, position: IVec3, faces: &mut Vec<Face>)
+    where V: Voxels + ?Sized
+{
+    for face in 0 .. 6 {
+        let normal = IVec3::from(FACE_NORMALS[face as usize]);
+        if voxels.is_opaque(position + normal) {
+            continue;
+        }
+        let ao = face_ambient_occlusion(voxels, position, face);
+        let light = voxels.light(position + normal);
+        let (u, v) = voxels.texture(position, face);
+        faces.push(Face::new(position, face, u, v, ao, light));
+    }
+}
+
+/// Compute the ambient occlusion values for the corners of a face.
+///
+/// See [`Face::ao`] for the meaning of the values.
+pub fn face_ambient_occlusion<V>(voxels: &V, position: IVec3, face: u8)
+    -> [u8; 4]
+    where V: Voxels + ?Sized
+{
+    let normal = IVec3::from(FACE_NORMALS[face as usize]);
+    let mut ao = [0; 4];
+    for (corner, &direction) in
+        CORNER_DIRECTIONS[face as usize].iter().enumerate()
+    {
+        // The corner direction agrees with the normal along the normal axis.
+        // Along the two other axes we find the blocks touching the corner.
+        let direction = IVec3::from(direction);
+        let tangent = direction - normal;
+        let (side1, side2) = split_tangent(tangent);
+        let side1 = voxels.is_opaque(position + normal + side1);
+        let side2 = voxels.is_opaque(position + normal + side2);
+        let corner_block = voxels.is_opaque(position + direction);
+        ao[corner] = corner_ambient_occlusion(side1, side2, corner_block);
+    }
+    ao
+}
+
+/// Split a vector with two non-zero components into two vectors
+/// with one non-zero component each.
+fn split_tangent(tangent: IVec3) -> (IVec3, IVec3)
+{
+    if tangent.x == 0 {
+        (IVec3::new(0, tangent.y, 0), IVec3::new(0, 0, tangent.z))
+    } else if tangent.y == 0 {
+        (IVec3::new(tangent.x, 0, 0), IVec3::new(0, 0, tangent.z))
+    } else {
+        (IVec3::new(tangent.x, 0, 0), IVec3::new(0, tangent.y, 0))
+    }
+}
+
+/// Ambient occlusion value for a corner given the blocks touching it.
+///
+/// When both side blocks are present they hide the corner block,
+/// and the corner is fully occluded regardless of the corner block.
+fn corner_ambient_occlusion(side1: bool, side2: bool, corner: bool) -> u8
+{
+    if side1 && side2 {
+        0
+    } else {
+        3 - (side1 as u8 + side2 as u8 + corner as u8)
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    use std::collections::HashSet;
+
+    struct Blocks(HashSet<IVec3>);
+
+    impl Voxels for Blocks
+    {
+        fn is_opaque(&self, position: IVec3) -> bool
+        {
+            self.0.contains(&position)
+        }
+
+        fn light(&self, _position: IVec3) -> Light
+        {
+            Light::SKY
+        }
+
+        fn texture(&self, _position: IVec3, _face: u8) -> (u16, u16)
+        {
+            (0, 0)
+        }
+    }
+
+    #[test]
+    fn step_occludes_top_face()
+    {
+        let blocks = Blocks(HashSet::from([
+            IVec3::new(1, 1, 0),
+            IVec3::new(2, 1, 1),
+        ]));
+
+        // The blocks touch only along an edge, so all faces are visible.
+        assert_eq!(mesh(&blocks).len(), 12);
+
+        // The step is east of the top face, so the eastern corners are darker.
+        assert_eq!(
+            face_ambient_occlusion(&blocks, IVec3::new(1, 1, 0), 4),
+            [3, 3, 2, 2],
+        );
+    }
+}
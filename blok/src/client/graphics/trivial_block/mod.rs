@@ -1,19 +1,44 @@
 //! Pipeline for rendering opaque unit cubes at integer coordinates.
 
+pub use self::mesh::*;
+
 use crate::{
     client::graphics::{
         GlBuffer,
+        GlContext,
         GlProgram,
         GlShader,
-        GlUniform,
+        GlVertexArray,
         generic::FragmentShader,
     },
+    gl_vertex,
+    state::{CHUNK_SIZE, Light},
     try_gl,
 };
 use anyhow::Result;
 use glam::{IVec2, IVec3, Mat4};
-use opengl::gl::{self, types::*};
-use std::{borrow::Borrow, mem::size_of};
+use opengl::gl;
+use std::borrow::Borrow;
+
+#[cfg(all(feature = "shader-hot-reload", debug_assertions))]
+use crate::client::graphics::hot_reload::ShaderReloader;
+
+mod mesh;
+
+/// Interface of the vertex shader, reflected by the build script.
+mod vertex_interface
+{
+    include!(
+        concat!(
+            env!("OUT_DIR"),
+            "/client/graphics/trivial_block/shader.vert.rs",
+        )
+    );
+}
+
+/// Path of the GLSL source of the vertex shader.
+pub const VERTEX_SHADER_PATH: &str =
+    "src/client/graphics/trivial_block/shader.vert";
 
 static VERTEX_SHADER_BINARY: &'static [u8] =
     include_bytes!(
@@ -51,13 +76,70 @@ pub struct Face
 
     /// V coordinate.
     pub v: u16,
+
+    /// Ambient occlusion of each corner, two bits per corner.
+    ///
+    /// The first corner is in the 2 LSbs and the last corner in the 2 MSbs.
+    /// A value of 0 means fully occluded and 3 means not occluded at all.
+    /// The vertex shader darkens the corners accordingly,
+    /// and splits the face along the diagonal between the brightest corners.
+    pub ao: u8,
+
+    /// Light levels of the voxel in front of the face.
+    ///
+    /// See [`Light::as_raw`] for the layout.
+    pub light: u8,
+}
+
+gl_vertex! {
+    Face {
+        xy: vertex_interface::inputs::FACE_XY,
+        zf: vertex_interface::inputs::FACE_ZF,
+        u: vertex_interface::inputs::FACE_U,
+        v: vertex_interface::inputs::FACE_V,
+        ao: vertex_interface::inputs::FACE_AO,
+        light: vertex_interface::inputs::FACE_LIGHT,
+    }
+}
+
+impl Face
+{
+    /// Pack the attributes of a face.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the position is outside the chunk,
+    /// if the face selector is not in the range 0 through 5,
+    /// or if any ambient occlusion value is greater than 3.
+    pub fn new(
+        position: IVec3,
+        face: u8,
+        u: u16,
+        v: u16,
+        ao: [u8; 4],
+        light: Light,
+    ) -> Self
+    {
+        let in_chunk = |c| (0 .. CHUNK_SIZE).contains(&c);
+        assert!(position.to_array().into_iter().all(in_chunk));
+        assert!(face < 6);
+        assert!(ao.iter().all(|&a| a <= 3));
+        Self{
+            xy: (position.x << 4 | position.y) as u8,
+            zf: (position.z << 4) as u8 | face,
+            u,
+            v,
+            ao: ao[0] | ao[1] << 2 | ao[2] << 4 | ao[3] << 6,
+            light: light.as_raw(),
+        }
+    }
 }
 
 /// Set of trivial block faces that appear in a chunk.
-pub struct FaceSet
+pub struct FaceSet<'c>
 {
     /// The faces to draw for this chunk.
-    pub faces: GlBuffer<Face>,
+    pub faces: GlBuffer<'c, Face>,
 
     /// An increment of 1 in either dimension corresponds
     /// to the adjacent chunk in that dimension.
@@ -70,79 +152,102 @@ pub struct FaceSet
 /// The vertex shader will generate the four vertices of each face,
 /// so the buffers passed to this pipeline store only one entry for each face.
 /// Faces that are adjacent to other trivial blocks do not have to be included.
-pub struct Pipeline
+pub struct Pipeline<'c>
 {
-    program: GlProgram,
-    vertex_array: GLuint,
+    context: &'c GlContext,
+    program: GlProgram<'c>,
+    vertex_array: GlVertexArray<'c>,
 }
 
-impl Drop for Pipeline
+impl<'c> Pipeline<'c>
 {
-    fn drop(&mut self)
+    /// Compile the pipeline.
+    pub fn new(context: &'c GlContext, fragment_shader: &FragmentShader)
+        -> Result<Self>
     {
-        // SAFETY: Provided by caller of `new`.
-        unsafe {
-            gl::DeleteVertexArrays(1, &self.vertex_array);
-        }
+        Self::new_impl(context, Some(fragment_shader))
     }
-}
 
-impl Pipeline
-{
-    /// Compile the pipeline.
-    #[doc = crate::doc_safety_opengl!()]
-    pub unsafe fn new(fragment_shader: &FragmentShader) -> Result<Self>
+    /// Compile a variant of the pipeline that only writes depth.
+    ///
+    /// This is used for rendering shadow maps.
+    pub fn new_depth_only(context: &'c GlContext) -> Result<Self>
+    {
+        Self::new_impl(context, None)
+    }
+
+    fn new_impl(
+        context: &'c GlContext,
+        fragment_shader: Option<&FragmentShader>,
+    ) -> Result<Self>
+    {
+        let program = Self::make_program(
+            /* context              */ context,
+            /* vertex_shader_binary */ VERTEX_SHADER_BINARY,
+            /* fragment_shader      */ fragment_shader,
+        )?;
+        let vertex_array = Self::make_vertex_array(context)?;
+        Ok(Self{context, program, vertex_array})
+    }
+
+    /// Rebuild the program from the most recent shader binaries.
+    ///
+    /// The fragment shader must be given if and only if
+    /// the pipeline was not created with `new_depth_only`.
+    /// If this fails, the pipeline keeps using its previous program.
+    #[cfg(all(feature = "shader-hot-reload", debug_assertions))]
+    pub fn reload(
+        &mut self,
+        reloader: &ShaderReloader,
+        fragment_shader: Option<&FragmentShader>,
+    ) -> Result<()>
     {
-        let program = Self::make_program(fragment_shader)?;
-        let mut this = Self{program, vertex_array: 0};
-        this.make_vertex_array()?;
-        Ok(this)
+        self.program = Self::make_program(
+            /* context              */ self.context,
+            /* vertex_shader_binary */
+                reloader.binary(VERTEX_SHADER_PATH, VERTEX_SHADER_BINARY),
+            /* fragment_shader      */ fragment_shader,
+        )?;
+        Ok(())
     }
 
-    unsafe fn make_program(fragment_shader: &FragmentShader)
-        -> Result<GlProgram>
+    fn make_program(
+        context: &'c GlContext,
+        vertex_shader_binary: &[u8],
+        fragment_shader: Option<&FragmentShader>,
+    ) -> Result<GlProgram<'c>>
     {
         let vertex_shader = GlShader::new(
+            /* context          */ context,
             /* shader_type      */ gl::VERTEX_SHADER,
-            /* shader_binary    */ VERTEX_SHADER_BINARY,
+            /* source_path      */ VERTEX_SHADER_PATH,
+            /* shader_binary    */ vertex_shader_binary,
             /* constant_indices */ &[],
             /* constant_values  */ &[],
         )?;
-        GlProgram::new(&[&vertex_shader, fragment_shader.as_shader()])
+        let (program, label) = match fragment_shader {
+            Some(fs) => (GlProgram::new(context,
+                                        &[&vertex_shader, fs.as_shader()])?,
+                         "trivial block pipeline"),
+            None     => (GlProgram::new(context, &[&vertex_shader])?,
+                         "trivial block depth-only pipeline"),
+        };
+        program.label(label)?;
+        Ok(program)
     }
 
-    unsafe fn make_vertex_array(&mut self) -> Result<()>
+    fn make_vertex_array(context: &'c GlContext) -> Result<GlVertexArray<'c>>
     {
-        // Create vertex array.
-        try_gl! { gl::CreateVertexArrays(1, &mut self.vertex_array); }
-
-        // Convenient alias.
-        let vao = self.vertex_array;
-
-        // Enable vertex attributes.
-        try_gl! { gl::EnableVertexArrayAttrib(vao, 0); }
-        try_gl! { gl::EnableVertexArrayAttrib(vao, 1); }
-        try_gl! { gl::EnableVertexArrayAttrib(vao, 2); }
-        try_gl! { gl::EnableVertexArrayAttrib(vao, 3); }
-
-        // Associate the attributes with the sole binding.
-        try_gl! { gl::VertexArrayAttribBinding(vao, 0, 0); }
-        try_gl! { gl::VertexArrayAttribBinding(vao, 1, 0); }
-        try_gl! { gl::VertexArrayAttribBinding(vao, 2, 0); }
-        try_gl! { gl::VertexArrayAttribBinding(vao, 3, 0); }
-
-        // Configure the formats of the attributes.
-        try_gl! { gl::VertexArrayAttribIFormat(vao, 0, 1, gl::UNSIGNED_BYTE,  0); }
-        try_gl! { gl::VertexArrayAttribIFormat(vao, 1, 1, gl::UNSIGNED_BYTE,  1); }
-        try_gl! { gl::VertexArrayAttribIFormat(vao, 2, 1, gl::UNSIGNED_SHORT, 2); }
-        try_gl! { gl::VertexArrayAttribIFormat(vao, 3, 1, gl::UNSIGNED_SHORT, 4); }
+        let mut vertex_array = GlVertexArray::new(context)?;
+        vertex_array.label("trivial block vertex array")?;
+        vertex_array.vertex_layout::<Face>(0)?;
 
         // There is only one buffer entry for each face,
         // and faces consist of four vertices (its corners).
         // So drawing must advance only once every four vertices.
-        try_gl! { gl::VertexArrayBindingDivisor(vao, 0, 4); }
+        vertex_array.binding_divisor(0, 4)?;
 
-        Ok(())
+        Ok(vertex_array)
     }
 
     /// Render a collection of sets of trivial block faces.
@@ -155,16 +260,14 @@ impl Pipeline
     /// <dt><code>vp_matrix</code></dt>
     /// <dd>The view–projection matrix to apply to each face.</dd>
     /// </dl>
-    ///
-    #[doc = crate::doc_safety_opengl!()]
-    pub unsafe fn render<'a, I, M>(
+    pub fn render<'a, I, M>(
         &self,
         atlas_size: &IVec2,
         vp_matrix: &Mat4,
         models: I,
     ) -> Result<()>
         where I: IntoIterator<Item=M>
-            , M: Borrow<FaceSet>
+            , M: Borrow<FaceSet<'a>>
     {
         self.pre_render(atlas_size)?;
         for model in models {
@@ -175,26 +278,29 @@ impl Pipeline
     }
 
     /// Implementation detail of `render`.
-    unsafe fn pre_render(&self, atlas_size: &IVec2) -> Result<()>
+    fn pre_render(&self, atlas_size: &IVec2) -> Result<()>
     {
         // Select program and vertex array.
-        try_gl! { gl::UseProgram(self.program.as_raw()); }
-        try_gl! { gl::BindVertexArray(self.vertex_array); }
+        self.vertex_array.bind()?;
+        // SAFETY: The context is current.
+        unsafe {
+            try_gl! { gl::UseProgram(self.program.as_raw()); }
 
-        // Configure face culling.
-        try_gl! { gl::Enable(gl::CULL_FACE); }
-        try_gl! { gl::CullFace(gl::BACK); }
-        try_gl! { gl::FrontFace(gl::CCW); }
+            // Configure face culling.
+            try_gl! { gl::Enable(gl::CULL_FACE); }
+            try_gl! { gl::CullFace(gl::BACK); }
+            try_gl! { gl::FrontFace(gl::CCW); }
+        }
 
         // Set uniforms common to all chunks.
-        atlas_size.as_vec2().gl_uniform(1)?;
+        let atlas_size = atlas_size.as_vec2();
+        vertex_interface::uniforms::ATLAS_SIZE.set(self.context, &atlas_size)?;
 
         Ok(())
     }
 
     /// Implementation detail of `render`.
-    unsafe fn render_one(&self, vp_matrix: &Mat4, model: &FaceSet)
-        -> Result<()>
+    fn render_one(&self, vp_matrix: &Mat4, model: &FaceSet) -> Result<()>
     {
         // Compute the MVP matrix for this chunk.
         let m_vector = (16 * model.chunk_position).as_vec3();
@@ -202,33 +308,33 @@ impl Pipeline
         let mvp_matrix = *vp_matrix * m_matrix;
 
         // Set uniforms specific to this chunk.
-        mvp_matrix.gl_uniform(2)?;
+        use vertex_interface::uniforms::*;
+        MVP_MATRIX.set(self.context, &mvp_matrix)?;
+        M_MATRIX.set(self.context, &m_matrix)?;
 
         // Select the buffer to read faces from.
-        try_gl! {
-            gl::BindVertexBuffer(
-                /* bindingindex */ 0,
-                /* buffer       */ model.faces.as_raw(),
-                /* offset       */ 0,
-                /* stride       */ size_of::<Face>() as _,
-            );
-        }
+        self.vertex_array.vertex_buffer(0, &model.faces)?;
+
+        // SAFETY: The context is current,
+        //         and the count matches the bound vertex buffer.
+        unsafe {
+            // Draw all the faces in a single draw call.
+            try_gl! {
+                gl::DrawArraysInstanced(
+                    /* mode  */ gl::TRIANGLE_FAN,
+                    /* first */ 0,
+
+                    // Every face consists of four vertices.
+                    /* count */ 4,
+
+                    // According to the glDrawArraysInstanced manual entry,
+                    // attributes with divisor N advance once every N instances.
+                    // We want to advance once for each face, and our divisor is 4,
+                    // so we must multiply the face count by 4 here.
+                    /* primcount */ (4 * model.faces.len()) as _,
+                );
+            }
 
-        // Draw all the faces in a single draw call.
-        try_gl! {
-            gl::DrawArraysInstanced(
-                /* mode  */ gl::TRIANGLE_FAN,
-                /* first */ 0,
-
-                // Every face consists of four vertices.
-                /* count */ 4,
-
-                // According to the glDrawArraysInstanced manual entry,
-                // attributes with divisor N advance once every N instances.
-                // We want to advance once for each face, and our divisor is 4,
-                // so we must multiply the face count by 4 here.
-                /* primcount */ (4 * model.faces.len()) as _,
-            );
         }
 
         Ok(())
@@ -3,16 +3,31 @@
 /// See [`TrivialBlockPipeline::render`].
 layout(location = 1) uniform vec2 atlas_size;
 layout(location = 2) uniform mat4 mvp_matrix;
+layout(location = 3) uniform mat4 m_matrix;
 
 /// See [`TrivialBlockFace`].
 layout(location = 0) in uint face_xy;
 layout(location = 1) in uint face_zf;
 layout(location = 2) in uint face_u;
 layout(location = 3) in uint face_v;
+layout(location = 4) in uint face_ao;
+layout(location = 5) in uint face_light;
 
 /// Normalized U and V coordinates in the texture atlas.
 layout(location = 0) out vec2 fragment_uv;
 
+/// Brightness of the corner due to ambient occlusion.
+layout(location = 1) out float fragment_ao;
+
+/// Sky and block light levels, normalized into the interval [0, 1].
+layout(location = 2) out vec2 fragment_light;
+
+/// Direction in which the face is pointing.
+layout(location = 3) out vec3 fragment_normal;
+
+/// Position of the corner in world space.
+layout(location = 4) out vec3 fragment_position;
+
 /// For each of the six faces of the cube,
 /// this specifies the coordinate of each corner
 /// relative to the center of the face.
@@ -49,6 +64,17 @@ const vec3 corner_positions[6 * 4] = {
     vec3(-0.5, -0.5, -0.5),
 };
 
+/// For each of the six faces of the cube,
+/// this specifies the direction in which the face is pointing.
+const vec3 face_normals[6] = {
+    vec3(+1.0,  0.0,  0.0), // East face.
+    vec3( 0.0, +1.0,  0.0), // North face.
+    vec3(-1.0,  0.0,  0.0), // West face.
+    vec3( 0.0, -1.0,  0.0), // South face.
+    vec3( 0.0,  0.0, +1.0), // Top face.
+    vec3( 0.0,  0.0, -1.0), // Bottom face.
+};
+
 /// This specifies the offset to be applied
 /// to the U and V coordinates for each corner.
 const vec2 uv_per_corner[4] = {
@@ -58,6 +84,14 @@ const vec2 uv_per_corner[4] = {
     vec2(1.0, 0.0),
 };
 
+/// Brightness for each of the four ambient occlusion values.
+const float ao_brightness[4] = {
+    0.4,
+    0.6,
+    0.8,
+    1.0,
+};
+
 void main()
 {
     // Some attributes are packed into four bits
@@ -68,15 +102,39 @@ void main()
     int  face_z = int(face_zf >> 4);
     uint face_f = face_zf & 0xFu;
 
+    // The ambient occlusion values are packed two bits per corner.
+    uint ao[4] = {
+        (face_ao >> 0) & 3u,
+        (face_ao >> 2) & 3u,
+        (face_ao >> 4) & 3u,
+        (face_ao >> 6) & 3u,
+    };
+
+    // The face is drawn as a fan of two triangles around the first corner,
+    // so the split runs along the diagonal between the first and third corner.
+    // Interpolation along that diagonal looks wrong when the other diagonal
+    // is brighter, so in that case we start the fan at the second corner.
+    uint flip = ao[0] + ao[2] < ao[1] + ao[3] ? 1u : 0u;
+    uint corner_index = (uint(gl_VertexID) + flip) % 4u;
+
     // The corner position depends on the face position
     // and which of the four corners we are processing.
     vec3 center = vec3(face_x, face_y, face_z);
-    vec3 corner = corner_positions[4 * face_f + gl_VertexID];
+    vec3 corner = corner_positions[4 * face_f + corner_index];
     gl_Position = mvp_matrix * vec4(center + corner, 1.0);
+    fragment_position = (m_matrix * vec4(center + corner, 1.0)).xyz;
 
     // The texture coordinates also depend on which corner we are processing.
     // Furthermore, they need to be normalized into the interval [0, 1]
     // so we divide them by the number of textures in the texture atlas.
     vec2 face_uv = vec2(face_u, face_v);
-    fragment_uv = (face_uv + uv_per_corner[gl_VertexID]) / atlas_size;
+    fragment_uv = (face_uv + uv_per_corner[corner_index]) / atlas_size;
+
+    fragment_ao = ao_brightness[ao[corner_index]];
+
+    // The light levels are packed four bits each.
+    fragment_light = vec2(face_light >> 4, face_light & 0xFu) / 15.0;
+
+    // Chunks are only ever translated, so the normal needs no transformation.
+    fragment_normal = face_normals[face_f];
 }
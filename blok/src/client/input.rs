@@ -1,25 +1,11 @@
 //! Dealing with player input.
 
+pub use crate::state::AbstractInput;
+
 use crate::state::Tick;
 
 use std::collections::VecDeque;
 
-/// High-level description of the input.
-///
-/// This describes the input in terms of gameplay actions
-/// rather than signals from human interface devices.
-/// Generating abstract input requires knowledge about
-/// the control mapping that the player configured and
-/// the user interface elements that are being displayed.
-#[allow(missing_docs)]
-pub struct AbstractInput
-{
-    pub move_forward: bool,
-    pub move_backward: bool,
-    pub strafe_left: bool,
-    pub strafe_right: bool,
-}
-
 /// Buffer of recent inputs for server reconciliation.
 pub struct ReconciliationBuffer<T>
 {
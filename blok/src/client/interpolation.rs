@@ -0,0 +1,349 @@
+//! Smooth rendering of remote entities between server snapshots.
+//!
+//! The server sends snapshots of its entities at irregular intervals,
+//! as packets are delayed by varying amounts or lost.
+//! The client buffers the snapshots and renders entities
+//! a fixed delay behind the latest snapshot,
+//! so that there is usually a snapshot on either side of the render time
+//! and entities can be interpolated between them.
+//!
+//! Times are measured in ticks, as fractional numbers.
+//! Local time is the time of the client,
+//! typically its tick counter plus the interpolation alpha.
+//! Server time is the tick of the snapshot the server would send now.
+
+use crate::state::{Entities, Tick, Transform, slerp};
+use std::collections::VecDeque;
+
+/// Weight of a new sample in the running estimates,
+/// as in the interarrival jitter of RFC 3550.
+const SMOOTHING: f64 = 1.0 / 16.0;
+
+/// Parameters of snapshot interpolation.
+#[derive(Clone, Copy, Debug)]
+pub struct InterpolationConfig
+{
+    /// How far behind the estimated server time entities are rendered,
+    /// in ticks.
+    ///
+    /// This should exceed the interval between snapshots plus the jitter,
+    /// or the client will often have to extrapolate.
+    pub delay: f64,
+
+    /// How far past the latest snapshot entities may be extrapolated,
+    /// in ticks.
+    ///
+    /// Beyond this, entities freeze until the next snapshot arrives,
+    /// rather than flying off in a straight line.
+    pub max_extrapolation: f64,
+
+    /// Maximum number of buffered snapshots.
+    ///
+    /// When the buffer is full, the oldest snapshot is discarded.
+    pub capacity: usize,
+}
+
+impl Default for InterpolationConfig
+{
+    fn default() -> Self
+    {
+        Self{delay: 6.0, max_extrapolation: 4.0, capacity: 32}
+    }
+}
+
+/// Statistics about the arrival of snapshots.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct JitterStats
+{
+    /// Number of snapshots that were buffered.
+    pub received: u64,
+
+    /// Number of snapshots that were discarded
+    /// because they were older than a buffered snapshot.
+    pub out_of_order: u64,
+
+    /// Running estimate of the variation in transit time, in ticks.
+    ///
+    /// This is the interarrival jitter of RFC 3550.
+    pub jitter: f64,
+
+    /// Largest variation in transit time between two snapshots, in ticks.
+    pub max_jitter: f64,
+
+    /// Number of samples that extrapolated past the latest snapshot.
+    pub extrapolated: u64,
+
+    /// Number of samples that exceeded the extrapolation limit.
+    pub starved: u64,
+}
+
+/// Buffer of snapshots of remote entities.
+pub struct SnapshotBuffer
+{
+    config: InterpolationConfig,
+
+    // INVARIANT: Ticks are strictly increasing.
+    snapshots: VecDeque<(Tick, Entities)>,
+
+    /// Local time at which the latest snapshot arrived.
+    last_arrival: Option<f64>,
+
+    /// Running estimate of server time minus local time.
+    clock_offset: Option<f64>,
+
+    /// Latest render time, which must not go backwards.
+    last_render_time: f64,
+
+    stats: JitterStats,
+}
+
+impl SnapshotBuffer
+{
+    /// Create a buffer with no snapshots.
+    pub fn new(config: InterpolationConfig) -> Self
+    {
+        Self{
+            config,
+            snapshots: VecDeque::new(),
+            last_arrival: None,
+            clock_offset: None,
+            last_render_time: f64::NEG_INFINITY,
+            stats: JitterStats::default(),
+        }
+    }
+
+    /// Statistics about the snapshots received so far.
+    pub fn stats(&self) -> &JitterStats
+    {
+        &self.stats
+    }
+
+    /// The tick of the latest snapshot, if any.
+    pub fn latest_tick(&self) -> Option<Tick>
+    {
+        self.snapshots.back().map(|&(tick, _)| tick)
+    }
+
+    /// Buffer a snapshot that arrived at the given local time.
+    ///
+    /// Snapshots older than the latest snapshot are discarded,
+    /// as rendering has moved past them already.
+    pub fn push(&mut self, local_time: f64, tick: Tick, entities: Entities)
+    {
+        if let Some(latest) = self.latest_tick() {
+            if tick <= latest {
+                self.stats.out_of_order += 1;
+                return;
+            }
+
+            // Compare the interval between arrivals
+            // with the interval between the snapshots.
+            let arrival_interval = local_time - self.last_arrival.unwrap();
+            let tick_interval = (tick.0 - latest.0) as f64;
+            let variation = (arrival_interval - tick_interval).abs();
+            self.stats.jitter += (variation - self.stats.jitter) * SMOOTHING;
+            self.stats.max_jitter = self.stats.max_jitter.max(variation);
+        }
+
+        let offset = tick.0 as f64 - local_time;
+        self.clock_offset = Some(match self.clock_offset {
+            Some(estimate) => estimate + (offset - estimate) * SMOOTHING,
+            None => offset,
+        });
+
+        self.last_arrival = Some(local_time);
+        self.stats.received += 1;
+        self.snapshots.push_back((tick, entities));
+        while self.snapshots.len() > self.config.capacity {
+            self.snapshots.pop_front();
+        }
+    }
+
+    /// The server time at which entities are rendered
+    /// at the given local time.
+    ///
+    /// Returns [`None`] if no snapshot has arrived yet.
+    pub fn render_time(&self, local_time: f64) -> Option<f64>
+    {
+        let offset = self.clock_offset?;
+        let render_time = local_time + offset - self.config.delay;
+        Some(render_time.max(self.last_render_time))
+    }
+
+    /// The entities as they are rendered at the given local time.
+    ///
+    /// The components of the entities are those of the latest snapshot
+    /// before the render time, with transforms interpolated towards
+    /// the next snapshot, or extrapolated if there is none.
+    /// Snapshots that are no longer needed are discarded.
+    ///
+    /// Returns [`None`] if no snapshot has arrived yet.
+    pub fn sample(&mut self, local_time: f64) -> Option<Entities>
+    {
+        let render_time = self.render_time(local_time)?;
+        self.last_render_time = render_time;
+
+        // Discard snapshots before the one preceding the render time,
+        // but keep two for extrapolating.
+        while self.snapshots.len() > 2
+            && self.snapshots[1].0.0 as f64 <= render_time
+        {
+            self.snapshots.pop_front();
+        }
+
+        let &(latest_tick, _) = self.snapshots.back()?;
+        if self.snapshots.len() == 1 || render_time >= latest_tick.0 as f64 {
+            return Some(self.extrapolate(render_time));
+        }
+
+        let (from_tick, from) = &self.snapshots[0];
+        let (to_tick, to) = &self.snapshots[1];
+        let interval = (to_tick.0 - from_tick.0) as f64;
+        let t = (render_time - from_tick.0 as f64) / interval;
+        Some(interpolate(from, to, t.max(0.0)))
+    }
+
+    /// Implementation detail of `sample`, for when the render time
+    /// is past the latest snapshot.
+    fn extrapolate(&mut self, render_time: f64) -> Entities
+    {
+        let (latest_tick, latest) = self.snapshots.back().unwrap();
+        let latest_time = latest_tick.0 as f64;
+        let mut ahead = render_time - latest_time;
+        if ahead <= 0.0 {
+            return latest.clone();
+        }
+
+        self.stats.extrapolated += 1;
+        if ahead > self.config.max_extrapolation {
+            self.stats.starved += 1;
+            ahead = self.config.max_extrapolation;
+        }
+
+        // Extrapolate the motion between the last two snapshots.
+        let len = self.snapshots.len();
+        match len.checked_sub(2).map(|i| &self.snapshots[i]) {
+            Some((previous_tick, previous)) => {
+                let interval = (latest_tick.0 - previous_tick.0) as f64;
+                interpolate(previous, latest, 1.0 + ahead / interval)
+            },
+            None => latest.clone(),
+        }
+    }
+}
+
+/// Interpolate the transforms of entities between two snapshots.
+///
+/// Other components are taken from the first snapshot when interpolating,
+/// and from the second snapshot when extrapolating,
+/// which a parameter greater than one does.
+/// Entities that are only in one of the snapshots are not moved.
+fn interpolate(from: &Entities, to: &Entities, t: f64) -> Entities
+{
+    let mut entities = if t < 1.0 { from.clone() } else { to.clone() };
+    let t = t as f32;
+    let transforms = from.iter::<Transform>().filter_map(|(id, &a)| {
+        to.get::<Transform>(id).map(|&b| (id, a, b))
+    });
+    for (id, a, b) in transforms {
+        if let Some(transform) = entities.get_mut::<Transform>(id) {
+            *transform = Transform{
+                position: a.position.lerp(b.position, t),
+                orientation: slerp(a.orientation, b.orientation, t),
+            };
+        }
+    }
+    entities
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+    use glam::{Quat, Vec3, vec3};
+
+    fn snapshot(x: f32) -> Entities
+    {
+        let mut entities = Entities::new();
+        let id = entities.spawn();
+        entities.insert(id, Transform{
+            position: vec3(x, 0.0, 0.0),
+            orientation: Quat::IDENTITY,
+        });
+        entities
+    }
+
+    fn x(entities: &Entities) -> f32
+    {
+        let (_, transform) = entities.iter::<Transform>().next().unwrap();
+        transform.position.x
+    }
+
+    fn buffer() -> SnapshotBuffer
+    {
+        SnapshotBuffer::new(InterpolationConfig{
+            delay: 2.0,
+            max_extrapolation: 4.0,
+            capacity: 8,
+        })
+    }
+
+    #[test]
+    fn interpolates_between_snapshots()
+    {
+        let mut buffer = buffer();
+        assert!(buffer.sample(0.0).is_none());
+        buffer.push(0.0, Tick(0), snapshot(0.0));
+        buffer.push(2.0, Tick(2), snapshot(4.0));
+        assert_eq!(x(&buffer.sample(3.0).unwrap()), 2.0);
+        assert_eq!(x(&buffer.sample(3.5).unwrap()), 3.0);
+        assert_eq!(buffer.stats().extrapolated, 0);
+    }
+
+    #[test]
+    fn extrapolates_within_limit()
+    {
+        let mut buffer = buffer();
+        buffer.push(0.0, Tick(0), snapshot(0.0));
+        buffer.push(2.0, Tick(2), snapshot(4.0));
+
+        // One tick past the latest snapshot.
+        assert_eq!(x(&buffer.sample(5.0).unwrap()), 6.0);
+        assert_eq!(buffer.stats().extrapolated, 1);
+
+        // Far past the latest snapshot, motion stops at the limit.
+        assert_eq!(x(&buffer.sample(10.0).unwrap()), 12.0);
+        assert_eq!(buffer.stats().starved, 1);
+    }
+
+    #[test]
+    fn rotations_take_shortest_arc()
+    {
+        let a = snapshot(0.0);
+        let mut b = snapshot(0.0);
+        let id = b.ids().next().unwrap();
+        b.get_mut::<Transform>(id).unwrap().orientation = -Quat::IDENTITY;
+        let halfway = interpolate(&a, &b, 0.5);
+        let orientation = halfway.get::<Transform>(id).unwrap().orientation;
+        assert!(orientation.mul_vec3(Vec3::X).abs_diff_eq(Vec3::X, 1e-6));
+    }
+
+    #[test]
+    fn jitter_statistics()
+    {
+        let mut buffer = buffer();
+        buffer.push(0.0, Tick(0), snapshot(0.0));
+        buffer.push(1.5, Tick(1), snapshot(0.0));
+        buffer.push(2.0, Tick(2), snapshot(0.0));
+        buffer.push(3.0, Tick(3), snapshot(0.0));
+
+        // A late duplicate of an earlier snapshot.
+        buffer.push(3.5, Tick(1), snapshot(0.0));
+
+        let stats = buffer.stats();
+        assert_eq!(stats.received, 4);
+        assert_eq!(stats.out_of_order, 1);
+        assert_eq!(stats.max_jitter, 0.5);
+        assert!(stats.jitter > 0.0 && stats.jitter < 0.5);
+    }
+}
@@ -2,3 +2,5 @@
 
 pub mod graphics;
 pub mod input;
+pub mod interpolation;
+pub mod session;
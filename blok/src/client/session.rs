@@ -0,0 +1,267 @@
+//! Connection of the client to a server.
+//!
+//! The client does not simulate the game itself;
+//! it sends the input of the player to the server,
+//! and renders the world and the entities that the server sends back.
+//!
+//! The exception is the player that the client controls,
+//! which would otherwise respond to input only after a round trip.
+//! The client predicts the position of its player by applying its input
+//! to the position in the latest snapshot, starting from the first input
+//! that the server had not applied yet, which the snapshot tells.
+//! Inputs that the server applied are then no longer needed,
+//! so they are kept in a [`ReconciliationBuffer`].
+
+use crate::{
+    client::{
+        input::ReconciliationBuffer,
+        interpolation::{InterpolationConfig, JitterStats, SnapshotBuffer},
+    },
+    net::{
+        ClientMessage,
+        ServerMessage,
+        SnapshotDecoder,
+        receive_message,
+        send_message,
+        transport::{Client, Socket},
+    },
+    state::{
+        AbstractInput,
+        Entities,
+        EntityId,
+        TICK_DURATION,
+        Tick,
+        Transform,
+        World,
+        move_player,
+    },
+};
+use anyhow::Result;
+use glam::IVec3;
+use std::{collections::VecDeque, net::SocketAddr, time::Instant};
+
+/// Number of ticks of input repeated in each input message.
+///
+/// Input is sent unreliably, so this many consecutive messages
+/// must be lost for the server to miss the input of a tick.
+const INPUT_REDUNDANCY: usize = 8;
+
+/// The client side of a game on a server.
+pub struct Session<S>
+{
+    client: Client<S>,
+    started: Instant,
+    decoder: SnapshotDecoder,
+    snapshots: SnapshotBuffer,
+    player: Option<EntityId>,
+    world: World,
+
+    // INVARIANT: Ticks are strictly increasing.
+    recent_inputs: VecDeque<(Tick, AbstractInput)>,
+
+    /// Inputs that the server had not applied as of the latest snapshot.
+    unapplied_inputs: ReconciliationBuffer<AbstractInput>,
+
+    /// Tick of the latest snapshot, which prediction starts from.
+    reconciled: Option<Tick>,
+
+    /// Predicted transform of the player.
+    predicted: Option<Transform>,
+}
+
+impl<S> Session<S>
+    where S: Socket
+{
+    /// Start connecting to a server.
+    pub fn connect(
+        socket: S,
+        server: SocketAddr,
+        now: Instant,
+        interpolation: InterpolationConfig,
+    ) -> Self
+    {
+        Self{
+            client: Client::connect(socket, server, now),
+            started: now,
+            decoder: SnapshotDecoder::new(),
+            snapshots: SnapshotBuffer::new(interpolation),
+            player: None,
+            world: World::new(),
+            recent_inputs: VecDeque::new(),
+            unapplied_inputs: ReconciliationBuffer::new(),
+            reconciled: None,
+            predicted: None,
+        }
+    }
+
+    /// Whether the client is connected to the server.
+    pub fn is_connected(&self) -> bool
+    {
+        self.client.is_connected()
+    }
+
+    /// The player entity that the client controls,
+    /// once the server welcomed the client.
+    pub fn player(&self) -> Option<EntityId>
+    {
+        self.player
+    }
+
+    /// The chunks received from the server.
+    pub fn world(&self) -> &World
+    {
+        &self.world
+    }
+
+    /// The time since connecting, in ticks.
+    ///
+    /// This is the local time for [`SnapshotBuffer`].
+    pub fn local_time(&self, now: Instant) -> f64
+    {
+        let elapsed = now.saturating_duration_since(self.started);
+        elapsed.as_secs_f64() / TICK_DURATION.as_secs_f64()
+    }
+
+    /// Exchange messages with the server.
+    ///
+    /// This must be called regularly, typically once per tick.
+    /// Returns the positions of the chunks that arrived.
+    /// This fails if the connection failed or was closed,
+    /// or if the server sent a malformed message.
+    pub fn update(&mut self, now: Instant) -> Result<Vec<IVec3>>
+    {
+        self.client.update(now)?;
+
+        let local_time = self.local_time(now);
+        let mut arrived = Vec::new();
+        let mut latest: Option<(Tick, _, _)> = None;
+        if let Some(connection) = self.client.connection() {
+            while let Some(message) = receive_message(connection)? {
+                match message {
+                    ServerMessage::Welcome{player} =>
+                        self.player = Some(player),
+                    ServerMessage::Chunk{position, chunk} => {
+                        self.world.insert_chunk(position, chunk);
+                        arrived.push(position);
+                    },
+                    ServerMessage::Snapshot{last_input, data} => {
+                        // Snapshots whose baseline was discarded are lost,
+                        // like any other unreliable message.
+                        let snapshot = self.decoder.decode(&data);
+                        let (tick, entities) = match snapshot {
+                            Ok(snapshot) => snapshot,
+                            Err(err) => {
+                                log::debug!("{:#}", err);
+                                continue;
+                            },
+                        };
+                        let acknowledge = ClientMessage::Acknowledge(tick);
+                        send_message(connection, &acknowledge)?;
+
+                        let transform = self.player
+                            .and_then(|id| entities.get::<Transform>(id))
+                            .copied();
+                        if !matches!(latest, Some((t, ..)) if t > tick) {
+                            latest = Some((tick, last_input, transform));
+                        }
+                        self.snapshots.push(local_time, tick, entities);
+                    },
+                }
+            }
+        }
+
+        if let Some((tick, last_input, transform)) = latest {
+            self.reconcile(tick, last_input, transform);
+        }
+
+        // Send the acknowledgements now rather than at the next update.
+        self.client.update(now)?;
+        Ok(arrived)
+    }
+
+    /// Predict the player from a snapshot, unless it is outdated.
+    fn reconcile(
+        &mut self,
+        tick: Tick,
+        last_input: Option<Tick>,
+        transform: Option<Transform>,
+    )
+    {
+        if matches!(self.reconciled, Some(reconciled) if tick <= reconciled) {
+            return;
+        }
+        self.reconciled = Some(tick);
+
+        if let Some(last_input) = last_input {
+            self.unapplied_inputs.drain(Tick(last_input.0 + 1));
+        }
+        self.predicted = transform.map(|mut transform| {
+            for (_, input) in self.unapplied_inputs.iter() {
+                move_player(&mut transform, input);
+            }
+            transform
+        });
+    }
+
+    /// The predicted transform of the player.
+    ///
+    /// This includes the input that the server has not applied yet,
+    /// so it is ahead of the player in the snapshots.
+    /// Returns [`None`] if no snapshot with the player has arrived yet.
+    pub fn predicted_player(&self) -> Option<Transform>
+    {
+        self.predicted
+    }
+
+    /// Send the input of the player for a tick.
+    ///
+    /// Ticks must be strictly increasing.
+    /// The input is applied to the predicted player right away,
+    /// and sent at the next [`update`](Self::update).
+    pub fn send_input(&mut self, tick: Tick, input: AbstractInput)
+        -> Result<()>
+    {
+        self.unapplied_inputs.push(tick, input);
+        if let Some(predicted) = &mut self.predicted {
+            move_player(predicted, &input);
+        }
+
+        self.recent_inputs.push_back((tick, input));
+        if self.recent_inputs.len() > INPUT_REDUNDANCY {
+            self.recent_inputs.pop_front();
+        }
+        if let Some(connection) = self.client.connection() {
+            let inputs = self.recent_inputs.iter().copied().collect();
+            send_message(connection, &ClientMessage::Input(inputs))?;
+        }
+        Ok(())
+    }
+
+    /// Statistics about the arrival of snapshots from the server.
+    pub fn jitter_stats(&self) -> &JitterStats
+    {
+        self.snapshots.stats()
+    }
+
+    /// The entities as they are rendered at the given time,
+    /// with the player at its predicted position.
+    ///
+    /// Returns [`None`] if no snapshot has arrived yet.
+    pub fn entities(&mut self, now: Instant) -> Option<Entities>
+    {
+        let local_time = self.local_time(now);
+        let mut entities = self.snapshots.sample(local_time)?;
+        if let (Some(player), Some(predicted)) = (self.player, self.predicted) {
+            if let Some(transform) = entities.get_mut::<Transform>(player) {
+                *transform = predicted;
+            }
+        }
+        Some(entities)
+    }
+
+    /// Close the connection to the server.
+    pub fn disconnect(&mut self) -> Result<()>
+    {
+        self.client.disconnect()
+    }
+}
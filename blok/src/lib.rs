@@ -7,4 +7,6 @@ extern crate anyhow;
 mod doc;
 
 pub mod client;
+pub mod net;
+pub mod server;
 pub mod state;
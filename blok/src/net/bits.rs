@@ -0,0 +1,196 @@
+use anyhow::{Result, bail};
+
+/// Writer of bit-packed data.
+///
+/// Bits are written from the least significant bit of each byte up.
+pub struct BitWriter
+{
+    bytes: Vec<u8>,
+
+    // Number of bits written to the last byte, or zero if it is full.
+    // INVARIANT: Less than eight.
+    bit: u32,
+}
+
+impl BitWriter
+{
+    /// Create a writer with nothing written.
+    pub fn new() -> Self
+    {
+        Self{bytes: Vec::new(), bit: 0}
+    }
+
+    /// The written bytes, with the last byte padded with zeros.
+    pub fn into_bytes(self) -> Vec<u8>
+    {
+        self.bytes
+    }
+
+    /// Write a single bit.
+    pub fn write_bit(&mut self, value: bool)
+    {
+        if self.bit == 0 {
+            self.bytes.push(0);
+        }
+        if value {
+            *self.bytes.last_mut().unwrap() |= 1 << self.bit;
+        }
+        self.bit = (self.bit + 1) % 8;
+    }
+
+    /// Write the given number of low bits of a value.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the count exceeds 64.
+    pub fn write_bits(&mut self, value: u64, count: u32)
+    {
+        assert!(count <= 64);
+        for i in 0 .. count {
+            self.write_bit(value >> i & 1 != 0);
+        }
+    }
+
+    /// Write an unsigned integer in groups of seven bits,
+    /// each preceded by a bit that tells whether another group follows.
+    ///
+    /// Small values take few bits.
+    pub fn write_varint(&mut self, mut value: u64)
+    {
+        loop {
+            let more = value >= 0x80;
+            self.write_bit(more);
+            self.write_bits(value & 0x7F, 7);
+            value >>= 7;
+            if !more {
+                break;
+            }
+        }
+    }
+
+    /// Write a signed integer such that values near zero take few bits.
+    pub fn write_signed_varint(&mut self, value: i64)
+    {
+        // Zigzag encoding interleaves negative and positive values.
+        self.write_varint((value << 1 ^ value >> 63) as u64);
+    }
+}
+
+impl Default for BitWriter
+{
+    fn default() -> Self
+    {
+        Self::new()
+    }
+}
+
+/// Reader of data written by [`BitWriter`].
+pub struct BitReader<'a>
+{
+    bytes: &'a [u8],
+
+    // Number of bits read.
+    // INVARIANT: At most eight times the number of bytes.
+    position: usize,
+}
+
+impl<'a> BitReader<'a>
+{
+    /// Read from the start of the given bytes.
+    pub fn new(bytes: &'a [u8]) -> Self
+    {
+        Self{bytes, position: 0}
+    }
+
+    /// Read a single bit.
+    pub fn read_bit(&mut self) -> Result<bool>
+    {
+        let byte = match self.bytes.get(self.position / 8) {
+            Some(byte) => byte,
+            None => bail!("Unexpected end of data"),
+        };
+        let value = byte >> (self.position % 8) & 1 != 0;
+        self.position += 1;
+        Ok(value)
+    }
+
+    /// Read the given number of bits into the low bits of a value.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the count exceeds 64.
+    pub fn read_bits(&mut self, count: u32) -> Result<u64>
+    {
+        assert!(count <= 64);
+        let mut value = 0;
+        for i in 0 .. count {
+            value |= (self.read_bit()? as u64) << i;
+        }
+        Ok(value)
+    }
+
+    /// Read an integer written with [`BitWriter::write_varint`].
+    pub fn read_varint(&mut self) -> Result<u64>
+    {
+        let mut value = 0;
+        for shift in (0 .. 64).step_by(7) {
+            let more = self.read_bit()?;
+            value |= self.read_bits(7)? << shift;
+            if !more {
+                return Ok(value);
+            }
+        }
+        bail!("Variable-length integer is too long")
+    }
+
+    /// Read an integer written with [`BitWriter::write_signed_varint`].
+    pub fn read_signed_varint(&mut self) -> Result<i64>
+    {
+        let value = self.read_varint()?;
+        Ok((value >> 1) as i64 ^ -((value & 1) as i64))
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    #[test]
+    fn round_trip()
+    {
+        let values = [0, 1, -1, 63, -64, 64, i32::MAX as i64, i64::MIN];
+        let mut writer = BitWriter::new();
+        writer.write_bit(true);
+        writer.write_bits(0b101, 3);
+        for &value in &values {
+            writer.write_signed_varint(value);
+        }
+        writer.write_varint(u64::MAX);
+        let bytes = writer.into_bytes();
+
+        let mut reader = BitReader::new(&bytes);
+        assert!(reader.read_bit().unwrap());
+        assert_eq!(reader.read_bits(3).unwrap(), 0b101);
+        for &value in &values {
+            assert_eq!(reader.read_signed_varint().unwrap(), value);
+        }
+        assert_eq!(reader.read_varint().unwrap(), u64::MAX);
+    }
+
+    #[test]
+    fn small_values_are_short()
+    {
+        let mut writer = BitWriter::new();
+        writer.write_signed_varint(-3);
+        writer.write_bit(false);
+        assert_eq!(writer.into_bytes().len(), 2);
+    }
+
+    #[test]
+    fn truncated_data_is_an_error()
+    {
+        let mut reader = BitReader::new(&[0xFF]);
+        assert!(reader.read_varint().is_err());
+    }
+}
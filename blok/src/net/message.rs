@@ -0,0 +1,113 @@
+use crate::{
+    net::transport::{Channel, Connection},
+    state::{AbstractInput, Chunk, EntityId, Tick},
+};
+use anyhow::{Context, Result};
+use glam::IVec3;
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+
+/// Message of the protocol between server and clients.
+///
+/// Messages are serialized with bincode,
+/// and each kind of message is sent on a fixed channel.
+pub trait Message: DeserializeOwned + Serialize
+{
+    /// The channel on which the message is sent.
+    fn channel(&self) -> Channel;
+}
+
+/// Message from a client to the server.
+#[derive(Clone, Debug, PartialEq)]
+#[derive(Deserialize, Serialize)]
+pub enum ClientMessage
+{
+    /// Input of the player for recent ticks, oldest first.
+    ///
+    /// Input is sent unreliably, so each message repeats the input
+    /// of a few earlier ticks, and the server ignores what it has seen.
+    Input(Vec<(Tick, AbstractInput)>),
+
+    /// The client received the snapshot of a tick.
+    Acknowledge(Tick),
+}
+
+/// Message from the server to a client.
+#[derive(Clone, Deserialize, Serialize)]
+pub enum ServerMessage
+{
+    /// The client joined the game.
+    Welcome
+    {
+        /// The player entity that the client controls.
+        player: EntityId,
+    },
+
+    /// A chunk of the world.
+    Chunk
+    {
+        /// Position of the chunk, in chunks.
+        position: IVec3,
+
+        /// The chunk.
+        chunk: Chunk,
+    },
+
+    /// The entities at a tick.
+    Snapshot
+    {
+        /// Tick of the latest input applied to the player.
+        last_input: Option<Tick>,
+
+        /// Entities encoded by a [`SnapshotEncoder`](super::SnapshotEncoder).
+        data: Vec<u8>,
+    },
+}
+
+impl Message for ClientMessage
+{
+    fn channel(&self) -> Channel
+    {
+        match self {
+            Self::Input(..)       => Channel::Unreliable,
+            Self::Acknowledge(..) => Channel::Unreliable,
+        }
+    }
+}
+
+impl Message for ServerMessage
+{
+    fn channel(&self) -> Channel
+    {
+        // The welcome goes with the chunks, so that it arrives first.
+        match self {
+            Self::Welcome{..}  => Channel::Chunks,
+            Self::Chunk{..}    => Channel::Chunks,
+            Self::Snapshot{..} => Channel::Unreliable,
+        }
+    }
+}
+
+/// Send a message on its channel.
+pub fn send_message<M>(connection: &mut Connection, message: &M) -> Result<()>
+    where M: Message
+{
+    let bytes = bincode::serialize(message)?;
+    connection.send(message.channel(), &bytes)
+}
+
+/// Take the next received message, if any.
+///
+/// This fails if the message cannot be deserialized,
+/// in which case the message is discarded.
+pub fn receive_message<M>(connection: &mut Connection) -> Result<Option<M>>
+    where M: Message
+{
+    match connection.receive() {
+        Some((_, bytes)) => {
+            let message = bincode::deserialize(&bytes)
+                .context("Deserialize message")?;
+            Ok(Some(message))
+        },
+        None => Ok(None),
+    }
+}
@@ -0,0 +1,11 @@
+//! Communication between server and clients.
+
+pub use self::bits::*;
+pub use self::message::*;
+pub use self::snapshot::*;
+
+pub mod transport;
+
+mod bits;
+mod message;
+mod snapshot;
@@ -0,0 +1,786 @@
+//! Delta compression of entity snapshots.
+//!
+//! The server sends each client a snapshot of the entities every tick.
+//! Each snapshot is encoded as a delta against the latest snapshot
+//! that the client acknowledged, called the baseline:
+//! only entities that were spawned, despawned or changed are sent,
+//! and of those only the fields that changed, each behind a change bit.
+//! Without an acknowledged baseline, the snapshot is encoded in full,
+//! which is a delta against no entities.
+//!
+//! Fields are quantized before encoding, positions to a fixed grid
+//! and rotations with the smallest-three method,
+//! so the client receives slightly different values than the server has.
+//! The encoder decodes its own output to remember what the client has,
+//! so that deltas are exact with respect to quantized values.
+
+use crate::{
+    net::{BitReader, BitWriter},
+    state::{
+        BoundingBox,
+        Component,
+        Entities,
+        EntityId,
+        Health,
+        ModelId,
+        Tick,
+        Transform,
+        Velocity,
+    },
+};
+use anyhow::{Context, Result, anyhow, ensure};
+use glam::{Quat, Vec3, vec3};
+use std::{collections::VecDeque, f32::consts::SQRT_2};
+
+/// Number of snapshots kept for use as baselines.
+///
+/// If a client does not acknowledge any of this many snapshots,
+/// the server falls back to sending full snapshots.
+pub const SNAPSHOT_HISTORY: usize = 64;
+
+/// Steps per block of quantized positions.
+const POSITION_SCALE: f32 = 512.0;
+
+/// Steps per block per tick of quantized velocities.
+const VELOCITY_SCALE: f32 = 1024.0;
+
+/// Steps per block of quantized bounding boxes.
+const BOUNDING_BOX_SCALE: f32 = 64.0;
+
+/// Bits per component of quantized rotations.
+const ROTATION_BITS: u32 = 10;
+
+////////////////////////////////////////////////////////////////////////////////
+// Encoding and decoding
+
+/// Encoder of the snapshots sent to a single client.
+pub struct SnapshotEncoder
+{
+    // The snapshots as the client decodes them, including the baseline.
+    // INVARIANT: Ticks are strictly increasing.
+    sent: VecDeque<(Tick, Entities)>,
+
+    // INVARIANT: If set, the tick is in `sent`.
+    baseline: Option<Tick>,
+}
+
+impl SnapshotEncoder
+{
+    /// Create an encoder for a client that has not received anything.
+    pub fn new() -> Self
+    {
+        Self{sent: VecDeque::new(), baseline: None}
+    }
+
+    /// The tick of the snapshot that the next snapshot is encoded against,
+    /// or [`None`] if the next snapshot is encoded in full.
+    pub fn baseline(&self) -> Option<Tick>
+    {
+        self.baseline
+    }
+
+    /// Record that the client received the snapshot of a tick.
+    ///
+    /// Acknowledgements of snapshots that are older than the baseline,
+    /// or that were not sent or are no longer remembered, are ignored.
+    pub fn acknowledge(&mut self, tick: Tick)
+    {
+        if matches!(self.baseline, Some(baseline) if tick <= baseline) {
+            return;
+        }
+        if self.sent.iter().all(|&(sent, _)| sent != tick) {
+            return;
+        }
+        self.baseline = Some(tick);
+
+        // Older snapshots will not be used as a baseline again.
+        while matches!(self.sent.front(), Some(&(sent, _)) if sent < tick) {
+            self.sent.pop_front();
+        }
+    }
+
+    /// Encode the snapshot for a tick.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `tick` does not monotonically increase.
+    pub fn encode(&mut self, tick: Tick, entities: &Entities) -> Vec<u8>
+    {
+        if let Some(&(latest, _)) = self.sent.back() {
+            assert!(tick > latest);
+        }
+
+        let baseline = self.baseline.map(|baseline| {
+            let index = self.sent.iter()
+                .position(|&(sent, _)| sent == baseline)
+                .unwrap();
+            (baseline, &self.sent[index].1)
+        });
+
+        let mut writer = BitWriter::new();
+        write_snapshot(&mut writer, tick, baseline, entities);
+        let bytes = writer.into_bytes();
+
+        let decoded = read_delta(&mut BitReader::new(&bytes), baseline, true)
+            .expect("Encoder cannot decode its own output");
+        self.sent.push_back((tick, decoded.1));
+
+        if self.sent.len() > SNAPSHOT_HISTORY {
+            let (evicted, _) = self.sent.pop_front().unwrap();
+            if self.baseline == Some(evicted) {
+                self.baseline = None;
+            }
+        }
+
+        bytes
+    }
+}
+
+impl Default for SnapshotEncoder
+{
+    fn default() -> Self
+    {
+        Self::new()
+    }
+}
+
+/// Decoder of the snapshots received by a client.
+pub struct SnapshotDecoder
+{
+    // INVARIANT: Ticks are strictly increasing.
+    received: VecDeque<(Tick, Entities)>,
+}
+
+impl SnapshotDecoder
+{
+    /// Create a decoder that has not received anything.
+    pub fn new() -> Self
+    {
+        Self{received: VecDeque::new()}
+    }
+
+    /// Decode a snapshot.
+    ///
+    /// The snapshot is remembered for decoding later snapshots against,
+    /// and its tick should be acknowledged to the server.
+    /// This fails if the data is malformed,
+    /// or if the baseline is no longer remembered.
+    pub fn decode(&mut self, bytes: &[u8]) -> Result<(Tick, Entities)>
+    {
+        let mut reader = BitReader::new(bytes);
+        let (tick, baseline_tick) = read_header(&mut reader)?;
+
+        let baseline = match baseline_tick {
+            Some(baseline_tick) => {
+                let (_, baseline) = self.received.iter()
+                    .find(|&&(received, _)| received == baseline_tick)
+                    .ok_or_else(|| {
+                        anyhow!("Baseline {:?} is not available", baseline_tick)
+                    })?;
+                Some((baseline_tick, baseline))
+            },
+            None => None,
+        };
+        let (tick, entities) =
+            read_delta(&mut BitReader::new(bytes), baseline, false)
+            .with_context(|| format!("Decode snapshot {:?}", tick))?;
+
+        // The server acknowledges monotonically,
+        // so snapshots older than the baseline are not used again.
+        if let Some(baseline_tick) = baseline_tick {
+            while matches!(
+                self.received.front(),
+                Some(&(received, _)) if received < baseline_tick,
+            ) {
+                self.received.pop_front();
+            }
+        }
+
+        let index = self.received.partition_point(|&(t, _)| t < tick);
+        if !matches!(self.received.get(index), Some(&(t, _)) if t == tick) {
+            self.received.insert(index, (tick, entities.clone()));
+        }
+        if self.received.len() > SNAPSHOT_HISTORY {
+            self.received.pop_front();
+        }
+
+        Ok((tick, entities))
+    }
+}
+
+impl Default for SnapshotDecoder
+{
+    fn default() -> Self
+    {
+        Self::new()
+    }
+}
+
+fn write_snapshot(
+    writer: &mut BitWriter,
+    tick: Tick,
+    baseline: Option<(Tick, &Entities)>,
+    entities: &Entities,
+)
+{
+    // Header.
+    writer.write_varint(tick.0);
+    writer.write_bit(baseline.is_some());
+    if let Some((baseline_tick, _)) = baseline {
+        writer.write_varint(tick.0 - baseline_tick.0);
+    }
+    writer.write_varint(entities.next_id().0);
+
+    let empty = Entities::new();
+    let baseline = baseline.map_or(&empty, |(_, baseline)| baseline);
+
+    // Despawned entities.
+    let despawned: Vec<EntityId> =
+        baseline.ids().filter(|&id| !entities.contains(id)).collect();
+    write_ids(writer, &despawned);
+
+    // Spawned and changed entities.
+    let changed: Vec<EntityId> =
+        entities.ids()
+        .filter(|&id| {
+            !baseline.contains(id)
+                || component_changed::<Transform>(baseline, entities, id)
+                || component_changed::<Velocity>(baseline, entities, id)
+                || component_changed::<BoundingBox>(baseline, entities, id)
+                || component_changed::<Health>(baseline, entities, id)
+                || component_changed::<ModelId>(baseline, entities, id)
+        })
+        .collect();
+    write_ids(writer, &changed);
+    for &id in &changed {
+        write_component::<Transform>(writer, baseline, entities, id);
+        write_component::<Velocity>(writer, baseline, entities, id);
+        write_component::<BoundingBox>(writer, baseline, entities, id);
+        write_component::<Health>(writer, baseline, entities, id);
+        write_component::<ModelId>(writer, baseline, entities, id);
+    }
+}
+
+fn read_header(reader: &mut BitReader) -> Result<(Tick, Option<Tick>)>
+{
+    let tick = reader.read_varint()?;
+    let baseline = if reader.read_bit()? {
+        let age = reader.read_varint()?;
+        let baseline = tick.checked_sub(age)
+            .ok_or_else(|| anyhow!("Baseline precedes tick zero"))?;
+        Some(Tick(baseline))
+    } else {
+        None
+    };
+    Ok((Tick(tick), baseline))
+}
+
+/// Decode a snapshot against its baseline.
+///
+/// With `trusted`, the data is known to be well-formed,
+/// and consistency checks are skipped.
+fn read_delta(
+    reader: &mut BitReader,
+    baseline: Option<(Tick, &Entities)>,
+    trusted: bool,
+) -> Result<(Tick, Entities)>
+{
+    let (tick, baseline_tick) = read_header(reader)?;
+    ensure!(
+        trusted || baseline_tick == baseline.map(|(t, _)| t),
+        "Snapshot has a different baseline",
+    );
+    let next_id = EntityId(reader.read_varint()?);
+
+    let mut entities = match baseline {
+        Some((_, baseline)) => baseline.clone(),
+        None => Entities::new(),
+    };
+
+    for id in read_ids(reader)? {
+        ensure!(entities.despawn(id), "Despawned {:?} does not exist", id);
+    }
+
+    let changed = read_ids(reader)?;
+    for &id in &changed {
+        if !entities.contains(id) {
+            ensure!(id < next_id,
+                    "Spawned {:?} exceeds next identifier", id);
+            entities.spawn_with_id(id);
+        }
+        read_component::<Transform>(reader, &mut entities, id)?;
+        read_component::<Velocity>(reader, &mut entities, id)?;
+        read_component::<BoundingBox>(reader, &mut entities, id)?;
+        read_component::<Health>(reader, &mut entities, id)?;
+        read_component::<ModelId>(reader, &mut entities, id)?;
+    }
+
+    entities.reserve_ids(next_id);
+    ensure!(entities.next_id() == next_id,
+            "Entity identifiers exceed next identifier");
+
+    Ok((tick, entities))
+}
+
+/// Write increasing identifiers, each as the difference from the previous.
+fn write_ids(writer: &mut BitWriter, ids: &[EntityId])
+{
+    writer.write_varint(ids.len() as u64);
+    let mut previous = 0;
+    for id in ids {
+        writer.write_varint(id.0 - previous);
+        previous = id.0;
+    }
+}
+
+fn read_ids(reader: &mut BitReader) -> Result<Vec<EntityId>>
+{
+    let count = reader.read_varint()?;
+    let mut ids = Vec::new();
+    let mut previous: u64 = 0;
+    for _ in 0 .. count {
+        let id = previous.checked_add(reader.read_varint()?)
+            .ok_or_else(|| anyhow!("Entity identifier overflows"))?;
+        ensure!(ids.is_empty() || id > previous,
+                "Entity identifiers do not increase");
+        ids.push(EntityId(id));
+        previous = id;
+    }
+    Ok(ids)
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// Components
+
+/// How a quantized field is encoded.
+#[derive(Clone, Copy)]
+enum FieldEncoding
+{
+    /// Difference from the baseline as a signed variable-length integer.
+    Delta,
+
+    /// The value itself with a fixed number of bits.
+    Raw(u32),
+}
+
+/// Component that can be sent in snapshots.
+trait Quantize: Component + Clone
+{
+    /// How each field is encoded.
+    const FIELDS: &'static [FieldEncoding];
+
+    /// The quantized value of each field.
+    ///
+    /// Absent components are encoded as if all fields were zero.
+    fn quantize(&self) -> Vec<i64>;
+
+    /// The component from quantized fields.
+    fn dequantize(fields: &[i64]) -> Self;
+}
+
+fn component_changed<T>(baseline: &Entities, entities: &Entities, id: EntityId)
+    -> bool
+    where T: Quantize
+{
+    let quantize = |entities: &Entities| {
+        entities.get::<T>(id).map(T::quantize)
+    };
+    quantize(baseline) != quantize(entities)
+}
+
+/// Write a change bit, and if changed, a presence bit,
+/// and if present, the fields, each with their own change bit.
+fn write_component<T>(
+    writer: &mut BitWriter,
+    baseline: &Entities,
+    entities: &Entities,
+    id: EntityId,
+)
+    where T: Quantize
+{
+    let changed = component_changed::<T>(baseline, entities, id);
+    writer.write_bit(changed);
+    if !changed {
+        return;
+    }
+
+    let component = entities.get::<T>(id);
+    writer.write_bit(component.is_some());
+    let fields = match component {
+        Some(component) => component.quantize(),
+        None => return,
+    };
+    let baseline_fields = baseline_fields::<T>(baseline, id);
+
+    for ((&field, &baseline_field), &encoding) in
+        fields.iter().zip(&baseline_fields).zip(T::FIELDS)
+    {
+        writer.write_bit(field != baseline_field);
+        if field == baseline_field {
+            continue;
+        }
+        match encoding {
+            FieldEncoding::Delta =>
+                writer.write_signed_varint(field - baseline_field),
+            FieldEncoding::Raw(bits) =>
+                writer.write_bits(field as u64, bits),
+        }
+    }
+}
+
+fn read_component<T>(
+    reader: &mut BitReader,
+    entities: &mut Entities,
+    id: EntityId,
+) -> Result<()>
+    where T: Quantize
+{
+    if !reader.read_bit()? {
+        return Ok(());
+    }
+    if !reader.read_bit()? {
+        entities.remove::<T>(id);
+        return Ok(());
+    }
+
+    let mut fields = baseline_fields::<T>(entities, id);
+    for (field, &encoding) in fields.iter_mut().zip(T::FIELDS) {
+        if !reader.read_bit()? {
+            continue;
+        }
+        *field = match encoding {
+            FieldEncoding::Delta =>
+                field.checked_add(reader.read_signed_varint()?)
+                    .ok_or_else(|| anyhow!("Field overflows"))?,
+            FieldEncoding::Raw(bits) =>
+                reader.read_bits(bits)? as i64,
+        };
+    }
+    entities.insert(id, T::dequantize(&fields));
+    Ok(())
+}
+
+fn baseline_fields<T>(baseline: &Entities, id: EntityId) -> Vec<i64>
+    where T: Quantize
+{
+    match baseline.get::<T>(id) {
+        Some(component) => component.quantize(),
+        None => vec![0; T::FIELDS.len()],
+    }
+}
+
+fn quantize_f32(value: f32, scale: f32) -> i64
+{
+    (value * scale).round() as i64
+}
+
+fn dequantize_f32(value: i64, scale: f32) -> f32
+{
+    value as f32 / scale
+}
+
+fn quantize_vec3(value: Vec3, scale: f32) -> [i64; 3]
+{
+    value.to_array().map(|c| quantize_f32(c, scale))
+}
+
+fn dequantize_vec3(fields: &[i64], scale: f32) -> Vec3
+{
+    vec3(
+        dequantize_f32(fields[0], scale),
+        dequantize_f32(fields[1], scale),
+        dequantize_f32(fields[2], scale),
+    )
+}
+
+/// Quantize a rotation with the smallest-three method.
+///
+/// The largest component of a unit quaternion follows from the others,
+/// which each lie within ±1/√2. Only the index of the largest component
+/// and the other three components are stored.
+fn quantize_rotation(rotation: Quat) -> i64
+{
+    let components: [f32; 4] = rotation.normalize().into();
+    let largest = (0 .. 4)
+        .max_by(|&a, &b| {
+            components[a].abs().total_cmp(&components[b].abs())
+        })
+        .unwrap();
+
+    // q and -q are the same rotation, so make the largest positive.
+    let sign = components[largest].signum();
+    let max = ((1 << ROTATION_BITS) - 1) as f32;
+    let mut packed = largest as i64;
+    for (i, component) in components.iter().enumerate() {
+        if i != largest {
+            let unit = (component * sign * SQRT_2 + 1.0) / 2.0;
+            let step = (unit * max).round().clamp(0.0, max) as i64;
+            packed = packed << ROTATION_BITS | step;
+        }
+    }
+    packed
+}
+
+fn dequantize_rotation(packed: i64) -> Quat
+{
+    let max = ((1 << ROTATION_BITS) - 1) as f32;
+    let mask = (1 << ROTATION_BITS) - 1;
+    let largest = (packed >> (3 * ROTATION_BITS) & 3) as usize;
+    let mut components = [0.0; 4];
+    let mut shift = 3 * ROTATION_BITS;
+    for (i, component) in components.iter_mut().enumerate() {
+        if i != largest {
+            shift -= ROTATION_BITS;
+            let step = (packed >> shift & mask) as f32;
+            *component = (step / max * 2.0 - 1.0) / SQRT_2;
+        }
+    }
+    let sum: f32 = components.iter().map(|c| c * c).sum();
+    components[largest] = (1.0 - sum).max(0.0).sqrt();
+    Quat::from_array(components).normalize()
+}
+
+impl Quantize for Transform
+{
+    const FIELDS: &'static [FieldEncoding] = &[
+        FieldEncoding::Delta,
+        FieldEncoding::Delta,
+        FieldEncoding::Delta,
+        FieldEncoding::Raw(2 + 3 * ROTATION_BITS),
+    ];
+
+    fn quantize(&self) -> Vec<i64>
+    {
+        let [x, y, z] = quantize_vec3(self.position, POSITION_SCALE);
+        vec![x, y, z, quantize_rotation(self.orientation)]
+    }
+
+    fn dequantize(fields: &[i64]) -> Self
+    {
+        Self{
+            position: dequantize_vec3(&fields[0 .. 3], POSITION_SCALE),
+            orientation: dequantize_rotation(fields[3]),
+        }
+    }
+}
+
+impl Quantize for Velocity
+{
+    const FIELDS: &'static [FieldEncoding] = &[FieldEncoding::Delta; 3];
+
+    fn quantize(&self) -> Vec<i64>
+    {
+        quantize_vec3(self.0, VELOCITY_SCALE).to_vec()
+    }
+
+    fn dequantize(fields: &[i64]) -> Self
+    {
+        Self(dequantize_vec3(fields, VELOCITY_SCALE))
+    }
+}
+
+impl Quantize for BoundingBox
+{
+    const FIELDS: &'static [FieldEncoding] = &[FieldEncoding::Delta; 6];
+
+    fn quantize(&self) -> Vec<i64>
+    {
+        let min = quantize_vec3(self.min, BOUNDING_BOX_SCALE);
+        let max = quantize_vec3(self.max, BOUNDING_BOX_SCALE);
+        [min, max].concat()
+    }
+
+    fn dequantize(fields: &[i64]) -> Self
+    {
+        Self{
+            min: dequantize_vec3(&fields[0 .. 3], BOUNDING_BOX_SCALE),
+            max: dequantize_vec3(&fields[3 .. 6], BOUNDING_BOX_SCALE),
+        }
+    }
+}
+
+impl Quantize for Health
+{
+    const FIELDS: &'static [FieldEncoding] = &[FieldEncoding::Delta; 2];
+
+    fn quantize(&self) -> Vec<i64>
+    {
+        vec![self.current as i64, self.maximum as i64]
+    }
+
+    fn dequantize(fields: &[i64]) -> Self
+    {
+        Self{current: fields[0] as u32, maximum: fields[1] as u32}
+    }
+}
+
+impl Quantize for ModelId
+{
+    const FIELDS: &'static [FieldEncoding] = &[FieldEncoding::Delta];
+
+    fn quantize(&self) -> Vec<i64>
+    {
+        vec![self.0 as i64]
+    }
+
+    fn dequantize(fields: &[i64]) -> Self
+    {
+        Self(fields[0] as u32)
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+    use std::f32::consts::PI;
+
+    /// Entities with all components, moving and turning each tick.
+    fn crowd(tick: u64, count: u64) -> Entities
+    {
+        let mut entities = Entities::new();
+        for i in 0 .. count {
+            let id = EntityId(i);
+            entities.spawn_with_id(id);
+            let t = tick as f32 * 0.1 + i as f32;
+            entities.insert(id, Transform{
+                position: vec3(t.sin() * 10.0, i as f32, 64.0),
+                orientation: Quat::from_rotation_z(t),
+            });
+            entities.insert(id, Velocity(vec3(t.cos(), 0.0, 0.0)));
+            entities.insert(id, BoundingBox{
+                min: vec3(-0.3, -0.3, 0.0),
+                max: vec3(0.3, 0.3, 1.8),
+            });
+            entities.insert(id, Health{current: 20, maximum: 20});
+            entities.insert(id, ModelId(i as u32 % 3));
+        }
+        entities
+    }
+
+    fn assert_close(decoded: &Entities, original: &Entities)
+    {
+        assert_eq!(decoded.ids().collect::<Vec<_>>(),
+                   original.ids().collect::<Vec<_>>());
+        assert_eq!(decoded.next_id(), original.next_id());
+        for id in original.ids() {
+            let a = decoded.get::<Transform>(id);
+            let b = original.get::<Transform>(id);
+            assert_eq!(a.is_some(), b.is_some());
+            if let (Some(a), Some(b)) = (a, b) {
+                let epsilon = 1.0 / POSITION_SCALE;
+                assert!(a.position.abs_diff_eq(b.position, epsilon));
+                assert!(a.orientation.angle_between(b.orientation) < 0.01);
+            }
+            assert_eq!(decoded.get::<Health>(id), original.get::<Health>(id));
+            assert_eq!(decoded.get::<ModelId>(id),
+                       original.get::<ModelId>(id));
+        }
+    }
+
+    #[test]
+    fn rotation_quantization()
+    {
+        let rotations = [
+            Quat::IDENTITY,
+            -Quat::IDENTITY,
+            Quat::from_rotation_z(PI / 3.0),
+            Quat::from_rotation_y(-2.0),
+            Quat::from_euler(glam::EulerRot::XYZ, 0.3, -1.2, 2.5),
+        ];
+        for rotation in rotations {
+            let decoded = dequantize_rotation(quantize_rotation(rotation));
+            assert!(decoded.angle_between(rotation) < 0.005,
+                    "{:?} decoded as {:?}", rotation, decoded);
+        }
+    }
+
+    #[test]
+    fn full_snapshot_round_trip()
+    {
+        let entities = crowd(0, 10);
+        let mut encoder = SnapshotEncoder::new();
+        let mut decoder = SnapshotDecoder::new();
+        let bytes = encoder.encode(Tick(5), &entities);
+        let (tick, decoded) = decoder.decode(&bytes).unwrap();
+        assert_eq!(tick, Tick(5));
+        assert_close(&decoded, &entities);
+    }
+
+    #[test]
+    fn delta_round_trip_with_spawns_and_despawns()
+    {
+        let mut encoder = SnapshotEncoder::new();
+        let mut decoder = SnapshotDecoder::new();
+
+        for tick in 0 .. 20 {
+            let mut entities = crowd(tick, 10);
+            // Despawn some entities and remove some components.
+            if tick % 3 == 0 {
+                entities.despawn(EntityId(tick % 10));
+            }
+            if tick % 4 == 0 {
+                entities.remove::<Velocity>(EntityId(1));
+            }
+            let spawned = entities.spawn();
+            entities.insert(spawned, ModelId(7));
+
+            let bytes = encoder.encode(Tick(tick), &entities);
+            let (_, decoded) = decoder.decode(&bytes).unwrap();
+            assert_close(&decoded, &entities);
+            assert_eq!(decoded.get::<Velocity>(EntityId(1)).is_some(),
+                       entities.get::<Velocity>(EntityId(1)).is_some());
+
+            // Acknowledge every other snapshot, as if some were lost.
+            if tick % 2 == 0 {
+                encoder.acknowledge(Tick(tick));
+            }
+        }
+        assert_eq!(encoder.baseline(), Some(Tick(18)));
+    }
+
+    #[test]
+    fn unchanged_entities_are_not_sent()
+    {
+        let entities = crowd(0, 100);
+        let mut encoder = SnapshotEncoder::new();
+        let full = encoder.encode(Tick(0), &entities);
+        encoder.acknowledge(Tick(0));
+        let delta = encoder.encode(Tick(1), &entities);
+        assert!(full.len() > 100 * 10);
+        assert!(delta.len() < 8, "Delta has {} bytes", delta.len());
+    }
+
+    #[test]
+    fn missing_baseline_is_an_error()
+    {
+        let entities = crowd(0, 3);
+        let mut encoder = SnapshotEncoder::new();
+        encoder.encode(Tick(0), &entities);
+        encoder.acknowledge(Tick(0));
+        let delta = encoder.encode(Tick(1), &entities);
+
+        // The decoder never received the baseline.
+        let mut decoder = SnapshotDecoder::new();
+        assert!(decoder.decode(&delta).is_err());
+        assert!(decoder.decode(&[0xFF; 3]).is_err());
+    }
+
+    #[test]
+    fn identifier_beyond_next_identifier_is_an_error()
+    {
+        // A full snapshot that spawns the greatest possible identifier,
+        // whose successor cannot be represented.
+        let mut writer = BitWriter::new();
+        writer.write_varint(0);
+        writer.write_bit(false);
+        writer.write_varint(u64::MAX);
+        write_ids(&mut writer, &[]);
+        write_ids(&mut writer, &[EntityId(u64::MAX)]);
+        let bytes = writer.into_bytes();
+
+        let mut decoder = SnapshotDecoder::new();
+        let err = decoder.decode(&bytes).err().unwrap();
+        assert!(format!("{:?}", err).contains("exceeds next identifier"));
+    }
+}
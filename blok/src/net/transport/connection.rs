@@ -0,0 +1,724 @@
+use super::{
+    Channel,
+    MAX_MESSAGE_SIZE,
+    MAX_PACKET_SIZE,
+    packet::{
+        FRAGMENT_HEADER_SIZE,
+        Fragment,
+        PAYLOAD_HEADER_SIZE,
+        Packet,
+        PayloadHeader,
+    },
+};
+use anyhow::{Result, ensure};
+use std::{
+    collections::{HashMap, VecDeque},
+    time::{Duration, Instant},
+};
+
+/// Time without receiving packets after which a connection is lost.
+pub const CONNECTION_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Maximum size of the data in a fragment, in bytes.
+const FRAGMENT_SIZE: usize =
+    MAX_PACKET_SIZE - PAYLOAD_HEADER_SIZE - FRAGMENT_HEADER_SIZE;
+
+/// Number of packets before the latest that each packet acknowledges.
+const ACK_BITS: u16 = 32;
+
+/// Number of reliable messages per channel that may be in flight.
+///
+/// This must be well below half the range of message identifiers,
+/// so that the receiver can tell old messages from new ones.
+const RELIABLE_WINDOW: u16 = 1024;
+
+/// Number of recent unreliable messages that may be partially received.
+const UNRELIABLE_WINDOW: u16 = 64;
+
+/// Retransmission timeout before the round-trip time is known,
+/// and its bounds once it is.
+const INITIAL_RTO: Duration = Duration::from_millis(250);
+const MIN_RTO: Duration = Duration::from_millis(30);
+const MAX_RTO: Duration = Duration::from_secs(2);
+
+/// Time after which a packet is sent even if there is nothing to send,
+/// so that the other side does not time out.
+const KEEPALIVE_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Maximum number of packets sent per update.
+///
+/// This crudely limits the bandwidth used for resending and for
+/// large messages, which would otherwise flood the network at once.
+const MAX_PACKETS_PER_UPDATE: usize = 64;
+
+/// Maximum number of sent packets remembered for acknowledgement.
+const MAX_SENT_PACKETS: usize = 1024;
+
+/// Statistics about a connection.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct ConnectionStats
+{
+    /// Smoothed round-trip time, once a packet was acknowledged.
+    pub rtt: Option<Duration>,
+
+    /// Variation in round-trip time.
+    pub rtt_variation: Duration,
+
+    /// Number of packets sent.
+    pub packets_sent: u64,
+
+    /// Number of packets received, including duplicates.
+    pub packets_received: u64,
+
+    /// Number of packets sent that were never acknowledged.
+    pub packets_lost: u64,
+
+    /// Number of times a fragment of a reliable message was resent.
+    pub fragments_resent: u64,
+}
+
+/// Established connection between a client and the server.
+///
+/// Messages passed to [`send`](Self::send) are transmitted
+/// when the endpoint that owns the connection is updated,
+/// and messages received are returned by [`receive`](Self::receive).
+pub struct Connection
+{
+    session: u64,
+
+    next_sequence: u16,
+
+    // INVARIANT: Sequence numbers are increasing, modulo wraparound.
+    sent: VecDeque<SentPacket>,
+
+    /// Sequence number of the latest packet received,
+    /// and which of the packets before it were received.
+    remote_sequence: Option<u16>,
+    remote_bits: u32,
+
+    /// Whether data was received since the last packet was sent.
+    ack_pending: bool,
+
+    outgoing: [OutgoingChannel; 3],
+    incoming: [IncomingChannel; 3],
+    delivered: VecDeque<(Channel, Vec<u8>)>,
+
+    last_sent: Option<Instant>,
+    last_received: Instant,
+
+    stats: ConnectionStats,
+}
+
+struct SentPacket
+{
+    sequence: u16,
+    time: Instant,
+    acked: bool,
+
+    /// Fragments of reliable messages in the packet,
+    /// as channel, message identifier and fragment index.
+    fragments: Vec<(Channel, u16, u16)>,
+}
+
+#[derive(Default)]
+struct OutgoingChannel
+{
+    next_message_id: u16,
+
+    // INVARIANT: Ordered by message identifier and fragment index.
+    fragments: VecDeque<OutgoingFragment>,
+}
+
+struct OutgoingFragment
+{
+    message_id: u16,
+    index: u16,
+    count: u16,
+    data: Vec<u8>,
+    last_sent: Option<Instant>,
+}
+
+#[derive(Default)]
+struct IncomingChannel
+{
+    /// For reliable channels, the next message to deliver.
+    /// For the unreliable channel, one past the latest message seen.
+    next_message_id: u16,
+
+    assemblies: HashMap<u16, Assembly>,
+}
+
+/// Partially received message.
+struct Assembly
+{
+    fragments: Vec<Option<Vec<u8>>>,
+    missing: usize,
+}
+
+impl Connection
+{
+    pub(super) fn new(session: u64, now: Instant) -> Self
+    {
+        Self{
+            session,
+            next_sequence: 0,
+            sent: VecDeque::new(),
+            remote_sequence: None,
+            remote_bits: 0,
+            ack_pending: false,
+            outgoing: Default::default(),
+            incoming: Default::default(),
+            delivered: VecDeque::new(),
+            last_sent: None,
+            last_received: now,
+            stats: ConnectionStats::default(),
+        }
+    }
+
+    pub(super) fn session(&self) -> u64
+    {
+        self.session
+    }
+
+    /// Statistics about the connection so far.
+    pub fn stats(&self) -> &ConnectionStats
+    {
+        &self.stats
+    }
+
+    /// Queue a message for sending on a channel.
+    ///
+    /// Messages larger than a packet are fragmented.
+    /// This fails if the message exceeds [`MAX_MESSAGE_SIZE`].
+    pub fn send(&mut self, channel: Channel, message: &[u8]) -> Result<()>
+    {
+        ensure!(message.len() <= MAX_MESSAGE_SIZE,
+                "Message of {} bytes is too large", message.len());
+
+        let outgoing = &mut self.outgoing[channel.index()];
+        let message_id = outgoing.next_message_id;
+        outgoing.next_message_id = message_id.wrapping_add(1);
+
+        // Empty messages still take a fragment.
+        let mut parts: Vec<&[u8]> = message.chunks(FRAGMENT_SIZE).collect();
+        if parts.is_empty() {
+            parts.push(&[]);
+        }
+        let count = parts.len();
+        for (index, part) in parts.into_iter().enumerate() {
+            outgoing.fragments.push_back(OutgoingFragment{
+                message_id,
+                index: index as u16,
+                count: count as u16,
+                data: part.to_vec(),
+                last_sent: None,
+            });
+        }
+        Ok(())
+    }
+
+    /// Take the next received message, if any.
+    ///
+    /// Messages on reliable channels are returned in the order sent.
+    pub fn receive(&mut self) -> Option<(Channel, Vec<u8>)>
+    {
+        self.delivered.pop_front()
+    }
+
+    /// Whether nothing was received for [`CONNECTION_TIMEOUT`].
+    pub(super) fn is_timed_out(&self, now: Instant) -> bool
+    {
+        now.saturating_duration_since(self.last_received) > CONNECTION_TIMEOUT
+    }
+
+    /// Handle a payload packet of this connection.
+    pub(super) fn process(
+        &mut self,
+        now: Instant,
+        header: PayloadHeader,
+        fragments: &[Fragment],
+    )
+    {
+        self.last_received = now;
+        self.stats.packets_received += 1;
+        self.process_acks(now, header.ack, header.ack_bits);
+
+        // Duplicated packets must not deliver unreliable messages twice.
+        if !self.record_received(header.sequence) {
+            return;
+        }
+
+        // Acknowledge data promptly, so that it is not resent needlessly.
+        // Packets without data are acknowledged along with the next packet,
+        // lest acknowledgements be acknowledged back and forth forever.
+        if !fragments.is_empty() {
+            self.ack_pending = true;
+        }
+
+        for fragment in fragments {
+            self.receive_fragment(fragment);
+        }
+    }
+
+    /// Encode the packets that are due.
+    ///
+    /// This sends queued fragments, resends unacknowledged fragments
+    /// of reliable messages after the retransmission timeout,
+    /// and sends acknowledgements and keepalives.
+    pub(super) fn write_packets(&mut self, now: Instant) -> Vec<Vec<u8>>
+    {
+        let rto = self.rto();
+        let mut packets = Vec::new();
+
+        while packets.len() < MAX_PACKETS_PER_UPDATE {
+            let chosen = self.choose_fragments(now, rto);
+            let keepalive = !matches!(
+                self.last_sent,
+                Some(last) if now < last + KEEPALIVE_INTERVAL,
+            );
+            if chosen.is_empty() && !self.ack_pending && !keepalive {
+                break;
+            }
+
+            // Before receiving anything, acknowledge a sequence number
+            // that the first packets of the other side do not match.
+            let header = PayloadHeader{
+                sequence: self.next_sequence,
+                ack: self.remote_sequence
+                    .unwrap_or_else(|| 0u16.wrapping_sub(ACK_BITS + 1)),
+                ack_bits: self.remote_bits,
+            };
+            let fragments =
+                chosen.iter()
+                .map(|&(channel, position)| {
+                    let fragment =
+                        &self.outgoing[channel.index()].fragments[position];
+                    Fragment{
+                        channel,
+                        message_id: fragment.message_id,
+                        index: fragment.index,
+                        count: fragment.count,
+                        data: &fragment.data,
+                    }
+                })
+                .collect();
+            let packet = Packet::Payload{
+                session: self.session,
+                header,
+                fragments,
+            };
+            packets.push(packet.encode());
+
+            let reliable = self.mark_sent(now, &chosen);
+            self.sent.push_back(SentPacket{
+                sequence: self.next_sequence,
+                time: now,
+                acked: false,
+                fragments: reliable,
+            });
+            if self.sent.len() > MAX_SENT_PACKETS {
+                let packet = self.sent.pop_front().unwrap();
+                if !packet.acked {
+                    self.stats.packets_lost += 1;
+                }
+            }
+
+            self.next_sequence = self.next_sequence.wrapping_add(1);
+            self.ack_pending = false;
+            self.last_sent = Some(now);
+            self.stats.packets_sent += 1;
+        }
+
+        packets
+    }
+
+    /// Implementation detail of `write_packets`.
+    ///
+    /// Returns the channel and position in the queue of each fragment
+    /// that goes in the next packet, in increasing order.
+    fn choose_fragments(&self, now: Instant, rto: Duration)
+        -> Vec<(Channel, usize)>
+    {
+        let mut size = PAYLOAD_HEADER_SIZE;
+        let mut chosen = Vec::new();
+        for channel in Channel::ALL {
+            let fragments = &self.outgoing[channel.index()].fragments;
+            let oldest = match fragments.front() {
+                Some(fragment) => fragment.message_id,
+                None => continue,
+            };
+            for (position, fragment) in fragments.iter().enumerate() {
+                if fragment.message_id.wrapping_sub(oldest) >= RELIABLE_WINDOW {
+                    break;
+                }
+                let last_sent = fragment.last_sent;
+                if matches!(last_sent, Some(last) if now < last + rto) {
+                    continue;
+                }
+                let fragment_size = FRAGMENT_HEADER_SIZE + fragment.data.len();
+                if size + fragment_size > MAX_PACKET_SIZE {
+                    continue;
+                }
+                size += fragment_size;
+                chosen.push((channel, position));
+            }
+        }
+        chosen
+    }
+
+    /// Implementation detail of `write_packets`.
+    ///
+    /// Removes sent fragments of unreliable messages,
+    /// and returns the fragments of reliable messages.
+    fn mark_sent(&mut self, now: Instant, chosen: &[(Channel, usize)])
+        -> Vec<(Channel, u16, u16)>
+    {
+        let mut reliable = Vec::new();
+        for &(channel, position) in chosen.iter().rev() {
+            let fragments = &mut self.outgoing[channel.index()].fragments;
+            if channel.is_reliable() {
+                let fragment = &mut fragments[position];
+                if fragment.last_sent.is_some() {
+                    self.stats.fragments_resent += 1;
+                }
+                fragment.last_sent = Some(now);
+                reliable.push((channel, fragment.message_id, fragment.index));
+            } else {
+                fragments.remove(position);
+            }
+        }
+        reliable
+    }
+
+    /// Record the sequence number of a received packet.
+    ///
+    /// Returns whether the packet was not received before.
+    /// Packets too old to tell are considered received before.
+    fn record_received(&mut self, sequence: u16) -> bool
+    {
+        let latest = match self.remote_sequence {
+            Some(latest) => latest,
+            None => {
+                self.remote_sequence = Some(sequence);
+                return true;
+            },
+        };
+
+        if sequence_greater(sequence, latest) {
+            let shift = sequence.wrapping_sub(latest) as u64;
+            self.remote_bits = if shift <= ACK_BITS as u64 {
+                ((self.remote_bits as u64) << shift | 1 << (shift - 1)) as u32
+            } else {
+                0
+            };
+            self.remote_sequence = Some(sequence);
+            return true;
+        }
+
+        let age = latest.wrapping_sub(sequence);
+        if age == 0 || age > ACK_BITS {
+            return false;
+        }
+        let bit = 1 << (age - 1);
+        let fresh = self.remote_bits & bit == 0;
+        self.remote_bits |= bit;
+        fresh
+    }
+
+    fn process_acks(&mut self, now: Instant, ack: u16, ack_bits: u32)
+    {
+        let mut rtt_samples = Vec::new();
+        for packet in &mut self.sent {
+            let age = ack.wrapping_sub(packet.sequence);
+            let acked = age == 0
+                || age <= ACK_BITS && ack_bits >> (age - 1) & 1 != 0;
+            if !acked || packet.acked {
+                continue;
+            }
+            packet.acked = true;
+            rtt_samples.push(now.saturating_duration_since(packet.time));
+            for (channel, message_id, index) in packet.fragments.drain(..) {
+                self.outgoing[channel.index()].fragments.retain(|fragment| {
+                    (fragment.message_id, fragment.index) != (message_id, index)
+                });
+            }
+        }
+
+        for sample in rtt_samples {
+            self.update_rtt(sample);
+        }
+
+        // Packets outside the acknowledged range will never be acknowledged.
+        while let Some(packet) = self.sent.front() {
+            let age = ack.wrapping_sub(packet.sequence);
+            let out_of_range = ACK_BITS < age && age < 0x8000;
+            if !packet.acked && !out_of_range {
+                break;
+            }
+            if !packet.acked {
+                self.stats.packets_lost += 1;
+            }
+            self.sent.pop_front();
+        }
+    }
+
+    /// Update the round-trip time estimate as in RFC 6298.
+    fn update_rtt(&mut self, sample: Duration)
+    {
+        let stats = &mut self.stats;
+        match stats.rtt {
+            None => {
+                stats.rtt = Some(sample);
+                stats.rtt_variation = sample / 2;
+            },
+            Some(rtt) => {
+                let difference = rtt.max(sample) - rtt.min(sample);
+                stats.rtt_variation =
+                    stats.rtt_variation * 3 / 4 + difference / 4;
+                stats.rtt = Some(rtt * 7 / 8 + sample / 8);
+            },
+        }
+    }
+
+    /// Time after which an unacknowledged fragment is resent.
+    fn rto(&self) -> Duration
+    {
+        match self.stats.rtt {
+            Some(rtt) => {
+                let rto = rtt + self.stats.rtt_variation * 4;
+                rto.max(MIN_RTO).min(MAX_RTO)
+            },
+            None => INITIAL_RTO,
+        }
+    }
+
+    fn receive_fragment(&mut self, fragment: &Fragment)
+    {
+        let channel = fragment.channel;
+        let incoming = &mut self.incoming[channel.index()];
+        let message_id = fragment.message_id;
+
+        let size = fragment.count as usize * FRAGMENT_SIZE;
+        if fragment.data.len() > FRAGMENT_SIZE
+            || size > MAX_MESSAGE_SIZE + FRAGMENT_SIZE
+        {
+            return;
+        }
+
+        if channel.is_reliable() {
+            // Ignore messages that were delivered already.
+            let offset = message_id.wrapping_sub(incoming.next_message_id);
+            if offset >= RELIABLE_WINDOW {
+                return;
+            }
+        } else {
+            let age = incoming.next_message_id.wrapping_sub(message_id);
+            if age == 0 || age >= 0x8000 {
+                // Newer than any message seen; forget old partial messages.
+                let next = message_id.wrapping_add(1);
+                incoming.next_message_id = next;
+                incoming.assemblies.retain(|&id, _| {
+                    next.wrapping_sub(id) <= UNRELIABLE_WINDOW
+                });
+            } else if age > UNRELIABLE_WINDOW {
+                return;
+            }
+        }
+
+        let assembly =
+            incoming.assemblies.entry(message_id)
+            .or_insert_with(|| Assembly{
+                fragments: vec![None; fragment.count as usize],
+                missing: fragment.count as usize,
+            });
+        if assembly.fragments.len() != fragment.count as usize {
+            return;
+        }
+        let slot = &mut assembly.fragments[fragment.index as usize];
+        if slot.is_none() {
+            *slot = Some(fragment.data.to_vec());
+            assembly.missing -= 1;
+        }
+
+        if channel.is_reliable() {
+            // Deliver complete messages in order.
+            loop {
+                let next = incoming.next_message_id;
+                match incoming.assemblies.get(&next) {
+                    Some(assembly) if assembly.missing == 0 => (),
+                    _ => break,
+                }
+                let assembly = incoming.assemblies.remove(&next).unwrap();
+                self.delivered.push_back((channel, assembly.into_message()));
+                incoming.next_message_id = next.wrapping_add(1);
+            }
+        } else if assembly.missing == 0 {
+            let assembly = incoming.assemblies.remove(&message_id).unwrap();
+            self.delivered.push_back((channel, assembly.into_message()));
+        }
+    }
+}
+
+impl Assembly
+{
+    fn into_message(self) -> Vec<u8>
+    {
+        self.fragments.into_iter().flatten().flatten().collect()
+    }
+}
+
+/// Whether sequence number `a` is newer than `b`, modulo wraparound.
+fn sequence_greater(a: u16, b: u16) -> bool
+{
+    a != b && a.wrapping_sub(b) < 0x8000
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    /// One direction of a link with a fixed latency.
+    struct Pipe
+    {
+        latency: Duration,
+        in_flight: VecDeque<(Instant, Vec<u8>)>,
+        sent: u64,
+    }
+
+    impl Pipe
+    {
+        fn new(latency: Duration) -> Self
+        {
+            Self{latency, in_flight: VecDeque::new(), sent: 0}
+        }
+
+        /// Send the due packets of one connection, dropping every `drop`th,
+        /// and deliver the packets that arrived to the other.
+        fn transfer(
+            &mut self,
+            now: Instant,
+            drop: u64,
+            from: &mut Connection,
+            to: &mut Connection,
+        )
+        {
+            for packet in from.write_packets(now) {
+                self.sent += 1;
+                if self.sent % drop != 0 {
+                    self.in_flight.push_back((now + self.latency, packet));
+                }
+            }
+            while matches!(self.in_flight.front(), Some(&(t, _)) if t <= now) {
+                let (_, bytes) = self.in_flight.pop_front().unwrap();
+                match Packet::decode(&bytes).unwrap() {
+                    Packet::Payload{header, fragments, ..} =>
+                        to.process(now, header, &fragments),
+                    packet => panic!("Unexpected {:?}", packet),
+                }
+            }
+        }
+    }
+
+    fn run(
+        a: &mut Connection,
+        b: &mut Connection,
+        latency: Duration,
+        drop: u64,
+        start: Instant,
+        steps: u32,
+    )
+    {
+        let mut ab = Pipe::new(latency);
+        let mut ba = Pipe::new(latency);
+        for step in 0 .. steps {
+            let now = start + Duration::from_millis(10) * step;
+            ab.transfer(now, drop, a, b);
+            ba.transfer(now, drop, b, a);
+        }
+    }
+
+    #[test]
+    fn sequence_numbers_wrap()
+    {
+        assert!(sequence_greater(1, 0));
+        assert!(sequence_greater(0, 65535));
+        assert!(!sequence_greater(65535, 0));
+        assert!(!sequence_greater(7, 7));
+    }
+
+    #[test]
+    fn reliable_messages_arrive_in_order_despite_loss()
+    {
+        let start = Instant::now();
+        let mut a = Connection::new(1, start);
+        let mut b = Connection::new(1, start);
+
+        let chunk: Vec<u8> = (0 .. 100_000).map(|i| (i * 7) as u8).collect();
+        a.send(Channel::Chunks, &chunk).unwrap();
+        for i in 0 .. 20u8 {
+            a.send(Channel::Chat, &[i]).unwrap();
+        }
+        a.send(Channel::Chat, &[]).unwrap();
+
+        // Every third packet is lost.
+        let latency = Duration::from_millis(20);
+        run(&mut a, &mut b, latency, 3, start, 300);
+
+        let mut chat = Vec::new();
+        let mut chunks = Vec::new();
+        while let Some((channel, message)) = b.receive() {
+            match channel {
+                Channel::Chat => chat.push(message),
+                Channel::Chunks => chunks.push(message),
+                Channel::Unreliable => panic!("Unexpected unreliable message"),
+            }
+        }
+        let mut expected: Vec<Vec<u8>> = (0 .. 20).map(|i| vec![i]).collect();
+        expected.push(vec![]);
+        assert_eq!(chat, expected);
+        assert_eq!(chunks, [chunk]);
+        assert!(a.stats().fragments_resent > 0);
+        assert!(a.stats().packets_lost > 0);
+    }
+
+    #[test]
+    fn unreliable_messages_are_not_resent()
+    {
+        let start = Instant::now();
+        let mut a = Connection::new(1, start);
+        let mut b = Connection::new(1, start);
+        for i in 0 .. 10u8 {
+            a.send(Channel::Unreliable, &[i; 2000]).unwrap();
+            run(&mut a, &mut b, Duration::ZERO, 2, start, 1);
+        }
+        run(&mut a, &mut b, Duration::ZERO, 2, start, 100);
+
+        // Each message spans two packets, one of which is lost.
+        assert_eq!(b.receive(), None);
+        assert_eq!(a.stats().fragments_resent, 0);
+    }
+
+    #[test]
+    fn round_trip_time_is_estimated()
+    {
+        let start = Instant::now();
+        let mut a = Connection::new(1, start);
+        let mut b = Connection::new(1, start);
+        let latency = Duration::from_millis(40);
+        let mut ab = Pipe::new(latency);
+        let mut ba = Pipe::new(latency);
+        for step in 0 .. 100 {
+            a.send(Channel::Unreliable, b"input").unwrap();
+            b.send(Channel::Unreliable, b"snapshot").unwrap();
+            let now = start + Duration::from_millis(10) * step;
+            ab.transfer(now, u64::MAX, &mut a, &mut b);
+            ba.transfer(now, u64::MAX, &mut b, &mut a);
+        }
+
+        let rtt = a.stats().rtt.unwrap();
+        assert!(rtt >= latency * 2 && rtt < latency * 2 + latency / 2,
+                "RTT is {:?}", rtt);
+        assert_eq!(a.stats().packets_lost, 0);
+    }
+}
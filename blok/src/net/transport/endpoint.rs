@@ -0,0 +1,521 @@
+use super::{
+    Connection,
+    MAX_PACKET_SIZE,
+    Socket,
+    packet::{Fragment, Packet, PayloadHeader},
+};
+use anyhow::{Result, bail};
+use std::{
+    collections::{BTreeMap, HashMap, VecDeque, hash_map::RandomState},
+    hash::{BuildHasher, Hasher},
+    net::SocketAddr,
+    time::{Duration, Instant, SystemTime},
+};
+
+/// Time after which an unanswered connection request fails.
+pub const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Interval at which connection requests are repeated.
+const REQUEST_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Number of times a disconnect packet is sent,
+/// as it is not acknowledged.
+const DISCONNECT_REPEATS: usize = 3;
+
+/// Size of receive buffers.
+///
+/// This exceeds the maximum packet size,
+/// so that oversized datagrams are detected rather than truncated.
+const RECEIVE_BUFFER_SIZE: usize = 2 * MAX_PACKET_SIZE;
+
+/// Client side of a connection.
+pub struct Client<S>
+{
+    socket: S,
+    server: SocketAddr,
+    client_salt: u64,
+    state: ClientState,
+}
+
+enum ClientState
+{
+    Connecting{since: Instant, last_request: Option<Instant>},
+    Connected(Box<Connection>),
+    Disconnected,
+}
+
+impl<S> Client<S>
+    where S: Socket
+{
+    /// Start connecting to a server.
+    ///
+    /// The handshake happens during [`update`](Self::update).
+    pub fn connect(socket: S, server: SocketAddr, now: Instant) -> Self
+    {
+        Self{
+            socket,
+            server,
+            client_salt: random_salt(),
+            state: ClientState::Connecting{since: now, last_request: None},
+        }
+    }
+
+    /// The socket through which the client communicates.
+    pub fn socket(&self) -> &S
+    {
+        &self.socket
+    }
+
+    /// Whether the handshake completed and the connection was not lost.
+    pub fn is_connected(&self) -> bool
+    {
+        matches!(self.state, ClientState::Connected(_))
+    }
+
+    /// The connection to the server, once connected.
+    pub fn connection(&mut self) -> Option<&mut Connection>
+    {
+        match &mut self.state {
+            ClientState::Connected(connection) => Some(connection.as_mut()),
+            _ => None,
+        }
+    }
+
+    /// Receive and send packets.
+    ///
+    /// This must be called regularly, typically once per tick.
+    /// This fails if connecting timed out, the connection was lost,
+    /// or the server closed the connection,
+    /// after which the client is disconnected.
+    pub fn update(&mut self, now: Instant) -> Result<()>
+    {
+        let result = self.update_inner(now);
+        if result.is_err() {
+            self.state = ClientState::Disconnected;
+        }
+        result
+    }
+
+    fn update_inner(&mut self, now: Instant) -> Result<()>
+    {
+        let mut buffer = [0; RECEIVE_BUFFER_SIZE];
+        while let Some((size, address)) =
+            self.socket.receive_from(&mut buffer)?
+        {
+            if address != self.server || size > MAX_PACKET_SIZE {
+                continue;
+            }
+            if let Ok(packet) = Packet::decode(&buffer[.. size]) {
+                self.handle_packet(now, packet)?;
+            }
+        }
+
+        match &mut self.state {
+            ClientState::Connecting{since, last_request} => {
+                if now.saturating_duration_since(*since) > CONNECT_TIMEOUT {
+                    bail!("Connecting to {} timed out", self.server);
+                }
+                let due = !matches!(
+                    *last_request,
+                    Some(last) if now < last + REQUEST_INTERVAL,
+                );
+                if due {
+                    *last_request = Some(now);
+                    let client_salt = self.client_salt;
+                    let request = Packet::Request{client_salt};
+                    self.socket.send_to(&request.encode(), self.server)?;
+                }
+            },
+            ClientState::Connected(connection) => {
+                if connection.is_timed_out(now) {
+                    bail!("Connection to {} timed out", self.server);
+                }
+                for packet in connection.write_packets(now) {
+                    self.socket.send_to(&packet, self.server)?;
+                }
+            },
+            ClientState::Disconnected => (),
+        }
+
+        Ok(())
+    }
+
+    fn handle_packet(&mut self, now: Instant, packet: Packet) -> Result<()>
+    {
+        match (&mut self.state, packet) {
+            (
+                ClientState::Connecting{..},
+                Packet::Accept{client_salt, server_salt},
+            ) if client_salt == self.client_salt => {
+                let session = client_salt ^ server_salt;
+                let connection = Connection::new(session, now);
+                self.state = ClientState::Connected(Box::new(connection));
+            },
+            (
+                ClientState::Connected(connection),
+                Packet::Payload{session, header, fragments},
+            ) if session == connection.session() => {
+                connection.process(now, header, &fragments);
+            },
+            (
+                ClientState::Connected(connection),
+                Packet::Disconnect{session},
+            ) if session == connection.session() => {
+                bail!("Server {} closed the connection", self.server);
+            },
+            _ => (),
+        }
+        Ok(())
+    }
+
+    /// Close the connection.
+    ///
+    /// The server is notified, unless the notification is lost,
+    /// in which case the connection times out on the server.
+    /// With a [`SimulatedSocket`](super::SimulatedSocket),
+    /// keep updating the client for the notification to be sent.
+    pub fn disconnect(&mut self) -> Result<()>
+    {
+        if let ClientState::Connected(connection) = &self.state {
+            let packet = Packet::Disconnect{session: connection.session()};
+            let packet = packet.encode();
+            for _ in 0 .. DISCONNECT_REPEATS {
+                self.socket.send_to(&packet, self.server)?;
+            }
+        }
+        self.state = ClientState::Disconnected;
+        Ok(())
+    }
+}
+
+/// Change in the set of clients of a server.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ServerEvent
+{
+    /// A client completed the handshake.
+    Connected(SocketAddr),
+
+    /// A client closed the connection or timed out.
+    Disconnected(SocketAddr),
+}
+
+/// Server side of the connections with clients.
+pub struct Server<S>
+{
+    socket: S,
+    max_clients: usize,
+
+    // INVARIANT: No address is both pending and connected.
+    pending: HashMap<SocketAddr, PendingClient>,
+    connections: BTreeMap<SocketAddr, (u64, Connection)>,
+
+    events: VecDeque<ServerEvent>,
+}
+
+/// Client whose request was accepted, but which has not sent
+/// a packet with the session identifier yet.
+struct PendingClient
+{
+    client_salt: u64,
+    server_salt: u64,
+    since: Instant,
+}
+
+impl<S> Server<S>
+    where S: Socket
+{
+    /// Create a server that accepts up to `max_clients` clients.
+    pub fn new(socket: S, max_clients: usize) -> Self
+    {
+        Self{
+            socket,
+            max_clients,
+            pending: HashMap::new(),
+            connections: BTreeMap::new(),
+            events: VecDeque::new(),
+        }
+    }
+
+    /// The socket through which the server communicates.
+    pub fn socket(&self) -> &S
+    {
+        &self.socket
+    }
+
+    /// Take the next change in the set of clients, if any.
+    pub fn poll_event(&mut self) -> Option<ServerEvent>
+    {
+        self.events.pop_front()
+    }
+
+    /// The addresses of the connected clients.
+    pub fn clients(&self) -> impl Iterator<Item=SocketAddr> + '_
+    {
+        self.connections.keys().copied()
+    }
+
+    /// The connection with a client.
+    pub fn connection(&mut self, client: SocketAddr) -> Option<&mut Connection>
+    {
+        self.connections.get_mut(&client).map(|(_, connection)| connection)
+    }
+
+    /// Receive and send packets.
+    ///
+    /// This must be called regularly, typically once per tick.
+    /// This only fails if the socket fails.
+    pub fn update(&mut self, now: Instant) -> Result<()>
+    {
+        let mut buffer = [0; RECEIVE_BUFFER_SIZE];
+        while let Some((size, address)) =
+            self.socket.receive_from(&mut buffer)?
+        {
+            if size > MAX_PACKET_SIZE {
+                continue;
+            }
+            if let Ok(packet) = Packet::decode(&buffer[.. size]) {
+                self.handle_packet(now, address, packet)?;
+            }
+        }
+
+        self.pending.retain(|_, pending| {
+            now.saturating_duration_since(pending.since) <= CONNECT_TIMEOUT
+        });
+
+        let timed_out: Vec<SocketAddr> =
+            self.connections.iter()
+            .filter(|(_, (_, connection))| connection.is_timed_out(now))
+            .map(|(&address, _)| address)
+            .collect();
+        for address in timed_out {
+            self.connections.remove(&address);
+            self.events.push_back(ServerEvent::Disconnected(address));
+        }
+
+        for (&address, (_, connection)) in &mut self.connections {
+            for packet in connection.write_packets(now) {
+                self.socket.send_to(&packet, address)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn handle_packet(
+        &mut self,
+        now: Instant,
+        address: SocketAddr,
+        packet: Packet,
+    ) -> Result<()>
+    {
+        match packet {
+            Packet::Request{client_salt} =>
+                self.handle_request(now, address, client_salt)?,
+            Packet::Payload{session, header, fragments} =>
+                self.handle_payload(now, address, session, header, &fragments),
+            Packet::Disconnect{session} => {
+                let matches = matches!(
+                    self.connections.get(&address),
+                    Some((_, c)) if c.session() == session,
+                );
+                if matches {
+                    self.connections.remove(&address);
+                    self.events.push_back(ServerEvent::Disconnected(address));
+                }
+            },
+            Packet::Accept{..} => (),
+        }
+        Ok(())
+    }
+
+    fn handle_request(
+        &mut self,
+        now: Instant,
+        address: SocketAddr,
+        client_salt: u64,
+    ) -> Result<()>
+    {
+        if let Some(&(connected_salt, _)) = self.connections.get(&address) {
+            // A late duplicate of the request that led to the connection.
+            if connected_salt == client_salt {
+                return Ok(());
+            }
+            // The client restarted and lost its connection.
+            self.connections.remove(&address);
+            self.events.push_back(ServerEvent::Disconnected(address));
+        }
+
+        let known = self.pending.contains_key(&address);
+        if !known && self.pending.len() + self.connections.len()
+            >= self.max_clients
+        {
+            return Ok(());
+        }
+
+        let pending = self.pending.entry(address).or_insert(PendingClient{
+            client_salt,
+            server_salt: random_salt(),
+            since: now,
+        });
+        if pending.client_salt != client_salt {
+            *pending = PendingClient{
+                client_salt,
+                server_salt: random_salt(),
+                since: now,
+            };
+        }
+
+        let accept = Packet::Accept{
+            client_salt,
+            server_salt: pending.server_salt,
+        };
+        self.socket.send_to(&accept.encode(), address)
+    }
+
+    fn handle_payload(
+        &mut self,
+        now: Instant,
+        address: SocketAddr,
+        session: u64,
+        header: PayloadHeader,
+        fragments: &[Fragment],
+    )
+    {
+        // The first payload from a pending client completes the handshake.
+        let completes = matches!(
+            self.pending.get(&address),
+            Some(p) if p.client_salt ^ p.server_salt == session,
+        );
+        if completes {
+            let pending = self.pending.remove(&address).unwrap();
+            let connection = Connection::new(session, now);
+            self.connections.insert(address, (pending.client_salt, connection));
+            self.events.push_back(ServerEvent::Connected(address));
+        }
+
+        if let Some((_, connection)) = self.connections.get_mut(&address) {
+            if connection.session() == session {
+                connection.process(now, header, fragments);
+            }
+        }
+    }
+
+    /// Close the connection with a client.
+    ///
+    /// No [`ServerEvent::Disconnected`] is emitted for this.
+    pub fn disconnect(&mut self, client: SocketAddr) -> Result<()>
+    {
+        if let Some((_, connection)) = self.connections.remove(&client) {
+            let packet = Packet::Disconnect{session: connection.session()};
+            let packet = packet.encode();
+            for _ in 0 .. DISCONNECT_REPEATS {
+                self.socket.send_to(&packet, client)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Unpredictable number for use in the handshake.
+fn random_salt() -> u64
+{
+    let mut hasher = RandomState::new().build_hasher();
+    if let Ok(time) = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH)
+    {
+        hasher.write_u128(time.as_nanos());
+    }
+    hasher.finish()
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+    use crate::net::transport::{
+        Channel,
+        LinkConditions,
+        SimulatedSocket,
+        bind_udp,
+    };
+    use std::{net::UdpSocket, thread};
+
+    type TestSocket = SimulatedSocket<UdpSocket>;
+
+    /// Update both endpoints until the condition holds.
+    fn run_until<F>(
+        client: &mut Client<TestSocket>,
+        server: &mut Server<TestSocket>,
+        mut condition: F,
+    )
+        where F: FnMut(&mut Client<TestSocket>, &mut Server<TestSocket>) -> bool
+    {
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while !condition(client, server) {
+            assert!(Instant::now() < deadline, "Condition did not hold");
+            let now = Instant::now();
+            client.update(now).unwrap();
+            server.update(now).unwrap();
+            thread::sleep(Duration::from_millis(2));
+        }
+    }
+
+    #[test]
+    fn localhost_with_simulated_loss_and_latency()
+    {
+        let conditions = LinkConditions{
+            latency: Duration::from_millis(20),
+            jitter: Duration::from_millis(10),
+            loss: 0.2,
+            ..LinkConditions::PERFECT
+        };
+        let server_socket = bind_udp("127.0.0.1:0").unwrap();
+        let server_address = server_socket.local_addr().unwrap();
+        let server_socket = SimulatedSocket::new(server_socket, conditions, 1);
+        let client_socket = bind_udp("127.0.0.1:0").unwrap();
+        let client_address = client_socket.local_addr().unwrap();
+        let client_socket = SimulatedSocket::new(client_socket, conditions, 2);
+
+        let mut server = Server::new(server_socket, 4);
+        let mut client =
+            Client::connect(client_socket, server_address, Instant::now());
+
+        let mut events = Vec::new();
+        run_until(&mut client, &mut server, |client, server| {
+            events.extend(server.poll_event());
+            client.is_connected() && !events.is_empty()
+        });
+        assert_eq!(events, [ServerEvent::Connected(client_address)]);
+
+        let chunk: Vec<u8> = (0 .. 50_000).map(|i| (i % 251) as u8).collect();
+        server.connection(client_address).unwrap()
+            .send(Channel::Chunks, &chunk).unwrap();
+        for i in 0 .. 10u8 {
+            client.connection().unwrap().send(Channel::Chat, &[i]).unwrap();
+        }
+
+        let mut chat = Vec::new();
+        let mut chunks = Vec::new();
+        run_until(&mut client, &mut server, |client, server| {
+            let from_client = server.connection(client_address).unwrap();
+            while let Some((_, message)) = from_client.receive() {
+                chat.push(message);
+            }
+            let from_server = client.connection().unwrap();
+            while let Some((_, message)) = from_server.receive() {
+                chunks.push(message);
+            }
+            chat.len() == 10 && chunks.len() == 1
+        });
+        let expected: Vec<Vec<u8>> = (0 .. 10).map(|i| vec![i]).collect();
+        assert_eq!(chat, expected);
+        assert_eq!(chunks, [chunk]);
+
+        client.disconnect().unwrap();
+        let mut events = Vec::new();
+        run_until(&mut client, &mut server, |_, server| {
+            events.extend(server.poll_event());
+            !events.is_empty()
+        });
+        assert_eq!(events, [ServerEvent::Disconnected(client_address)]);
+    }
+}
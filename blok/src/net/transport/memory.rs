@@ -0,0 +1,83 @@
+use super::Socket;
+use anyhow::Result;
+use std::{
+    net::{Ipv4Addr, SocketAddr},
+    sync::mpsc::{Receiver, Sender, TryRecvError, channel},
+};
+
+/// Socket that exchanges datagrams with a single peer through memory.
+///
+/// This is for running the server in the same process as the client,
+/// possibly on another thread, using the same transport as over UDP.
+/// Datagrams are neither lost nor reordered.
+/// The addresses of the sockets are made up,
+/// and datagrams sent to any other address are discarded.
+pub struct MemorySocket
+{
+    address: SocketAddr,
+    peer: SocketAddr,
+    sender: Sender<Vec<u8>>,
+    receiver: Receiver<Vec<u8>>,
+}
+
+impl MemorySocket
+{
+    /// Create two sockets that are each other's peer.
+    pub fn pair() -> (Self, Self)
+    {
+        let a_address = SocketAddr::from((Ipv4Addr::LOCALHOST, 1));
+        let b_address = SocketAddr::from((Ipv4Addr::LOCALHOST, 2));
+        let (a_sender, b_receiver) = channel();
+        let (b_sender, a_receiver) = channel();
+        let a = Self{
+            address: a_address,
+            peer: b_address,
+            sender: a_sender,
+            receiver: a_receiver,
+        };
+        let b = Self{
+            address: b_address,
+            peer: a_address,
+            sender: b_sender,
+            receiver: b_receiver,
+        };
+        (a, b)
+    }
+
+    /// The address of this socket.
+    pub fn local_addr(&self) -> SocketAddr
+    {
+        self.address
+    }
+
+    /// The address of the peer.
+    pub fn peer_addr(&self) -> SocketAddr
+    {
+        self.peer
+    }
+}
+
+impl Socket for MemorySocket
+{
+    fn send_to(&mut self, datagram: &[u8], address: SocketAddr) -> Result<()>
+    {
+        // If the peer was dropped, the datagram is lost, as over UDP.
+        if address == self.peer {
+            let _ = self.sender.send(datagram.to_vec());
+        }
+        Ok(())
+    }
+
+    fn receive_from(&mut self, buffer: &mut [u8])
+        -> Result<Option<(usize, SocketAddr)>>
+    {
+        match self.receiver.try_recv() {
+            Ok(datagram) => {
+                let size = datagram.len().min(buffer.len());
+                buffer[.. size].copy_from_slice(&datagram[.. size]);
+                Ok(Some((size, self.peer)))
+            },
+            Err(TryRecvError::Empty | TryRecvError::Disconnected) => Ok(None),
+        }
+    }
+}
@@ -0,0 +1,88 @@
+//! Delivery of messages between server and clients over UDP.
+//!
+//! A client connects to a server with a handshake:
+//! it sends a connection request with a random salt,
+//! the server replies with the client salt and a random salt of its own,
+//! and both sides combine the salts into a session identifier
+//! that is included in every later packet.
+//! Packets with a wrong session identifier are ignored,
+//! which protects against stray packets from earlier connections.
+//!
+//! Each packet carries a sequence number and acknowledges
+//! the latest packet received from the other side,
+//! as well as the 32 packets before it, in a bit field.
+//! Acknowledgements are used for estimating the round-trip time
+//! and for resending fragments of reliable messages that were lost.
+//!
+//! Messages are sent on channels, see [`Channel`].
+//! Messages larger than a packet are split into fragments,
+//! which the receiver reassembles.
+//!
+//! The transport does not do IO itself;
+//! it sends and receives datagrams through a [`Socket`],
+//! which is a UDP socket, a [`MemorySocket`] for a server
+//! in the same process, or a [`SimulatedSocket`]
+//! that delays, drops, duplicates and reorders datagrams for testing.
+
+pub use self::connection::*;
+pub use self::endpoint::*;
+pub use self::memory::*;
+pub use self::simulation::*;
+pub use self::socket::*;
+
+mod connection;
+mod endpoint;
+mod memory;
+mod packet;
+mod simulation;
+mod socket;
+
+/// Maximum size of a datagram, in bytes.
+///
+/// This stays below the path MTU of virtually all networks,
+/// so that datagrams are not fragmented by IP.
+pub const MAX_PACKET_SIZE: usize = 1200;
+
+/// Maximum size of a message, in bytes.
+pub const MAX_MESSAGE_SIZE: usize = 4 << 20;
+
+/// Channel on which a message is sent.
+///
+/// Each reliable channel delivers its messages in order,
+/// independently of the other channels,
+/// so that a large chunk does not hold up chat messages.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum Channel
+{
+    /// Messages that are not resent and may arrive out of order,
+    /// such as input and snapshots, which are soon outdated anyway.
+    Unreliable,
+
+    /// Chat messages, delivered reliably and in order.
+    Chat,
+
+    /// Chunk data, delivered reliably and in order.
+    Chunks,
+}
+
+impl Channel
+{
+    /// All channels, in the order in which their messages are sent.
+    pub const ALL: [Self; 3] = [Self::Unreliable, Self::Chat, Self::Chunks];
+
+    /// Whether messages on this channel are resent until acknowledged.
+    pub fn is_reliable(self) -> bool
+    {
+        self != Self::Unreliable
+    }
+
+    fn index(self) -> usize
+    {
+        self as usize
+    }
+
+    fn from_index(index: u8) -> Option<Self>
+    {
+        Self::ALL.get(index as usize).copied()
+    }
+}
@@ -0,0 +1,249 @@
+use super::Channel;
+use anyhow::{Result, anyhow, bail, ensure};
+use std::convert::TryInto;
+
+/// Identifies the protocol, so that unrelated datagrams are ignored.
+pub const PROTOCOL_ID: u32 = 0x426C_6F6B;
+
+/// Size of connection requests, in bytes.
+///
+/// Requests are padded to be no smaller than the replies,
+/// so that the server cannot be used to amplify traffic
+/// towards a spoofed address.
+pub const REQUEST_SIZE: usize = 64;
+
+/// Size of the payload packet header, in bytes.
+pub const PAYLOAD_HEADER_SIZE: usize = 17;
+
+/// Size of the header of each fragment, in bytes.
+pub const FRAGMENT_HEADER_SIZE: usize = 9;
+
+const KIND_REQUEST: u8 = 0;
+const KIND_ACCEPT: u8 = 1;
+const KIND_PAYLOAD: u8 = 2;
+const KIND_DISCONNECT: u8 = 3;
+
+/// Datagram exchanged between client and server.
+#[derive(Debug, PartialEq)]
+pub enum Packet<'a>
+{
+    /// Client asks to connect.
+    Request{client_salt: u64},
+
+    /// Server accepts a connection request.
+    Accept{client_salt: u64, server_salt: u64},
+
+    /// Data and acknowledgements of an established connection.
+    Payload{session: u64, header: PayloadHeader, fragments: Vec<Fragment<'a>>},
+
+    /// Either side closes the connection.
+    Disconnect{session: u64},
+}
+
+/// Sequencing information of a payload packet.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PayloadHeader
+{
+    /// Sequence number of the packet.
+    pub sequence: u16,
+
+    /// Sequence number of the latest packet received.
+    pub ack: u16,
+
+    /// Which of the 32 packets before `ack` were received,
+    /// with the least significant bit for the packet right before.
+    pub ack_bits: u32,
+}
+
+/// Part of a message.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Fragment<'a>
+{
+    pub channel: Channel,
+    pub message_id: u16,
+    pub index: u16,
+    pub count: u16,
+    pub data: &'a [u8],
+}
+
+impl<'a> Packet<'a>
+{
+    /// Encode the packet as a datagram.
+    pub fn encode(&self) -> Vec<u8>
+    {
+        let mut bytes = Vec::new();
+        match self {
+            Self::Request{client_salt} => {
+                bytes.push(KIND_REQUEST);
+                bytes.extend(PROTOCOL_ID.to_be_bytes());
+                bytes.extend(client_salt.to_be_bytes());
+                bytes.resize(REQUEST_SIZE, 0);
+            },
+            Self::Accept{client_salt, server_salt} => {
+                bytes.push(KIND_ACCEPT);
+                bytes.extend(client_salt.to_be_bytes());
+                bytes.extend(server_salt.to_be_bytes());
+            },
+            Self::Payload{session, header, fragments} => {
+                bytes.push(KIND_PAYLOAD);
+                bytes.extend(session.to_be_bytes());
+                bytes.extend(header.sequence.to_be_bytes());
+                bytes.extend(header.ack.to_be_bytes());
+                bytes.extend(header.ack_bits.to_be_bytes());
+                for fragment in fragments {
+                    bytes.push(fragment.channel.index() as u8);
+                    bytes.extend(fragment.message_id.to_be_bytes());
+                    bytes.extend(fragment.index.to_be_bytes());
+                    bytes.extend(fragment.count.to_be_bytes());
+                    bytes.extend((fragment.data.len() as u16).to_be_bytes());
+                    bytes.extend(fragment.data);
+                }
+            },
+            Self::Disconnect{session} => {
+                bytes.push(KIND_DISCONNECT);
+                bytes.extend(session.to_be_bytes());
+            },
+        }
+        bytes
+    }
+
+    /// Decode a datagram.
+    pub fn decode(bytes: &'a [u8]) -> Result<Self>
+    {
+        let mut reader = ByteReader{bytes};
+        let packet = match reader.read_u8()? {
+            KIND_REQUEST => {
+                ensure!(bytes.len() >= REQUEST_SIZE, "Request is too short");
+                ensure!(reader.read_u32()? == PROTOCOL_ID,
+                        "Request is for a different protocol");
+                let client_salt = reader.read_u64()?;
+                reader.bytes = &[];
+                Self::Request{client_salt}
+            },
+            KIND_ACCEPT => Self::Accept{
+                client_salt: reader.read_u64()?,
+                server_salt: reader.read_u64()?,
+            },
+            KIND_PAYLOAD => {
+                let session = reader.read_u64()?;
+                let header = PayloadHeader{
+                    sequence: reader.read_u16()?,
+                    ack: reader.read_u16()?,
+                    ack_bits: reader.read_u32()?,
+                };
+                let mut fragments = Vec::new();
+                while !reader.bytes.is_empty() {
+                    fragments.push(reader.read_fragment()?);
+                }
+                Self::Payload{session, header, fragments}
+            },
+            KIND_DISCONNECT => Self::Disconnect{session: reader.read_u64()?},
+            kind => bail!("Unknown packet kind {}", kind),
+        };
+        ensure!(reader.bytes.is_empty(), "Trailing bytes after packet");
+        Ok(packet)
+    }
+}
+
+struct ByteReader<'a>
+{
+    bytes: &'a [u8],
+}
+
+impl<'a> ByteReader<'a>
+{
+    fn read_slice(&mut self, len: usize) -> Result<&'a [u8]>
+    {
+        ensure!(self.bytes.len() >= len, "Unexpected end of packet");
+        let (slice, rest) = self.bytes.split_at(len);
+        self.bytes = rest;
+        Ok(slice)
+    }
+
+    fn read_u8(&mut self) -> Result<u8>
+    {
+        Ok(self.read_slice(1)?[0])
+    }
+
+    fn read_u16(&mut self) -> Result<u16>
+    {
+        Ok(u16::from_be_bytes(self.read_slice(2)?.try_into().unwrap()))
+    }
+
+    fn read_u32(&mut self) -> Result<u32>
+    {
+        Ok(u32::from_be_bytes(self.read_slice(4)?.try_into().unwrap()))
+    }
+
+    fn read_u64(&mut self) -> Result<u64>
+    {
+        Ok(u64::from_be_bytes(self.read_slice(8)?.try_into().unwrap()))
+    }
+
+    fn read_fragment(&mut self) -> Result<Fragment<'a>>
+    {
+        let channel = self.read_u8()?;
+        let channel = Channel::from_index(channel)
+            .ok_or_else(|| anyhow!("Unknown channel {}", channel))?;
+        let message_id = self.read_u16()?;
+        let index = self.read_u16()?;
+        let count = self.read_u16()?;
+        ensure!(index < count, "Fragment index out of range");
+        let len = self.read_u16()?;
+        let data = self.read_slice(len as usize)?;
+        Ok(Fragment{channel, message_id, index, count, data})
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    #[test]
+    fn round_trip()
+    {
+        let packets = [
+            Packet::Request{client_salt: 7},
+            Packet::Accept{client_salt: 7, server_salt: 9},
+            Packet::Payload{
+                session: 7 ^ 9,
+                header: PayloadHeader{sequence: 1, ack: 65535, ack_bits: 5},
+                fragments: vec![
+                    Fragment{
+                        channel: Channel::Chat,
+                        message_id: 3,
+                        index: 0,
+                        count: 1,
+                        data: b"hello",
+                    },
+                    Fragment{
+                        channel: Channel::Unreliable,
+                        message_id: 4,
+                        index: 1,
+                        count: 2,
+                        data: b"",
+                    },
+                ],
+            },
+            Packet::Disconnect{session: 7 ^ 9},
+        ];
+        for packet in packets {
+            let bytes = packet.encode();
+            assert_eq!(Packet::decode(&bytes).unwrap(), packet);
+        }
+        assert_eq!(Packet::Request{client_salt: 0}.encode().len(),
+                   REQUEST_SIZE);
+    }
+
+    #[test]
+    fn malformed_packets_are_rejected()
+    {
+        let mut request = Packet::Request{client_salt: 7}.encode();
+        request[1] ^= 1;
+        assert!(Packet::decode(&request).is_err());
+        assert!(Packet::decode(&[]).is_err());
+        assert!(Packet::decode(&[KIND_ACCEPT, 0, 0]).is_err());
+        assert!(Packet::decode(&[KIND_DISCONNECT; 10]).is_err());
+    }
+}
@@ -0,0 +1,337 @@
+use super::Socket;
+use anyhow::{Context, Error, Result, anyhow, ensure};
+use std::{
+    cmp::Reverse,
+    collections::BinaryHeap,
+    net::SocketAddr,
+    str::FromStr,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+/// Largest latency or jitter accepted when parsing conditions.
+const MAX_PARSED_DELAY: Duration = Duration::from_secs(60);
+
+/// Network conditions imposed by a [`SimulatedSocket`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct LinkConditions
+{
+    /// Delay of each datagram.
+    pub latency: Duration,
+
+    /// Maximum random deviation from the latency, either way.
+    ///
+    /// Datagrams are reordered when the deviation exceeds
+    /// the interval between them.
+    pub jitter: Duration,
+
+    /// Probability that a datagram is lost, from zero to one.
+    pub loss: f64,
+
+    /// Probability that a datagram is sent twice, from zero to one.
+    ///
+    /// The delay of the copy is drawn independently.
+    pub duplicate: f64,
+
+    /// Probability that a datagram is held back, from zero to one.
+    ///
+    /// A held back datagram is delayed by up to twice the latency,
+    /// so that it usually arrives after datagrams sent after it.
+    pub reorder: f64,
+}
+
+impl LinkConditions
+{
+    /// Conditions that do not affect datagrams at all.
+    pub const PERFECT: Self = Self{
+        latency: Duration::ZERO,
+        jitter: Duration::ZERO,
+        loss: 0.0,
+        duplicate: 0.0,
+        reorder: 0.0,
+    };
+}
+
+/// Parse conditions from comma-separated settings,
+/// such as `latency=100,jitter=20,loss=0.05`.
+///
+/// Durations are in milliseconds, up to a minute,
+/// and probabilities from zero to one.
+/// The settings are `latency`, `jitter`, `loss`, `duplicate` and `reorder`;
+/// settings that are left out do not affect datagrams.
+impl FromStr for LinkConditions
+{
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self>
+    {
+        let mut conditions = Self::PERFECT;
+        for setting in s.split(',').filter(|s| !s.is_empty()) {
+            let (name, value) = setting.split_once('=')
+                .ok_or_else(|| anyhow!("Expected NAME=VALUE: {}", setting))?;
+            let value: f64 = value.parse()
+                .with_context(|| format!("Parse value of {}", name))?;
+            ensure!(value.is_finite(), "Value of {} is not finite", name);
+            ensure!(value >= 0.0, "Negative value of {}", name);
+            let milliseconds = || {
+                let seconds = value / 1000.0;
+                ensure!(seconds <= MAX_PARSED_DELAY.as_secs_f64(),
+                        "{} exceeds {:?}", name, MAX_PARSED_DELAY);
+                Ok(Duration::from_secs_f64(seconds))
+            };
+            let probability = || {
+                ensure!(value <= 1.0, "Probability {} exceeds one", name);
+                Ok(value)
+            };
+            match name {
+                "latency"   => conditions.latency   = milliseconds()?,
+                "jitter"    => conditions.jitter    = milliseconds()?,
+                "loss"      => conditions.loss      = probability()?,
+                "duplicate" => conditions.duplicate = probability()?,
+                "reorder"   => conditions.reorder   = probability()?,
+                _ => return Err(anyhow!("Unknown link condition {}", name)),
+            }
+        }
+        Ok(conditions)
+    }
+}
+
+/// Clock that only advances when told to.
+///
+/// A [`SimulatedSocket`] that uses this clock delays datagrams
+/// in simulated time, so that tests need not sleep.
+/// Clones share the same time.
+#[derive(Clone, Debug)]
+pub struct SimulatedClock(Arc<Mutex<Instant>>);
+
+impl SimulatedClock
+{
+    /// Create a clock that starts at the given time.
+    pub fn new(now: Instant) -> Self
+    {
+        Self(Arc::new(Mutex::new(now)))
+    }
+
+    /// The current time.
+    pub fn now(&self) -> Instant
+    {
+        *self.0.lock().unwrap()
+    }
+
+    /// Move the clock forward.
+    pub fn advance(&self, duration: Duration)
+    {
+        *self.0.lock().unwrap() += duration;
+    }
+}
+
+/// Socket that delays, drops, duplicates and reorders datagrams it sends.
+///
+/// This is for testing on localhost under realistic conditions.
+/// Only outgoing datagrams are affected,
+/// so wrap the sockets of both sides to affect both directions.
+/// Delayed datagrams are sent when the socket is next used,
+/// so the socket must be used regularly.
+pub struct SimulatedSocket<S>
+{
+    inner: S,
+    conditions: LinkConditions,
+    random: Random,
+    clock: Option<SimulatedClock>,
+
+    // INVARIANT: Ordered by due time, then by the order of sending.
+    delayed: BinaryHeap<Reverse<Delayed>>,
+    sent: u64,
+}
+
+impl<S> SimulatedSocket<S>
+    where S: Socket
+{
+    /// Wrap a socket.
+    ///
+    /// The seed determines which datagrams are lost and by how much
+    /// they deviate from the latency, so that tests are reproducible.
+    pub fn new(inner: S, conditions: LinkConditions, seed: u64) -> Self
+    {
+        Self{
+            inner,
+            conditions,
+            random: Random(seed),
+            clock: None,
+            delayed: BinaryHeap::new(),
+            sent: 0,
+        }
+    }
+
+    /// Measure delays with a simulated clock rather than in real time.
+    pub fn with_clock(mut self, clock: SimulatedClock) -> Self
+    {
+        self.clock = Some(clock);
+        self
+    }
+
+    /// Change the conditions for datagrams sent from now on.
+    pub fn set_conditions(&mut self, conditions: LinkConditions)
+    {
+        self.conditions = conditions;
+    }
+
+    /// The wrapped socket.
+    pub fn inner(&self) -> &S
+    {
+        &self.inner
+    }
+
+    fn now(&self) -> Instant
+    {
+        match &self.clock {
+            Some(clock) => clock.now(),
+            None => Instant::now(),
+        }
+    }
+
+    /// Schedule a datagram with a random delay.
+    fn delay(&mut self, now: Instant, datagram: &[u8], address: SocketAddr)
+    {
+        let deviation = self.random.next_f64() * 2.0 - 1.0;
+        let jitter = self.conditions.jitter.as_secs_f64() * deviation;
+        let mut delay = self.conditions.latency.as_secs_f64() + jitter;
+        if self.random.next_f64() < self.conditions.reorder {
+            let latency = self.conditions.latency.as_secs_f64();
+            delay += latency * self.random.next_f64();
+        }
+        let due = now + Duration::from_secs_f64(delay.max(0.0));
+        let entry = (due, self.sent, address, datagram.to_vec());
+        self.delayed.push(Reverse(entry));
+        self.sent += 1;
+    }
+
+    /// Send the datagrams whose delay is over.
+    fn flush(&mut self) -> Result<()>
+    {
+        let now = self.now();
+        while let Some(Reverse((due, ..))) = self.delayed.peek() {
+            if *due > now {
+                break;
+            }
+            let Reverse((_, _, address, datagram)) =
+                self.delayed.pop().unwrap();
+            self.inner.send_to(&datagram, address)?;
+        }
+        Ok(())
+    }
+}
+
+impl<S> Socket for SimulatedSocket<S>
+    where S: Socket
+{
+    fn send_to(&mut self, datagram: &[u8], address: SocketAddr) -> Result<()>
+    {
+        let now = self.now();
+        if self.random.next_f64() >= self.conditions.loss {
+            self.delay(now, datagram, address);
+            if self.random.next_f64() < self.conditions.duplicate {
+                self.delay(now, datagram, address);
+            }
+        }
+        self.flush()
+    }
+
+    fn receive_from(&mut self, buffer: &mut [u8])
+        -> Result<Option<(usize, SocketAddr)>>
+    {
+        self.flush()?;
+        self.inner.receive_from(buffer)
+    }
+}
+
+/// Datagram to be sent at a later time,
+/// with its due time, its number in the order of sending, and its address.
+type Delayed = (Instant, u64, SocketAddr, Vec<u8>);
+
+/// Small pseudorandom number generator (SplitMix64).
+struct Random(u64);
+
+impl Random
+{
+    fn next_u64(&mut self) -> u64
+    {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ z >> 30).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ z >> 27).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ z >> 31
+    }
+
+    /// Uniformly distributed in [0, 1).
+    fn next_f64(&mut self) -> f64
+    {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+    use crate::net::transport::MemorySocket;
+
+    #[test]
+    fn parse_conditions()
+    {
+        let conditions: LinkConditions =
+            "latency=100,jitter=20.5,loss=0.1,reorder=0.05".parse().unwrap();
+        assert_eq!(conditions, LinkConditions{
+            latency: Duration::from_millis(100),
+            jitter: Duration::from_micros(20_500),
+            loss: 0.1,
+            duplicate: 0.0,
+            reorder: 0.05,
+        });
+        let empty: LinkConditions = "".parse().unwrap();
+        assert_eq!(empty, LinkConditions::PERFECT);
+        assert!("loss=2".parse::<LinkConditions>().is_err());
+        assert!("latency".parse::<LinkConditions>().is_err());
+        assert!("bandwidth=10".parse::<LinkConditions>().is_err());
+        assert!("latency=inf".parse::<LinkConditions>().is_err());
+        assert!("latency=NaN".parse::<LinkConditions>().is_err());
+        assert!("jitter=1e300".parse::<LinkConditions>().is_err());
+    }
+
+    #[test]
+    fn duplicate_and_reorder_in_simulated_time()
+    {
+        let clock = SimulatedClock::new(Instant::now());
+        let conditions = LinkConditions{
+            latency: Duration::from_millis(50),
+            duplicate: 0.5,
+            reorder: 0.5,
+            ..LinkConditions::PERFECT
+        };
+        let (a, mut b) = MemorySocket::pair();
+        let address = a.peer_addr();
+        let mut a = SimulatedSocket::new(a, conditions, 7)
+            .with_clock(clock.clone());
+
+        for i in 0 .. 100u8 {
+            a.send_to(&[i], address).unwrap();
+        }
+        let mut buffer = [0; 1];
+        assert_eq!(b.receive_from(&mut buffer).unwrap(), None);
+
+        // Nothing is held back by more than twice the latency.
+        clock.advance(Duration::from_millis(100));
+        a.receive_from(&mut buffer).unwrap();
+        let mut received = Vec::new();
+        while let Some((size, _)) = b.receive_from(&mut buffer).unwrap() {
+            received.extend_from_slice(&buffer[.. size]);
+        }
+
+        assert!(received.len() > 100, "No datagrams were duplicated");
+        assert!(received.windows(2).any(|w| w[0] > w[1]), "None reordered");
+        let mut distinct = received.clone();
+        distinct.sort_unstable();
+        distinct.dedup();
+        assert_eq!(distinct, (0 .. 100).collect::<Vec<u8>>());
+    }
+}
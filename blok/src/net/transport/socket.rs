@@ -0,0 +1,85 @@
+use anyhow::Result;
+use std::{
+    io::ErrorKind,
+    net::{SocketAddr, ToSocketAddrs, UdpSocket},
+};
+
+/// Means of sending and receiving datagrams.
+pub trait Socket
+{
+    /// Send a datagram to an address.
+    ///
+    /// Datagrams may be lost, so failure to deliver is not an error.
+    fn send_to(&mut self, datagram: &[u8], address: SocketAddr) -> Result<()>;
+
+    /// Receive a datagram into a buffer, without blocking.
+    ///
+    /// Returns the size of the datagram and the address it came from,
+    /// or [`None`] if no datagram is waiting.
+    /// Datagrams larger than the buffer are truncated.
+    fn receive_from(&mut self, buffer: &mut [u8])
+        -> Result<Option<(usize, SocketAddr)>>;
+}
+
+impl<S> Socket for Box<S>
+    where S: ?Sized + Socket
+{
+    fn send_to(&mut self, datagram: &[u8], address: SocketAddr) -> Result<()>
+    {
+        (**self).send_to(datagram, address)
+    }
+
+    fn receive_from(&mut self, buffer: &mut [u8])
+        -> Result<Option<(usize, SocketAddr)>>
+    {
+        (**self).receive_from(buffer)
+    }
+}
+
+/// Bind a UDP socket that does not block, for use as a [`Socket`].
+pub fn bind_udp<A>(address: A) -> Result<UdpSocket>
+    where A: ToSocketAddrs
+{
+    let socket = UdpSocket::bind(address)?;
+    socket.set_nonblocking(true)?;
+    Ok(socket)
+}
+
+/// The socket must not block; see [`bind_udp`].
+impl Socket for UdpSocket
+{
+    fn send_to(&mut self, datagram: &[u8], address: SocketAddr) -> Result<()>
+    {
+        match UdpSocket::send_to(self, datagram, address) {
+            Ok(_) => Ok(()),
+            Err(err) if is_transient(err.kind()) => Ok(()),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    fn receive_from(&mut self, buffer: &mut [u8])
+        -> Result<Option<(usize, SocketAddr)>>
+    {
+        loop {
+            match self.recv_from(buffer) {
+                Ok(received) => return Ok(Some(received)),
+                Err(err) if err.kind() == ErrorKind::WouldBlock =>
+                    return Ok(None),
+                // Some platforms report ICMP errors for earlier datagrams
+                // when receiving; these concern the peer, not this socket.
+                Err(err) if is_transient(err.kind()) => continue,
+                Err(err) => return Err(err.into()),
+            }
+        }
+    }
+}
+
+fn is_transient(kind: ErrorKind) -> bool
+{
+    matches!(
+        kind,
+        ErrorKind::WouldBlock
+            | ErrorKind::ConnectionRefused
+            | ErrorKind::ConnectionReset
+    )
+}
@@ -0,0 +1,297 @@
+use crate::{
+    net::{
+        ClientMessage,
+        ServerMessage,
+        SnapshotEncoder,
+        receive_message,
+        send_message,
+        transport::{Connection, Server, ServerEvent, Socket},
+    },
+    state::{
+        AbstractInput,
+        BoundingBox,
+        Entities,
+        EntityId,
+        ModelId,
+        Schedule,
+        Tick,
+        Transform,
+        World,
+        apply_input,
+    },
+};
+use anyhow::Result;
+use glam::{Quat, vec3};
+use std::{
+    collections::{BTreeMap, VecDeque},
+    net::SocketAddr,
+    time::Instant,
+};
+
+/// Maximum number of inputs of a client applied per tick.
+///
+/// Input that arrives in a burst after a delay is caught up on
+/// gradually, rather than making the player jump.
+const MAX_INPUTS_PER_TICK: usize = 2;
+
+/// Maximum number of inputs of a client waiting to be applied.
+///
+/// Older input is discarded, so that the player does not lag behind
+/// after the connection stalled.
+const MAX_PENDING_INPUTS: usize = 16;
+
+/// Authoritative simulation of the game, with connected clients.
+pub struct GameServer<S>
+{
+    transport: Server<S>,
+    world: World,
+    entities: Entities,
+    schedule: Schedule,
+    tick: Tick,
+    clients: BTreeMap<SocketAddr, RemoteClient>,
+}
+
+/// State of the server about a connected client.
+struct RemoteClient
+{
+    player: EntityId,
+    encoder: SnapshotEncoder,
+
+    // INVARIANT: Ticks are strictly increasing,
+    //            and greater than the tick of the latest applied input.
+    pending_inputs: VecDeque<(Tick, AbstractInput)>,
+
+    /// Tick of the latest input applied to the player.
+    last_input: Option<Tick>,
+}
+
+impl<S> GameServer<S>
+    where S: Socket
+{
+    /// Create a server for a world, without entities.
+    pub fn new(socket: S, max_clients: usize, world: World) -> Self
+    {
+        Self{
+            transport: Server::new(socket, max_clients),
+            world,
+            entities: Entities::new(),
+            schedule: Schedule::standard(),
+            tick: Tick(0),
+            clients: BTreeMap::new(),
+        }
+    }
+
+    /// The world that the server simulates.
+    pub fn world(&self) -> &World
+    {
+        &self.world
+    }
+
+    /// The entities that the server simulates.
+    pub fn entities(&self) -> &Entities
+    {
+        &self.entities
+    }
+
+    /// The tick that is simulated next.
+    pub fn next_tick(&self) -> Tick
+    {
+        self.tick
+    }
+
+    /// The transport through which clients are connected.
+    pub fn transport(&self) -> &Server<S>
+    {
+        &self.transport
+    }
+
+    /// The player entity of a connected client.
+    pub fn player(&self, client: SocketAddr) -> Option<EntityId>
+    {
+        self.clients.get(&client).map(|client| client.player)
+    }
+
+    /// Simulate a tick.
+    ///
+    /// This receives messages from clients, applies their input,
+    /// runs the systems, and sends each client a snapshot.
+    /// This only fails if the socket fails;
+    /// malformed messages from clients are logged and ignored.
+    pub fn tick(&mut self, now: Instant) -> Result<()>
+    {
+        self.transport.update(now)?;
+        while let Some(event) = self.transport.poll_event() {
+            match event {
+                ServerEvent::Connected(address) => self.join(address)?,
+                ServerEvent::Disconnected(address) => self.leave(address),
+            }
+        }
+
+        self.receive_messages();
+        self.apply_inputs();
+        self.schedule.run(self.tick, &mut self.entities);
+        self.send_snapshots()?;
+
+        // Send the snapshots now rather than at the next tick.
+        self.transport.update(now)?;
+        self.tick = Tick(self.tick.0 + 1);
+        Ok(())
+    }
+
+    fn join(&mut self, address: SocketAddr) -> Result<()>
+    {
+        // The client may have disconnected in the same update
+        // in which it connected, in which case it does not join.
+        let connection = match self.transport.connection(address) {
+            Some(connection) => connection,
+            None => return Ok(()),
+        };
+
+        let player = self.entities.spawn();
+        self.entities.insert(player, Transform{
+            position: vec3(2.0, 2.0, 1.0),
+            orientation: Quat::IDENTITY,
+        });
+        self.entities.insert(player, BoundingBox{
+            min: vec3(-0.3, -0.3, 0.0),
+            max: vec3(0.3, 0.3, 1.8),
+        });
+        self.entities.insert(player, ModelId(0));
+
+        send_message(connection, &ServerMessage::Welcome{player})?;
+        for (position, chunk) in self.world.chunks() {
+            let chunk = chunk.clone();
+            send_message(connection, &ServerMessage::Chunk{position, chunk})?;
+        }
+
+        self.clients.insert(address, RemoteClient{
+            player,
+            encoder: SnapshotEncoder::new(),
+            pending_inputs: VecDeque::new(),
+            last_input: None,
+        });
+        log::info!("Client {} joined as {:?}", address, player);
+        Ok(())
+    }
+
+    fn leave(&mut self, address: SocketAddr)
+    {
+        if let Some(client) = self.clients.remove(&address) {
+            self.entities.despawn(client.player);
+            log::info!("Client {} left", address);
+        }
+    }
+
+    fn receive_messages(&mut self)
+    {
+        for (&address, client) in &mut self.clients {
+            let connection = match self.transport.connection(address) {
+                Some(connection) => connection,
+                None => continue,
+            };
+            while let Some(message) = receive_from(address, connection) {
+                client.handle_message(message);
+            }
+        }
+    }
+
+    fn apply_inputs(&mut self)
+    {
+        for client in self.clients.values_mut() {
+            for _ in 0 .. MAX_INPUTS_PER_TICK {
+                let (tick, input) = match client.pending_inputs.pop_front() {
+                    Some(pending) => pending,
+                    None => break,
+                };
+                apply_input(&mut self.entities, client.player, &input);
+                client.last_input = Some(tick);
+            }
+        }
+    }
+
+    fn send_snapshots(&mut self) -> Result<()>
+    {
+        for (&address, client) in &mut self.clients {
+            let connection = match self.transport.connection(address) {
+                Some(connection) => connection,
+                None => continue,
+            };
+            let data = client.encoder.encode(self.tick, &self.entities);
+            let last_input = client.last_input;
+            let snapshot = ServerMessage::Snapshot{last_input, data};
+            send_message(connection, &snapshot)?;
+        }
+        Ok(())
+    }
+}
+
+impl RemoteClient
+{
+    fn handle_message(&mut self, message: ClientMessage)
+    {
+        match message {
+            ClientMessage::Input(inputs) => {
+                for (tick, input) in inputs {
+                    let latest = self.pending_inputs.back()
+                        .map(|&(tick, _)| tick)
+                        .or(self.last_input);
+                    if matches!(latest, Some(latest) if tick <= latest) {
+                        continue;
+                    }
+                    self.pending_inputs.push_back((tick, input));
+                    if self.pending_inputs.len() > MAX_PENDING_INPUTS {
+                        self.pending_inputs.pop_front();
+                    }
+                }
+            },
+            ClientMessage::Acknowledge(tick) =>
+                self.encoder.acknowledge(tick),
+        }
+    }
+}
+
+/// Take the next message from a client, logging malformed messages.
+fn receive_from(address: SocketAddr, connection: &mut Connection)
+    -> Option<ClientMessage>
+{
+    loop {
+        match receive_message(connection) {
+            Ok(message) => return message,
+            Err(err) => log::warn!("Message from {}: {:#}", address, err),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+    use crate::net::transport::{Client, MemorySocket};
+
+    #[test]
+    fn connect_and_disconnect_in_one_update()
+    {
+        let (client_socket, server_socket) = MemorySocket::pair();
+        let server_address = server_socket.local_addr();
+        let client_address = client_socket.local_addr();
+
+        let now = Instant::now();
+        let mut server = GameServer::new(server_socket, 1, World::new());
+        let mut client = Client::connect(client_socket, server_address, now);
+
+        // Complete the handshake and leave again
+        // before the server receives the first payload.
+        client.update(now).unwrap();
+        server.tick(now).unwrap();
+        client.update(now).unwrap();
+        let connection = client.connection().unwrap();
+        send_message(connection, &ClientMessage::Acknowledge(Tick(0)))
+            .unwrap();
+        client.update(now).unwrap();
+        client.disconnect().unwrap();
+
+        server.tick(now).unwrap();
+        assert_eq!(server.player(client_address), None);
+        assert_eq!(server.entities().ids().count(), 0);
+    }
+}
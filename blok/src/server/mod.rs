@@ -0,0 +1,66 @@
+//! Logic that is specific to the server.
+//!
+//! The server is authoritative: it simulates the world and the entities,
+//! and clients only send input and display what the server sends them.
+//! For single-player, the client runs the server on a thread
+//! and connects to it through a [`MemorySocket`],
+//! so that single-player and multiplayer take the same code path.
+//!
+//! [`MemorySocket`]: crate::net::transport::MemorySocket
+
+pub use self::game::*;
+pub use self::save::*;
+
+use crate::{net::transport::Socket, state::TICK_DURATION};
+use anyhow::Result;
+use std::{
+    path::Path,
+    sync::atomic::{AtomicBool, Ordering::SeqCst},
+    thread,
+    time::Instant,
+};
+
+mod game;
+mod save;
+
+/// Number of ticks the server may fall behind before it skips ticks.
+const MAX_TICKS_BEHIND: u32 = 10;
+
+/// Run a server until `stop` is set, then save the world.
+///
+/// The world is loaded from the save, or generated if there is none.
+/// The world is also saved when the server fails.
+pub fn run<S>(
+    socket: S,
+    max_clients: usize,
+    save_path: &Path,
+    stop: &AtomicBool,
+) -> Result<()>
+    where S: Socket
+{
+    let world = load_or_generate_world(save_path)?;
+    let mut server = GameServer::new(socket, max_clients, world);
+    let served = serve(&mut server, stop);
+    let saved = save_world(server.world(), save_path);
+    served.and(saved)
+}
+
+/// Tick a server at the tick rate until `stop` is set.
+fn serve<S>(server: &mut GameServer<S>, stop: &AtomicBool) -> Result<()>
+    where S: Socket
+{
+    let mut deadline = Instant::now();
+    while !stop.load(SeqCst) {
+        server.tick(Instant::now())?;
+
+        // Rather than simulating many ticks at once after a stall,
+        // such as a suspended process, continue from the current time.
+        deadline += TICK_DURATION;
+        let now = Instant::now();
+        if now > deadline + TICK_DURATION * MAX_TICKS_BEHIND {
+            deadline = now;
+        }
+        thread::sleep(deadline.saturating_duration_since(now));
+    }
+    Ok(())
+}
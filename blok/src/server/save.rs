@@ -0,0 +1,193 @@
+use crate::state::{Block, CHUNK_SIZE, Chunk, World};
+use anyhow::{Context, Result, ensure};
+use glam::{IVec3, ivec3};
+use serde::{Deserialize, Serialize};
+use std::{fs, path::Path};
+
+/// Version of the save format.
+///
+/// This is incremented on incompatible changes to the format,
+/// so that old saves are rejected rather than misread.
+const SAVE_VERSION: u32 = 1;
+
+/// Contents of a world save.
+#[derive(Deserialize, Serialize)]
+struct Save
+{
+    version: u32,
+    chunks: Vec<(IVec3, Chunk)>,
+}
+
+/// Load a world saved with [`save_world`].
+pub fn load_world(path: &Path) -> Result<World>
+{
+    read_save(path)
+        .with_context(|| format!("Load world from {}", path.display()))
+}
+
+/// Save the loaded chunks of a world.
+///
+/// The save is written to a temporary file that then replaces the save,
+/// so that a crash while saving does not corrupt an existing save.
+pub fn save_world(world: &World, path: &Path) -> Result<()>
+{
+    write_save(world, path)
+        .with_context(|| format!("Save world to {}", path.display()))
+}
+
+/// Load a world, or generate one if the save does not exist.
+pub fn load_or_generate_world(path: &Path) -> Result<World>
+{
+    if path.exists() {
+        load_world(path)
+    } else {
+        Ok(generate_world())
+    }
+}
+
+fn read_save(path: &Path) -> Result<World>
+{
+    let bytes = fs::read(path)?;
+    let save: Save = bincode::deserialize(&bytes)?;
+    ensure!(save.version == SAVE_VERSION,
+            "Unsupported save version {}", save.version);
+    let mut world = World::new();
+    for (position, chunk) in save.chunks {
+        ensure!(world.chunk(position).is_none(),
+                "Duplicate chunk at {}", position);
+        world.insert_chunk(position, chunk);
+    }
+    Ok(world)
+}
+
+fn write_save(world: &World, path: &Path) -> Result<()>
+{
+    let mut chunks: Vec<(IVec3, Chunk)> =
+        world.chunks()
+        .map(|(position, chunk)| (position, chunk.clone()))
+        .collect();
+    chunks.sort_by_key(|&(position, _)| position.to_array());
+    let save = Save{version: SAVE_VERSION, chunks};
+
+    if let Some(directory) = path.parent() {
+        fs::create_dir_all(directory)?;
+    }
+    let temporary = path.with_extension("tmp");
+    fs::write(&temporary, bincode::serialize(&save)?)?;
+    fs::rename(&temporary, path)?;
+    Ok(())
+}
+
+/// Generate a small world with a stone floor and a cave lit by a torch.
+pub fn generate_world() -> World
+{
+    let mut chunk = Chunk::new();
+    for x in 0 .. CHUNK_SIZE {
+        for y in 0 .. CHUNK_SIZE {
+            chunk.set_block(ivec3(x, y, 0), Block::Stone);
+        }
+    }
+    for x in 4 .. 8 {
+        for y in 4 .. 8 {
+            for z in 1 .. 4 {
+                let wall = x == 4 || x == 7 || y == 4 || y == 7 || z == 3;
+                let entrance = x == 7 && y == 5 && z < 3;
+                if wall && !entrance {
+                    chunk.set_block(ivec3(x, y, z), Block::Stone);
+                }
+            }
+        }
+    }
+
+    let mut world = World::new();
+    world.insert_chunk(ivec3(0, 0, 0), chunk);
+    world.set_block(ivec3(5, 6, 1), Block::Torch);
+    world
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+    use crate::{net::transport::MemorySocket, server::run};
+    use std::{
+        env,
+        path::PathBuf,
+        process,
+        sync::{Arc, atomic::{AtomicBool, Ordering::SeqCst}},
+        thread,
+    };
+
+    /// Path of a save in a directory of its own for the test.
+    fn temporary_save(test: &str) -> PathBuf
+    {
+        env::temp_dir()
+            .join(format!("blok-save-test-{}-{}", test, process::id()))
+            .join("world.bin")
+    }
+
+    fn assert_same_blocks(a: &World, b: &World)
+    {
+        for x in 0 .. CHUNK_SIZE {
+            for y in 0 .. CHUNK_SIZE {
+                for z in 0 .. CHUNK_SIZE {
+                    let position = ivec3(x, y, z);
+                    assert_eq!(a.block(position), b.block(position));
+                    assert_eq!(a.light(position), b.light(position));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn save_and_load()
+    {
+        let path = temporary_save("save-and-load");
+        let world = generate_world();
+        save_world(&world, &path).unwrap();
+        let loaded = load_world(&path).unwrap();
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+        assert_same_blocks(&loaded, &world);
+    }
+
+    #[test]
+    fn corrupt_save_is_an_error()
+    {
+        let path = temporary_save("corrupt");
+        save_world(&generate_world(), &path).unwrap();
+        let bytes = fs::read(&path).unwrap();
+
+        // Truncated.
+        fs::write(&path, &bytes[.. bytes.len() / 2]).unwrap();
+        assert!(load_world(&path).is_err());
+
+        // Not a save at all.
+        fs::write(&path, b"not a save").unwrap();
+        assert!(load_world(&path).is_err());
+
+        // Written by an incompatible version.
+        let save = Save{version: SAVE_VERSION + 1, chunks: Vec::new()};
+        fs::write(&path, bincode::serialize(&save).unwrap()).unwrap();
+        let error = load_world(&path).err().unwrap();
+        assert!(format!("{:#}", error).contains("Unsupported save version"));
+
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn server_saves_when_stopped()
+    {
+        let path = temporary_save("server");
+        let (_client_socket, server_socket) = MemorySocket::pair();
+        let stop = Arc::new(AtomicBool::new(false));
+        let server = thread::spawn({
+            let (path, stop) = (path.clone(), stop.clone());
+            move || run(server_socket, 1, &path, &stop)
+        });
+        stop.store(true, SeqCst);
+        server.join().unwrap().unwrap();
+        let loaded = load_world(&path).unwrap();
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+        assert_same_blocks(&loaded, &generate_world());
+    }
+}
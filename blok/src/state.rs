@@ -1,5 +0,0 @@
-//! Data structures for game state.
-
-/// Monotonically increasing number identifying a tick.
-#[derive(Clone, Copy, Debug, Eq, Ord, PartialEq, PartialOrd)]
-pub struct Tick(pub u64);
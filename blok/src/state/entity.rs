@@ -0,0 +1,475 @@
+use crate::state::Tick;
+use glam::{Mat4, Quat, Vec3};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+
+/// Stable identifier of an entity.
+///
+/// Identifiers are never reused, so an identifier that refers to
+/// a despawned entity does not accidentally refer to a new entity.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+#[derive(Deserialize, Serialize)]
+pub struct EntityId(pub u64);
+
+/// Position and orientation of an entity in the world.
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[derive(Deserialize, Serialize)]
+pub struct Transform
+{
+    /// Position of the origin of the entity, in blocks.
+    ///
+    /// By convention the origin is at the feet of the entity.
+    pub position: Vec3,
+
+    /// Orientation of the entity.
+    ///
+    /// This must be normalized.
+    pub orientation: Quat,
+}
+
+impl Transform
+{
+    /// The matrix that takes the entity’s model space into world space.
+    pub fn to_matrix(&self) -> Mat4
+    {
+        Mat4::from_rotation_translation(self.orientation, self.position)
+    }
+}
+
+/// Interpolate between two rotations along the shortest arc.
+///
+/// A quaternion and its negation describe the same rotation,
+/// but interpolating between them would turn a full circle.
+pub fn slerp(a: Quat, b: Quat, t: f32) -> Quat
+{
+    let b = if a.dot(b) < 0.0 { -b } else { b };
+    a.slerp(b, t)
+}
+
+/// Velocity of an entity, in blocks per tick.
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[derive(Deserialize, Serialize)]
+pub struct Velocity(pub Vec3);
+
+/// Axis-aligned box that an entity occupies.
+///
+/// The box is relative to the position of the entity,
+/// and does not rotate with the entity.
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[derive(Deserialize, Serialize)]
+pub struct BoundingBox
+{
+    /// Corner of the box with the smallest coordinates.
+    pub min: Vec3,
+
+    /// Corner of the box with the largest coordinates.
+    pub max: Vec3,
+}
+
+/// Health of an entity that can die.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[derive(Deserialize, Serialize)]
+pub struct Health
+{
+    /// Current health points.
+    ///
+    /// The entity dies when this reaches zero.
+    pub current: u32,
+
+    /// Health points when fully healed.
+    pub maximum: u32,
+}
+
+/// Reference to the model with which an entity is rendered.
+///
+/// The client maps these to the models it loaded;
+/// the game state does not know about models.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+#[derive(Deserialize, Serialize)]
+pub struct ModelId(pub u32);
+
+/// Type of data that can be attached to entities.
+///
+/// Each component type has its own storage in [`Entities`].
+pub trait Component: Sized
+{
+    /// The storage of this component type.
+    fn storage(entities: &Entities) -> &BTreeMap<EntityId, Self>;
+
+    /// The storage of this component type.
+    fn storage_mut(entities: &mut Entities)
+        -> &mut BTreeMap<EntityId, Self>;
+}
+
+/// Collection of entities and their components.
+///
+/// Components are stored per type, ordered by entity identifier,
+/// so that iteration order, and thus simulation, is deterministic.
+/// The collection is serializable, for sending snapshots to clients.
+#[derive(Clone, Debug, Default, PartialEq)]
+#[derive(Deserialize, Serialize)]
+pub struct Entities
+{
+    next_id: u64,
+
+    // INVARIANT: Each entity in a storage is in this set.
+    alive: BTreeSet<EntityId>,
+
+    transforms: BTreeMap<EntityId, Transform>,
+    velocities: BTreeMap<EntityId, Velocity>,
+    bounding_boxes: BTreeMap<EntityId, BoundingBox>,
+    healths: BTreeMap<EntityId, Health>,
+    models: BTreeMap<EntityId, ModelId>,
+}
+
+macro_rules! impl_component
+{
+    ($($type:ty => $field:ident;)*) => {
+        $(
+            impl Component for $type
+            {
+                fn storage(entities: &Entities)
+                    -> &BTreeMap<EntityId, Self>
+                {
+                    &entities.$field
+                }
+
+                fn storage_mut(entities: &mut Entities)
+                    -> &mut BTreeMap<EntityId, Self>
+                {
+                    &mut entities.$field
+                }
+            }
+        )*
+
+        impl Entities
+        {
+            fn remove_components(&mut self, id: EntityId)
+            {
+                $(self.$field.remove(&id);)*
+            }
+        }
+    };
+}
+
+impl_component! {
+    Transform   => transforms;
+    Velocity    => velocities;
+    BoundingBox => bounding_boxes;
+    Health      => healths;
+    ModelId     => models;
+}
+
+impl Entities
+{
+    /// Create a collection with no entities.
+    pub fn new() -> Self
+    {
+        Self::default()
+    }
+
+    /// Create an entity without components.
+    pub fn spawn(&mut self) -> EntityId
+    {
+        let id = EntityId(self.next_id);
+        self.next_id += 1;
+        self.alive.insert(id);
+        id
+    }
+
+    /// Create an entity with a given identifier.
+    ///
+    /// This is for mirroring a collection of entities elsewhere,
+    /// such as on the client, where identifiers are assigned by the server.
+    /// Entities spawned later get greater identifiers.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the entity already exists,
+    /// or if the identifier is the greatest possible identifier,
+    /// as no identifier would be left for later entities.
+    pub fn spawn_with_id(&mut self, id: EntityId)
+    {
+        let next_id = id.0.checked_add(1)
+            .expect("Entity identifier has no successor");
+        assert!(self.alive.insert(id), "Entity {:?} already exists", id);
+        self.reserve_ids(EntityId(next_id));
+    }
+
+    /// Ensure that entities spawned later get identifiers
+    /// no less than the given identifier.
+    pub fn reserve_ids(&mut self, next_id: EntityId)
+    {
+        self.next_id = self.next_id.max(next_id.0);
+    }
+
+    /// The identifier that the next spawned entity gets.
+    pub fn next_id(&self) -> EntityId
+    {
+        EntityId(self.next_id)
+    }
+
+    /// Remove an entity and all its components.
+    ///
+    /// Returns whether the entity existed.
+    pub fn despawn(&mut self, id: EntityId) -> bool
+    {
+        self.remove_components(id);
+        self.alive.remove(&id)
+    }
+
+    /// Whether the entity exists.
+    pub fn contains(&self, id: EntityId) -> bool
+    {
+        self.alive.contains(&id)
+    }
+
+    /// The identifiers of all entities, in increasing order.
+    pub fn ids(&self) -> impl Iterator<Item=EntityId> + '_
+    {
+        self.alive.iter().copied()
+    }
+
+    /// The number of entities.
+    pub fn len(&self) -> usize
+    {
+        self.alive.len()
+    }
+
+    /// Whether there are no entities.
+    pub fn is_empty(&self) -> bool
+    {
+        self.alive.is_empty()
+    }
+
+    /// Attach a component to an entity,
+    /// replacing the component of the same type, if any.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the entity does not exist.
+    pub fn insert<T>(&mut self, id: EntityId, component: T) -> Option<T>
+        where T: Component
+    {
+        assert!(self.contains(id), "Entity {:?} does not exist", id);
+        T::storage_mut(self).insert(id, component)
+    }
+
+    /// Detach a component from an entity.
+    pub fn remove<T>(&mut self, id: EntityId) -> Option<T>
+        where T: Component
+    {
+        T::storage_mut(self).remove(&id)
+    }
+
+    /// The component of an entity.
+    pub fn get<T>(&self, id: EntityId) -> Option<&T>
+        where T: Component
+    {
+        T::storage(self).get(&id)
+    }
+
+    /// The component of an entity.
+    pub fn get_mut<T>(&mut self, id: EntityId) -> Option<&mut T>
+        where T: Component
+    {
+        T::storage_mut(self).get_mut(&id)
+    }
+
+    /// The entities with a component, with that component.
+    pub fn iter<'a, T>(&'a self) -> impl Iterator<Item=(EntityId, &'a T)>
+        where T: 'a + Component
+    {
+        T::storage(self).iter().map(|(&id, component)| (id, component))
+    }
+}
+
+/// Logic that updates entities once per tick.
+///
+/// Systems run on both the server and the client,
+/// the latter for predicting the outcome of the next snapshot.
+/// They must therefore be deterministic.
+pub trait System
+{
+    /// Update the entities for the given tick.
+    fn run(&mut self, tick: Tick, entities: &mut Entities);
+}
+
+impl<F> System for F
+    where F: FnMut(Tick, &mut Entities)
+{
+    fn run(&mut self, tick: Tick, entities: &mut Entities)
+    {
+        self(tick, entities)
+    }
+}
+
+/// Sequence of systems that run once per tick, in order.
+pub struct Schedule
+{
+    systems: Vec<Box<dyn System>>,
+
+    // INVARIANT: Ticks are monotonically increasing.
+    last_tick: Option<Tick>,
+}
+
+impl Schedule
+{
+    /// Create a schedule with no systems.
+    pub fn new() -> Self
+    {
+        Self{systems: Vec::new(), last_tick: None}
+    }
+
+    /// Create a schedule with the systems that the game always runs.
+    ///
+    /// These are [`movement`] followed by [`death`].
+    pub fn standard() -> Self
+    {
+        let mut this = Self::new();
+        this.push(movement);
+        this.push(death);
+        this
+    }
+
+    /// Add a system that runs after the systems already added.
+    pub fn push<S>(&mut self, system: S)
+        where S: 'static + System
+    {
+        self.systems.push(Box::new(system));
+    }
+
+    /// Run each system for a tick.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `tick` does not monotonically increase,
+    /// so that no tick is simulated twice.
+    pub fn run(&mut self, tick: Tick, entities: &mut Entities)
+    {
+        if let Some(last_tick) = self.last_tick {
+            assert!(tick > last_tick);
+        }
+        self.last_tick = Some(tick);
+        for system in &mut self.systems {
+            system.run(tick, entities);
+        }
+    }
+}
+
+impl Default for Schedule
+{
+    fn default() -> Self
+    {
+        Self::new()
+    }
+}
+
+/// Move entities by their velocity.
+pub fn movement(_tick: Tick, entities: &mut Entities)
+{
+    let Entities{transforms, velocities, ..} = entities;
+    for (id, velocity) in velocities.iter() {
+        if let Some(transform) = transforms.get_mut(id) {
+            transform.position += velocity.0;
+        }
+    }
+}
+
+/// Despawn entities whose health reached zero.
+pub fn death(_tick: Tick, entities: &mut Entities)
+{
+    let dead: Vec<EntityId> =
+        entities.iter::<Health>()
+        .filter(|(_, health)| health.current == 0)
+        .map(|(id, _)| id)
+        .collect();
+    for id in dead {
+        entities.despawn(id);
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+    use glam::vec3;
+
+    fn mob(entities: &mut Entities, health: u32) -> EntityId
+    {
+        let id = entities.spawn();
+        let transform = Transform{
+            position: Vec3::ZERO,
+            orientation: Quat::IDENTITY,
+        };
+        entities.insert(id, transform);
+        entities.insert(id, Velocity(vec3(1.0, 0.0, 0.0)));
+        entities.insert(id, Health{current: health, maximum: 20});
+        entities.insert(id, ModelId(0));
+        id
+    }
+
+    #[test]
+    fn ids_are_not_reused()
+    {
+        let mut entities = Entities::new();
+        let a = entities.spawn();
+        assert!(entities.despawn(a));
+        let b = entities.spawn();
+        assert_ne!(a, b);
+        assert!(!entities.contains(a));
+        assert!(!entities.despawn(a));
+    }
+
+    #[test]
+    fn despawn_removes_components()
+    {
+        let mut entities = Entities::new();
+        let id = mob(&mut entities, 20);
+        entities.despawn(id);
+        assert_eq!(entities.get::<Transform>(id), None);
+        assert_eq!(entities.iter::<ModelId>().count(), 0);
+    }
+
+    #[test]
+    fn standard_schedule_moves_and_kills()
+    {
+        let mut entities = Entities::new();
+        let alive = mob(&mut entities, 20);
+        let dead = mob(&mut entities, 0);
+        let mut schedule = Schedule::standard();
+        schedule.run(Tick(0), &mut entities);
+        schedule.run(Tick(1), &mut entities);
+
+        let transform = entities.get::<Transform>(alive).unwrap();
+        assert_eq!(transform.position, vec3(2.0, 0.0, 0.0));
+        assert!(!entities.contains(dead));
+    }
+
+    #[test]
+    #[should_panic]
+    fn schedule_runs_each_tick_once()
+    {
+        let mut schedule = Schedule::new();
+        let mut entities = Entities::new();
+        schedule.run(Tick(1), &mut entities);
+        schedule.run(Tick(1), &mut entities);
+    }
+
+    #[test]
+    fn serialization_round_trip()
+    {
+        let mut entities = Entities::new();
+        mob(&mut entities, 20);
+        let despawned = entities.spawn();
+        entities.despawn(despawned);
+        mob(&mut entities, 5);
+
+        let bytes = bincode::serialize(&entities).unwrap();
+        let mut decoded: Entities = bincode::deserialize(&bytes).unwrap();
+        assert_eq!(decoded, entities);
+
+        // Identifiers continue where the original left off.
+        assert_eq!(decoded.spawn(), entities.spawn());
+    }
+}
@@ -0,0 +1,378 @@
+use crate::state::{CHUNK_SIZE, World};
+use glam::IVec3;
+use std::collections::VecDeque;
+
+/// Light levels of a voxel.
+///
+/// There are two kinds of light, each with a level from 0 through 15.
+/// Sky light enters the world from above the topmost loaded chunks.
+/// It travels down through non-opaque blocks without losing any level,
+/// and in all other directions it loses one level per block.
+/// Block light is emitted by blocks such as torches,
+/// and loses one level per block in every direction.
+/// Opaque blocks always have a light level of zero for both kinds.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Light(u8);
+
+/// The maximum light level.
+pub const MAX_LIGHT: u8 = 15;
+
+impl Light
+{
+    /// No light at all.
+    pub const DARK: Self = Self(0);
+
+    /// Unobstructed sky light and no block light.
+    pub const SKY: Self = Self(MAX_LIGHT << 4);
+
+    /// Combine a sky light level and a block light level.
+    ///
+    /// # Panics
+    ///
+    /// Panics if either level exceeds [`MAX_LIGHT`].
+    pub fn new(sky: u8, block: u8) -> Self
+    {
+        assert!(sky <= MAX_LIGHT && block <= MAX_LIGHT);
+        Self(sky << 4 | block)
+    }
+
+    /// The sky light level.
+    pub fn sky(self) -> u8
+    {
+        self.0 >> 4
+    }
+
+    /// The block light level.
+    pub fn block(self) -> u8
+    {
+        self.0 & 0xF
+    }
+
+    /// Sky light level in the 4 MSbs, block light level in the 4 LSbs.
+    pub fn as_raw(self) -> u8
+    {
+        self.0
+    }
+}
+
+/// The kind of light that is being propagated.
+#[derive(Clone, Copy, Eq, PartialEq)]
+enum Channel
+{
+    Sky,
+    Block,
+}
+
+impl Channel
+{
+    fn get(self, light: Light) -> u8
+    {
+        match self {
+            Self::Sky   => light.sky(),
+            Self::Block => light.block(),
+        }
+    }
+
+    fn set(self, light: Light, level: u8) -> Light
+    {
+        match self {
+            Self::Sky   => Light::new(level, light.block()),
+            Self::Block => Light::new(light.sky(), level),
+        }
+    }
+}
+
+const UP: IVec3 = glam::const_ivec3!([0, 0, 1]);
+const DOWN: IVec3 = glam::const_ivec3!([0, 0, -1]);
+
+const DIRECTIONS: [IVec3; 6] = [
+    glam::const_ivec3!([ 1,  0,  0]),
+    glam::const_ivec3!([ 0,  1,  0]),
+    glam::const_ivec3!([-1,  0,  0]),
+    glam::const_ivec3!([ 0, -1,  0]),
+    UP,
+    DOWN,
+];
+
+/// Light propagation.
+///
+/// Propagation is a breadth-first flood fill over the loaded voxels.
+/// Removing light is done with a second flood fill that clears all light
+/// that could have come from the removed source, collecting the voxels
+/// at its boundary that are lit by other sources; those are then
+/// propagated again to fill the cleared region.
+impl World
+{
+    /// Update light levels after the block at a position changed.
+    pub(super) fn light_changed_block(&mut self, position: IVec3)
+    {
+        let block = self.block(position);
+        for channel in [Channel::Sky, Channel::Block] {
+            let mut removal = VecDeque::new();
+            let mut addition = VecDeque::new();
+
+            // Remove all light that could have passed through the voxel.
+            let light = self.light(position);
+            let level = channel.get(light);
+            if level > 0 {
+                self.set_light(position, channel.set(light, 0));
+                removal.push_back((position, level));
+            }
+            self.propagate_removal(channel, &mut removal, &mut addition);
+
+            // Light may now flow into the voxel from its neighbours.
+            addition.extend(DIRECTIONS.iter().map(|&d| position + d));
+
+            // The voxel itself may be a light source.
+            if !block.is_opaque() {
+                let source = match channel {
+                    Channel::Sky if self.is_sky_exposed(position) => MAX_LIGHT,
+                    Channel::Sky => 0,
+                    Channel::Block => block.emission(),
+                };
+                if source > 0 {
+                    let light = self.light(position);
+                    self.set_light(position, channel.set(light, source));
+                    addition.push_back(position);
+                }
+            }
+
+            self.propagate_addition(channel, &mut addition);
+        }
+    }
+
+    /// Compute light levels for a chunk that was just inserted.
+    pub(super) fn light_inserted_chunk(&mut self, chunk_position: IVec3)
+    {
+        let origin = chunk_position * CHUNK_SIZE;
+        let mut addition = VecDeque::new();
+
+        // Light sources within the chunk.
+        for local in chunk_positions() {
+            let position = origin + local;
+            let block = self.block(position);
+            let sky = if block.is_opaque() || !self.is_sky_exposed(position)
+                { 0 } else { MAX_LIGHT };
+            let emission = block.emission();
+            if sky > 0 || emission > 0 {
+                self.set_light(position, Light::new(sky, emission));
+                addition.push_back(position);
+            }
+        }
+
+        // Light entering the chunk from adjacent chunks.
+        for local in chunk_positions() {
+            let on_border = local.to_array().into_iter()
+                .any(|c| c == 0 || c == CHUNK_SIZE - 1);
+            if on_border {
+                let position = origin + local;
+                addition.extend(DIRECTIONS.iter().map(|&d| position + d));
+            }
+        }
+
+        for channel in [Channel::Sky, Channel::Block] {
+            self.propagate_addition(channel, &mut addition.clone());
+        }
+
+        // The chunk below may have assumed that it was exposed to the sky.
+        // Wherever the new chunk blocks the sky, remove that sky light.
+        let mut removal = VecDeque::new();
+        let below = origin + DOWN;
+        for x in 0 .. CHUNK_SIZE {
+            for y in 0 .. CHUNK_SIZE {
+                let position = below + IVec3::new(x, y, 0);
+                if let Some(light) = self.loaded_light(position) {
+                    let above = self.light(position + UP);
+                    if light.sky() == MAX_LIGHT && above.sky() < MAX_LIGHT {
+                        self.set_light(position, Channel::Sky.set(light, 0));
+                        removal.push_back((position, MAX_LIGHT));
+                    }
+                }
+            }
+        }
+        let mut addition = VecDeque::new();
+        self.propagate_removal(Channel::Sky, &mut removal, &mut addition);
+        self.propagate_addition(Channel::Sky, &mut addition);
+    }
+
+    /// Whether a voxel is at the top of the loaded part of its column.
+    fn is_sky_exposed(&self, position: IVec3) -> bool
+    {
+        self.loaded_light(position + UP).is_none()
+    }
+
+    /// Spread light from the voxels in the queue to their neighbours.
+    ///
+    /// Positions in the queue that are not loaded are ignored.
+    fn propagate_addition(
+        &mut self,
+        channel: Channel,
+        queue: &mut VecDeque<IVec3>,
+    )
+    {
+        while let Some(position) = queue.pop_front() {
+            let level = match self.loaded_light(position) {
+                Some(light) => channel.get(light),
+                None => continue,
+            };
+            if level == 0 {
+                continue;
+            }
+            for &direction in &DIRECTIONS {
+                let neighbour = position + direction;
+                let light = match self.loaded_light(neighbour) {
+                    Some(light) => light,
+                    None => continue,
+                };
+                if self.block(neighbour).is_opaque() {
+                    continue;
+                }
+                let sky_beam = channel == Channel::Sky
+                    && direction == DOWN
+                    && level == MAX_LIGHT;
+                let target = if sky_beam { MAX_LIGHT } else { level - 1 };
+                if channel.get(light) < target {
+                    self.set_light(neighbour, channel.set(light, target));
+                    queue.push_back(neighbour);
+                }
+            }
+        }
+    }
+
+    /// Clear light that came from the voxels in the removal queue.
+    ///
+    /// Each entry in the removal queue is a voxel whose light
+    /// was already cleared, together with its former light level.
+    /// Voxels that are lit by other sources are added to the addition queue,
+    /// so that they can fill in the cleared region afterwards.
+    fn propagate_removal(
+        &mut self,
+        channel: Channel,
+        removal: &mut VecDeque<(IVec3, u8)>,
+        addition: &mut VecDeque<IVec3>,
+    )
+    {
+        while let Some((position, level)) = removal.pop_front() {
+            for &direction in &DIRECTIONS {
+                let neighbour = position + direction;
+                let light = match self.loaded_light(neighbour) {
+                    Some(light) => light,
+                    None => continue,
+                };
+                let neighbour_level = channel.get(light);
+                if neighbour_level == 0 {
+                    continue;
+                }
+                let sky_beam = channel == Channel::Sky
+                    && direction == DOWN
+                    && level == MAX_LIGHT
+                    && neighbour_level == MAX_LIGHT;
+                if neighbour_level < level || sky_beam {
+                    self.set_light(neighbour, channel.set(light, 0));
+                    removal.push_back((neighbour, neighbour_level));
+                    self.reseed(channel, neighbour, addition);
+                } else {
+                    addition.push_back(neighbour);
+                }
+            }
+        }
+    }
+
+    /// Restore the light of a voxel that is itself a light source.
+    fn reseed(
+        &mut self,
+        channel: Channel,
+        position: IVec3,
+        addition: &mut VecDeque<IVec3>,
+    )
+    {
+        let block = self.block(position);
+        let source = match channel {
+            Channel::Sky if self.is_sky_exposed(position) => MAX_LIGHT,
+            Channel::Sky => 0,
+            Channel::Block => block.emission(),
+        };
+        if source > 0 && !block.is_opaque() {
+            let light = self.light(position);
+            self.set_light(position, channel.set(light, source));
+            addition.push_back(position);
+        }
+    }
+}
+
+/// Iterator over all positions relative to a chunk.
+fn chunk_positions() -> impl Iterator<Item=IVec3>
+{
+    (0 .. CHUNK_SIZE).flat_map(|z| {
+        (0 .. CHUNK_SIZE).flat_map(move |y| {
+            (0 .. CHUNK_SIZE).map(move |x| IVec3::new(x, y, z))
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    use crate::state::{Block, Chunk};
+
+    /// Chunk with a stone roof, so that it receives no sky light.
+    fn roofed_chunk() -> Chunk
+    {
+        let mut chunk = Chunk::new();
+        for x in 0 .. CHUNK_SIZE {
+            for y in 0 .. CHUNK_SIZE {
+                chunk.set_block(IVec3::new(x, y, CHUNK_SIZE - 1), Block::Stone);
+            }
+        }
+        chunk
+    }
+
+    #[test]
+    fn torch_across_chunk_border()
+    {
+        let mut world = World::new();
+        world.insert_chunk(IVec3::new(0, 0, 0), roofed_chunk());
+        world.insert_chunk(IVec3::new(1, 0, 0), roofed_chunk());
+        assert_eq!(world.light(IVec3::new(8, 8, 8)), Light::DARK);
+
+        world.set_block(IVec3::new(15, 8, 8), Block::Torch);
+        assert_eq!(world.light(IVec3::new(15, 8, 8)).block(), 14);
+        assert_eq!(world.light(IVec3::new(17, 8, 8)).block(), 12);
+        assert_eq!(world.light(IVec3::new(12, 7, 8)).block(), 10);
+
+        world.set_block(IVec3::new(15, 8, 8), Block::Air);
+        assert_eq!(world.light(IVec3::new(15, 8, 8)), Light::DARK);
+        assert_eq!(world.light(IVec3::new(17, 8, 8)), Light::DARK);
+    }
+
+    #[test]
+    fn hole_in_roof()
+    {
+        let mut world = World::new();
+        world.insert_chunk(IVec3::new(0, 0, 0), roofed_chunk());
+
+        world.set_block(IVec3::new(8, 8, CHUNK_SIZE - 1), Block::Air);
+        assert_eq!(world.light(IVec3::new(8, 8, 0)).sky(), 15);
+        assert_eq!(world.light(IVec3::new(9, 8, 0)).sky(), 14);
+
+        // Light now has to go around the stone to reach below it.
+        world.set_block(IVec3::new(8, 8, 4), Block::Stone);
+        assert_eq!(world.light(IVec3::new(8, 8, 3)).sky(), 11);
+
+        world.set_block(IVec3::new(8, 8, CHUNK_SIZE - 1), Block::Stone);
+        assert_eq!(world.light(IVec3::new(8, 8, 0)), Light::DARK);
+    }
+
+    #[test]
+    fn chunk_above_blocks_sky()
+    {
+        let mut world = World::new();
+        world.insert_chunk(IVec3::new(0, 0, 0), Chunk::new());
+        assert_eq!(world.light(IVec3::new(8, 8, 0)).sky(), 15);
+
+        world.insert_chunk(IVec3::new(0, 0, 1), roofed_chunk());
+        assert_eq!(world.light(IVec3::new(8, 8, 0)), Light::DARK);
+    }
+}
@@ -0,0 +1,25 @@
+//! Data structures for game state.
+
+pub use self::entity::*;
+pub use self::light::*;
+pub use self::player::*;
+pub use self::world::*;
+
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+mod entity;
+mod light;
+mod player;
+mod world;
+
+/// Monotonically increasing number identifying a tick.
+#[derive(Clone, Copy, Debug, Eq, Ord, PartialEq, PartialOrd)]
+#[derive(Deserialize, Serialize)]
+pub struct Tick(pub u64);
+
+/// Real time between consecutive ticks.
+///
+/// The server simulates one tick per interval,
+/// and clients send input for one tick per interval.
+pub const TICK_DURATION: Duration = Duration::from_millis(50);
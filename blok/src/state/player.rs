@@ -0,0 +1,101 @@
+use crate::state::{Entities, EntityId, Transform};
+use glam::{Vec3, vec3};
+use serde::{Deserialize, Serialize};
+
+/// Distance a player moves per tick of input, in blocks.
+pub const PLAYER_SPEED: f32 = 0.25;
+
+/// High-level description of the input.
+///
+/// This describes the input in terms of gameplay actions
+/// rather than signals from human interface devices.
+/// Generating abstract input requires knowledge about
+/// the control mapping that the player configured and
+/// the user interface elements that are being displayed.
+///
+/// Clients send their input to the server for every tick,
+/// which applies it with [`apply_input`].
+#[allow(missing_docs)]
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+#[derive(Deserialize, Serialize)]
+pub struct AbstractInput
+{
+    pub move_forward: bool,
+    pub move_backward: bool,
+    pub strafe_left: bool,
+    pub strafe_right: bool,
+}
+
+impl AbstractInput
+{
+    /// The direction in which the input moves the player.
+    ///
+    /// Forward is along the Y axis and right is along the X axis.
+    /// The direction is normalized, unless the player does not move.
+    pub fn direction(&self) -> Vec3
+    {
+        let axis = |positive, negative| {
+            (positive as i32 - negative as i32) as f32
+        };
+        let direction = vec3(
+            axis(self.strafe_right, self.strafe_left),
+            axis(self.move_forward, self.move_backward),
+            0.0,
+        );
+        direction.normalize_or_zero()
+    }
+}
+
+/// Move a player according to the input for one tick.
+///
+/// Movement is applied directly to the transform, rather than by
+/// setting a velocity, so that the position of the player follows
+/// from the sequence of inputs alone, no matter when they are applied.
+/// This lets clients predict the position of their own player.
+/// Players without a transform are not moved.
+pub fn apply_input(
+    entities: &mut Entities,
+    player: EntityId,
+    input: &AbstractInput,
+)
+{
+    if let Some(transform) = entities.get_mut::<Transform>(player) {
+        move_player(transform, input);
+    }
+}
+
+/// Move the transform of a player according to the input for one tick.
+///
+/// This is [`apply_input`] for a transform outside the entities,
+/// such as the position that a client predicts for its player.
+pub fn move_player(transform: &mut Transform, input: &AbstractInput)
+{
+    transform.position += input.direction() * PLAYER_SPEED;
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+    use glam::Quat;
+
+    #[test]
+    fn diagonal_movement_is_not_faster()
+    {
+        let mut entities = Entities::new();
+        let player = entities.spawn();
+        entities.insert(player, Transform{
+            position: Vec3::ZERO,
+            orientation: Quat::IDENTITY,
+        });
+        let input = AbstractInput{
+            move_forward: true,
+            strafe_left: true,
+            ..AbstractInput::default()
+        };
+        apply_input(&mut entities, player, &input);
+        let position = entities.get::<Transform>(player).unwrap().position;
+        assert!((position.length() - PLAYER_SPEED).abs() < 1e-6);
+        assert!(position.x < 0.0 && position.y > 0.0);
+    }
+}
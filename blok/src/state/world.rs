@@ -0,0 +1,300 @@
+use crate::state::Light;
+use glam::IVec3;
+use serde::{Deserialize, Deserializer, Serialize, Serializer, de::Error};
+use std::collections::HashMap;
+
+/// Number of blocks along each dimension of a chunk.
+pub const CHUNK_SIZE: i32 = 16;
+
+/// Number of blocks in a chunk.
+const CHUNK_VOLUME: usize = (CHUNK_SIZE * CHUNK_SIZE * CHUNK_SIZE) as usize;
+
+/// Kind of block that occupies a voxel.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Block
+{
+    /// Empty space.
+    Air,
+
+    /// Opaque unit cube.
+    Stone,
+
+    /// Non-opaque block that emits light.
+    Torch,
+}
+
+impl Block
+{
+    /// Whether the block is a trivial block.
+    ///
+    /// Opaque blocks hide the faces of adjacent opaque blocks,
+    /// and light does not propagate through them.
+    pub fn is_opaque(self) -> bool
+    {
+        match self {
+            Self::Air   => false,
+            Self::Stone => true,
+            Self::Torch => false,
+        }
+    }
+
+    /// The block light level that the block emits.
+    pub fn emission(self) -> u8
+    {
+        match self {
+            Self::Air   => 0,
+            Self::Stone => 0,
+            Self::Torch => 14,
+        }
+    }
+
+    /// The block with the given discriminant, if any.
+    fn from_u8(value: u8) -> Option<Self>
+    {
+        match value {
+            0 => Some(Self::Air),
+            1 => Some(Self::Stone),
+            2 => Some(Self::Torch),
+            _ => None,
+        }
+    }
+}
+
+/// Blocks and light levels of a cubic region of the world.
+///
+/// Only the blocks are serialized, as one byte per block.
+/// Light levels are computed when the chunk is inserted into a world.
+#[derive(Clone)]
+pub struct Chunk
+{
+    blocks: Box<[Block; CHUNK_VOLUME]>,
+    light: Box<[Light; CHUNK_VOLUME]>,
+}
+
+impl Chunk
+{
+    /// Create a chunk that consists only of air.
+    pub fn new() -> Self
+    {
+        Self{
+            blocks: Box::new([Block::Air; CHUNK_VOLUME]),
+            light: Box::new([Light::DARK; CHUNK_VOLUME]),
+        }
+    }
+
+    /// The block at a position relative to the chunk.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the position is outside the chunk.
+    pub fn block(&self, position: IVec3) -> Block
+    {
+        self.blocks[Self::index(position)]
+    }
+
+    /// Replace the block at a position relative to the chunk.
+    ///
+    /// This does not update light levels, so it is only useful
+    /// for generating a chunk before it is inserted into a world.
+    /// Use [`World::set_block`] to change blocks in a world.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the position is outside the chunk.
+    pub fn set_block(&mut self, position: IVec3, block: Block)
+    {
+        self.blocks[Self::index(position)] = block;
+    }
+
+    /// The light level at a position relative to the chunk.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the position is outside the chunk.
+    pub fn light(&self, position: IVec3) -> Light
+    {
+        self.light[Self::index(position)]
+    }
+
+    pub(super) fn set_light(&mut self, position: IVec3, light: Light)
+    {
+        self.light[Self::index(position)] = light;
+    }
+
+    pub(super) fn clear_light(&mut self)
+    {
+        self.light.fill(Light::DARK);
+    }
+
+    fn index(position: IVec3) -> usize
+    {
+        let in_chunk = |c| (0 .. CHUNK_SIZE).contains(&c);
+        assert!(position.to_array().into_iter().all(in_chunk));
+        let (x, y, z) = (position.x, position.y, position.z);
+        ((z * CHUNK_SIZE + y) * CHUNK_SIZE + x) as usize
+    }
+}
+
+impl Default for Chunk
+{
+    fn default() -> Self
+    {
+        Self::new()
+    }
+}
+
+impl Serialize for Chunk
+{
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+        where S: Serializer
+    {
+        let blocks: Vec<u8> = self.blocks.iter().map(|&b| b as u8).collect();
+        blocks.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Chunk
+{
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+        where D: Deserializer<'de>
+    {
+        let blocks = Vec::<u8>::deserialize(deserializer)?;
+        if blocks.len() != CHUNK_VOLUME {
+            return Err(D::Error::invalid_length(blocks.len(), &"a chunk"));
+        }
+        let mut chunk = Self::new();
+        for (block, &value) in chunk.blocks.iter_mut().zip(&blocks) {
+            *block = Block::from_u8(value)
+                .ok_or_else(|| D::Error::custom("unknown block"))?;
+        }
+        Ok(chunk)
+    }
+}
+
+/// Collection of loaded chunks.
+///
+/// Light levels are kept up to date as chunks are inserted
+/// and as blocks are changed; see [`Light`] for details.
+pub struct World
+{
+    chunks: HashMap<IVec3, Chunk>,
+}
+
+impl World
+{
+    /// Create a world with no chunks loaded.
+    pub fn new() -> Self
+    {
+        Self{chunks: HashMap::new()}
+    }
+
+    /// The chunk at the given chunk position, if it is loaded.
+    ///
+    /// An increment of 1 in either dimension of the chunk position
+    /// corresponds to the adjacent chunk in that dimension.
+    pub fn chunk(&self, chunk_position: IVec3) -> Option<&Chunk>
+    {
+        self.chunks.get(&chunk_position)
+    }
+
+    /// The loaded chunks with their chunk positions, in no particular order.
+    pub fn chunks(&self) -> impl Iterator<Item=(IVec3, &Chunk)>
+    {
+        self.chunks.iter().map(|(&position, chunk)| (position, chunk))
+    }
+
+    /// The block at a position in the world.
+    ///
+    /// If the position is in a chunk that is not loaded,
+    /// this method returns [`Block::Air`].
+    pub fn block(&self, position: IVec3) -> Block
+    {
+        let (chunk_position, local) = split_position(position);
+        self.chunk(chunk_position)
+            .map(|chunk| chunk.block(local))
+            .unwrap_or(Block::Air)
+    }
+
+    /// The light level at a position in the world.
+    ///
+    /// If the position is in a chunk that is not loaded,
+    /// this method returns [`Light::SKY`].
+    pub fn light(&self, position: IVec3) -> Light
+    {
+        self.loaded_light(position).unwrap_or(Light::SKY)
+    }
+
+    /// Insert a chunk into the world.
+    ///
+    /// The light levels of the chunk are computed,
+    /// and light is propagated into and out of adjacent chunks.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a chunk is already loaded at the chunk position.
+    pub fn insert_chunk(&mut self, chunk_position: IVec3, mut chunk: Chunk)
+    {
+        assert!(!self.chunks.contains_key(&chunk_position));
+        chunk.clear_light();
+        self.chunks.insert(chunk_position, chunk);
+        self.light_inserted_chunk(chunk_position);
+    }
+
+    /// Replace the block at a position in the world.
+    ///
+    /// Light levels are updated incrementally,
+    /// including in adjacent chunks.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the position is in a chunk that is not loaded.
+    pub fn set_block(&mut self, position: IVec3, block: Block)
+    {
+        let (chunk_position, local) = split_position(position);
+        let chunk = self.chunks.get_mut(&chunk_position)
+            .expect("Chunk is not loaded");
+        chunk.set_block(local, block);
+        self.light_changed_block(position);
+    }
+
+    /// The light level at a position, if it is in a loaded chunk.
+    pub(super) fn loaded_light(&self, position: IVec3) -> Option<Light>
+    {
+        let (chunk_position, local) = split_position(position);
+        self.chunk(chunk_position).map(|chunk| chunk.light(local))
+    }
+
+    /// Set the light level at a position in a loaded chunk.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the position is in a chunk that is not loaded.
+    pub(super) fn set_light(&mut self, position: IVec3, light: Light)
+    {
+        let (chunk_position, local) = split_position(position);
+        let chunk = self.chunks.get_mut(&chunk_position)
+            .expect("Chunk is not loaded");
+        chunk.set_light(local, light);
+    }
+}
+
+impl Default for World
+{
+    fn default() -> Self
+    {
+        Self::new()
+    }
+}
+
+/// Split a position in the world into a chunk position
+/// and a position relative to that chunk.
+pub fn split_position(position: IVec3) -> (IVec3, IVec3)
+{
+    let chunk_size = IVec3::splat(CHUNK_SIZE);
+    let chunk_position = IVec3::new(
+        position.x.div_euclid(CHUNK_SIZE),
+        position.y.div_euclid(CHUNK_SIZE),
+        position.z.div_euclid(CHUNK_SIZE),
+    );
+    (chunk_position, position - chunk_position * chunk_size)
+}
@@ -0,0 +1,233 @@
+//! Golden-image tests for the rendering pipelines.
+//!
+//! Each scene is rendered into an offscreen framebuffer
+//! and compared against a reference image in `tests/golden`.
+//! Software renderers are not bit-exact across versions,
+//! so small differences in color are tolerated.
+//!
+//! To create or update the reference images after an intended change,
+//! run the tests with `BLOK_BLESS=1` and review the new images.
+//! When a test fails, the rendered image is written next to the reference
+//! with the extension `.actual.png`, for comparison.
+
+#![cfg(target_os = "linux")]
+
+use anyhow::{Result, bail};
+use blok::{
+    client::graphics::{
+        GlBuffer,
+        GlContext,
+        generic,
+        image::Image,
+        offscreen::{OffscreenContext, OffscreenFramebuffer},
+        renderer::{Camera, Renderer, Scene},
+        shadow::Perspective,
+        sky::DayNightCycle,
+        trivial_block,
+    },
+    server::generate_world,
+    state::Tick,
+};
+use glam::{Mat4, Vec3, ivec2, ivec3, vec2, vec3, vec4};
+use opengl::gl;
+use std::{env, f32::consts::PI, path::PathBuf};
+
+/// Size of the rendered images, in pixels.
+const WIDTH: u32 = 160;
+const HEIGHT: u32 = 120;
+
+/// Largest difference in any color channel that is not a failure.
+const TOLERANCE: u8 = 4;
+
+/// Resolution of the shadow map.
+const SHADOW_RESOLUTION: u32 = 512;
+
+/// Function that renders a scene.
+type RenderScene = fn(&GlContext) -> Result<Image>;
+
+/// All scenes are rendered from a single test,
+/// because only one offscreen context exists at a time,
+/// so separate tests would wait for each other anyway.
+#[test]
+fn golden_images()
+{
+    let context = OffscreenContext::new().unwrap();
+
+    let scenes: &[(&str, RenderScene)] = &[
+        ("generic_triangle", render_generic_triangle),
+        ("generic_skinned", render_generic_skinned),
+        ("trivial_blocks_noon", |c| render_trivial_blocks(c, Tick(900))),
+        ("trivial_blocks_night", |c| render_trivial_blocks(c, Tick(2700))),
+    ];
+
+    let failures: Vec<_> =
+        scenes.iter()
+        .filter_map(|(name, render)| {
+            let result =
+                render(context.gl()).and_then(|image| check(name, &image));
+            result.err().map(|err| format!("{}: {:#}", name, err))
+        })
+        .collect();
+
+    assert!(failures.is_empty(), "\n{}\n", failures.join("\n"));
+}
+
+/// Compare an image against its reference image.
+///
+/// A missing reference image is a failure,
+/// unless `BLOK_BLESS=1` asks for the rendered image to become the reference.
+fn check(name: &str, image: &Image) -> Result<()>
+{
+    let golden_dir =
+        PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/golden");
+    let reference_path = golden_dir.join(format!("{}.png", name));
+    let actual_path = golden_dir.join(format!("{}.actual.png", name));
+
+    if matches!(env::var("BLOK_BLESS"), Ok(bless) if bless == "1") {
+        return image.write_png(&reference_path);
+    }
+
+    if !reference_path.exists() {
+        image.write_png(&actual_path)?;
+        bail!("No reference image; run with BLOK_BLESS=1 to create it");
+    }
+
+    let reference = Image::read_png(&reference_path)?;
+    match image.max_difference(&reference) {
+        Some(difference) if difference <= TOLERANCE => Ok(()),
+        Some(difference) => {
+            image.write_png(&actual_path)?;
+            bail!("Image differs from reference by {}; see {}",
+                  difference, actual_path.display());
+        },
+        None => {
+            image.write_png(&actual_path)?;
+            bail!("Image differs in size from reference; see {}",
+                  actual_path.display());
+        },
+    }
+}
+
+/// Render a scene from a fixed camera and read back the result.
+fn draw(context: &GlContext, scene: &Scene) -> Result<Image>
+{
+    let mut renderer = Renderer::new(context, SHADOW_RESOLUTION, ivec2(16, 8))?;
+    let target = OffscreenFramebuffer::new(context, WIDTH as _, HEIGHT as _)?;
+
+    let camera = Camera{
+        v_matrix: Mat4::look_at_rh(
+            /* eye    */ Vec3::new(-4.0, -8.0, 10.0),
+            /* center */ Vec3::new(6.0, 6.0, 1.0),
+            /* up     */ Vec3::new(0.0, 0.0, 1.0),
+        ),
+        perspective: Perspective{
+            fov_y_radians: PI / 4.0,
+            aspect_ratio: WIDTH as f32 / HEIGHT as f32,
+            z_near: 1.0,
+            z_far: 50.0,
+        },
+    };
+
+    renderer.draw(
+        /* target        */ Some(&target.framebuffer),
+        /* viewport_size */ ivec2(WIDTH as _, HEIGHT as _),
+        /* camera        */ &camera,
+        /* scene         */ scene,
+    )?;
+
+    Image::read_pixels(context, Some(&target.framebuffer), WIDTH, HEIGHT)
+}
+
+fn render_generic_triangle(context: &GlContext) -> Result<Image>
+{
+    let vertex = |position, texcoord| generic::Vertex{
+        position,
+        normal: vec3(0.0, 0.0, 1.0),
+        texcoord,
+        bones: [0; 4],
+        weights: vec4(1.0, 0.0, 0.0, 0.0),
+    };
+    let model = generic::Model::new(
+        /* context  */ context,
+        /* vertices */ &[
+            vertex(vec3(2.0, 2.0, 1.0), vec2(0.0, 0.0)),
+            vertex(vec3(10.0, 2.0, 1.0), vec2(1.0, 0.0)),
+            vertex(vec3(6.0, 10.0, 1.0), vec2(0.0, 1.0)),
+        ],
+        /* indices  */ &[0, 1, 2],
+        /* bones    */ 1,
+    )?;
+    let instance = generic::Instance{
+        m_matrix: Mat4::IDENTITY,
+        bone_matrices: [Mat4::IDENTITY; generic::BONES],
+    };
+
+    draw(context, &Scene{
+        lighting: DayNightCycle{ticks_per_day: 3600}.lighting(Tick(900)),
+        generic_models: &[(&model, &[instance])],
+        trivial_block_face_sets: &[],
+    })
+}
+
+/// A strip whose far end is moved sideways by a second bone,
+/// with the middle blended a quarter of the way towards it,
+/// so that the strip bends in the middle.
+fn render_generic_skinned(context: &GlContext) -> Result<Image>
+{
+    let vertex = |x, y, weight| generic::Vertex{
+        position: vec3(x, y, 1.0),
+        normal: vec3(0.0, 0.0, 1.0),
+        texcoord: vec2(0.0, 0.0),
+        bones: [0, 1, 0, 0],
+        weights: vec4(1.0 - weight, weight, 0.0, 0.0),
+    };
+    let model = generic::Model::new(
+        /* context  */ context,
+        /* vertices */ &[
+            vertex(2.0, 4.0, 0.0),
+            vertex(2.0, 8.0, 0.0),
+            vertex(6.0, 4.0, 0.25),
+            vertex(6.0, 8.0, 0.25),
+            vertex(10.0, 4.0, 1.0),
+            vertex(10.0, 8.0, 1.0),
+        ],
+        /* indices  */ &[
+            0, 2, 1,  1, 2, 3,
+            2, 4, 3,  3, 4, 5,
+        ],
+        /* bones    */ 2,
+    )?;
+    let mut bone_matrices = [Mat4::IDENTITY; generic::BONES];
+    bone_matrices[1] = Mat4::from_translation(vec3(0.0, 4.0, 0.0));
+    let instance = generic::Instance{m_matrix: Mat4::IDENTITY, bone_matrices};
+
+    draw(context, &Scene{
+        lighting: DayNightCycle{ticks_per_day: 3600}.lighting(Tick(900)),
+        generic_models: &[(&model, &[instance])],
+        trivial_block_face_sets: &[],
+    })
+}
+
+fn render_trivial_blocks(context: &GlContext, tick: Tick) -> Result<Image>
+{
+    let world = generate_world();
+    let face_set = trivial_block::FaceSet{
+        faces: GlBuffer::new_upload(
+            context,
+            &trivial_block::mesh(&trivial_block::WorldVoxels{
+                world: &world,
+                chunk_position: ivec3(0, 0, 0),
+                texture: |_, face| (face as u16, 0),
+            }),
+            gl::STATIC_DRAW,
+        )?,
+        chunk_position: ivec3(0, 0, 0),
+    };
+
+    draw(context, &Scene{
+        lighting: DayNightCycle{ticks_per_day: 3600}.lighting(tick),
+        generic_models: &[],
+        trivial_block_face_sets: &[face_set],
+    })
+}
@@ -0,0 +1,65 @@
+//! Tests of a client connected to a server in the same process,
+//! as in single-player.
+
+use blok::{
+    client::{interpolation::InterpolationConfig, session::Session},
+    net::transport::MemorySocket,
+    server::{GameServer, generate_world},
+    state::{
+        AbstractInput,
+        Block,
+        PLAYER_SPEED,
+        TICK_DURATION,
+        Tick,
+        Transform,
+    },
+};
+use glam::ivec3;
+use std::time::Instant;
+
+#[test]
+fn single_player_over_memory_sockets()
+{
+    let (client_socket, server_socket) = MemorySocket::pair();
+    let server_address = server_socket.local_addr();
+    let client_address = client_socket.local_addr();
+
+    let mut now = Instant::now();
+    let mut server = GameServer::new(server_socket, 1, generate_world());
+    let mut session = Session::connect(
+        /* socket        */ client_socket,
+        /* server        */ server_address,
+        /* now           */ now,
+        /* interpolation */ InterpolationConfig::default(),
+    );
+
+    let forward = AbstractInput{move_forward: true, ..Default::default()};
+    let moves = 20;
+    let mut arrived = Vec::new();
+    for tick in 0 .. 60 {
+        arrived.extend(session.update(now).unwrap());
+        if session.player().is_some() && tick < moves + 10 && tick >= 10 {
+            session.send_input(Tick(tick), forward).unwrap();
+        }
+        server.tick(now).unwrap();
+        now += TICK_DURATION;
+    }
+
+    // The client joined and received the world.
+    assert!(session.is_connected());
+    let player = session.player().unwrap();
+    assert_eq!(server.player(client_address), Some(player));
+    assert_eq!(arrived, [ivec3(0, 0, 0)]);
+    assert_eq!(session.world().block(ivec3(5, 6, 1)), Block::Torch);
+
+    // The server applied every input exactly once.
+    let start = 2.0;
+    let expected = start + moves as f32 * PLAYER_SPEED;
+    let transform = server.entities().get::<Transform>(player).unwrap();
+    assert_eq!(transform.position.y, expected);
+
+    // The client renders the player where the server has it.
+    let entities = session.entities(now).unwrap();
+    let transform = entities.get::<Transform>(player).unwrap();
+    assert!((transform.position.y - expected).abs() < 0.01);
+}
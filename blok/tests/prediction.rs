@@ -0,0 +1,111 @@
+//! Tests of client-side prediction over a poor connection.
+//!
+//! The client and the server run in the same process,
+//! connected through memory sockets wrapped in simulated sockets,
+//! which measure delays on a simulated clock so that no test sleeps.
+
+use blok::{
+    client::{interpolation::InterpolationConfig, session::Session},
+    net::transport::{
+        LinkConditions,
+        MemorySocket,
+        SimulatedClock,
+        SimulatedSocket,
+    },
+    server::{GameServer, generate_world},
+    state::{AbstractInput, TICK_DURATION, Tick, Transform},
+};
+use std::time::{Duration, Instant};
+
+/// Simulated time between updates of the client.
+const STEP: Duration = Duration::from_millis(10);
+
+/// Input of the player at each tick: moving in several directions,
+/// then standing still so that the server catches up.
+fn input_at(tick: u64) -> AbstractInput
+{
+    let input = AbstractInput::default();
+    match tick {
+        0  .. 40  => AbstractInput{move_forward: true, ..input},
+        40 .. 60  => AbstractInput{strafe_right: true, ..input},
+        60 .. 80  => AbstractInput{
+            move_backward: true,
+            strafe_left: true,
+            ..input
+        },
+        _ => input,
+    }
+}
+
+#[test]
+fn prediction_converges_under_poor_conditions()
+{
+    let conditions = LinkConditions{
+        latency: Duration::from_millis(80),
+        jitter: Duration::from_millis(20),
+        loss: 0.1,
+        duplicate: 0.05,
+        reorder: 0.05,
+    };
+
+    let mut now = Instant::now();
+    let clock = SimulatedClock::new(now);
+    let (client_socket, server_socket) = MemorySocket::pair();
+    let client_address = client_socket.local_addr();
+    let server_address = server_socket.local_addr();
+    let client_socket = SimulatedSocket::new(client_socket, conditions, 1)
+        .with_clock(clock.clone());
+    let server_socket = SimulatedSocket::new(server_socket, conditions, 2)
+        .with_clock(clock.clone());
+
+    let mut server = GameServer::new(server_socket, 1, generate_world());
+    let mut session = Session::connect(
+        /* socket        */ client_socket,
+        /* server        */ server_address,
+        /* now           */ now,
+        /* interpolation */ InterpolationConfig::default(),
+    );
+
+    let steps_per_tick = (TICK_DURATION.as_nanos() / STEP.as_nanos()) as u64;
+    let mut input_tick = 0;
+    let mut max_lead: f32 = 0.0;
+    for step in 0 .. 200 * steps_per_tick {
+        session.update(now).unwrap();
+
+        if step % steps_per_tick == 0 {
+            // Start moving once the player can be predicted.
+            if session.predicted_player().is_some() {
+                let input = input_at(input_tick);
+                session.send_input(Tick(input_tick), input).unwrap();
+                input_tick += 1;
+            }
+            server.tick(now).unwrap();
+
+            // While moving forward, the prediction runs ahead of the server.
+            if let Some(predicted) = session.predicted_player() {
+                let player = session.player().unwrap();
+                let actual = server.entities().get::<Transform>(player);
+                let lead = predicted.position.y - actual.unwrap().position.y;
+                max_lead = max_lead.max(lead);
+            }
+        }
+
+        now += STEP;
+        clock.advance(STEP);
+    }
+
+    assert!(input_tick > 100, "The player did not join in time");
+    assert!(max_lead > 0.5, "The prediction did not run ahead");
+
+    let player = session.player().unwrap();
+    assert_eq!(server.player(client_address), Some(player));
+    let predicted = session.predicted_player().unwrap().position;
+    let actual = server.entities().get::<Transform>(player).unwrap().position;
+    assert!(
+        predicted.distance(actual) < 0.01,
+        "Predicted {} but the server has {}", predicted, actual,
+    );
+
+    // The player did move, so the test is not vacuous.
+    assert!(actual.y > 5.0);
+}
@@ -1,4 +1,11 @@
-use gl_generator::{Api, Fallbacks, GlobalGenerator, Profile, Registry};
+use gl_generator::{
+    Api,
+    Fallbacks,
+    GlobalGenerator,
+    Profile,
+    Registry,
+    StaticGenerator,
+};
 use std::{env, fs::File, path::Path};
 
 fn main()
@@ -12,4 +19,17 @@ fn main()
     Registry::new(Api::Gl, gl_vsn, Profile::Core, Fallbacks::All, gl_exts)
         .write_bindings(GlobalGenerator, &mut file)
         .unwrap();
+
+    // EGL is only used for creating contexts without a display,
+    // which we only need on Linux (for tests on machines without a GPU).
+    let target_os = env::var("CARGO_CFG_TARGET_OS").unwrap();
+    if target_os == "linux" {
+        let egl_vsn = (1, 5);
+        let egl_exts = ["EGL_MESA_platform_surfaceless"];
+
+        let mut file = File::create(Path::new(&dest).join("egl.rs")).unwrap();
+        Registry::new(Api::Egl, egl_vsn, Profile::Core, Fallbacks::All, egl_exts)
+            .write_bindings(StaticGenerator, &mut file)
+            .unwrap();
+    }
 }
@@ -1 +1,26 @@
 pub mod gl { include!(concat!(env!("OUT_DIR"), "/gl.rs")); }
+
+#[cfg(target_os = "linux")]
+#[allow(non_camel_case_types, unused_imports)]
+pub mod egl
+{
+    use std::os::raw::{c_long, c_ulong, c_void};
+
+    // Platform-specific types that the generated bindings refer to.
+    // These are the definitions from eglplatform.h for Linux.
+    pub type khronos_utime_nanoseconds_t = u64;
+    pub type khronos_uint64_t = u64;
+    pub type khronos_ssize_t = c_long;
+    pub type EGLint = i32;
+    pub type EGLNativeDisplayType = *mut c_void;
+    pub type EGLNativePixmapType = c_ulong;
+    pub type EGLNativeWindowType = c_ulong;
+    pub type NativeDisplayType = EGLNativeDisplayType;
+    pub type NativePixmapType = EGLNativePixmapType;
+    pub type NativeWindowType = EGLNativeWindowType;
+
+    #[link(name = "EGL")]
+    extern "system" { }
+
+    include!(concat!(env!("OUT_DIR"), "/egl.rs"));
+}
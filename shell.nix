@@ -37,7 +37,14 @@ in
         # We separate by ; because that is what WINEPATH is split on by Wine.
         LIBRARIES_linux = nixpkgs.lib.concatStringsSep ";" [
             "${nixpkgs.SDL2}/lib"
+            "${nixpkgs.libglvnd}/lib"
         ];
+
+        # Golden-image tests render offscreen through EGL,
+        # which must find Mesa so it can use its software renderer.
+        __EGL_VENDOR_LIBRARY_FILENAMES =
+            "${nixpkgs.mesa.drivers}/share/glvnd/egl_vendor.d/50_mesa.json";
+
         LIBRARIES_windows = nixpkgs.lib.concatStringsSep ";" [
             "${nixpkgs.pkgsCross.mingwW64.SDL2}/bin"
             "${nixpkgs.pkgsCross.mingwW64.windows.pthreads}/lib"